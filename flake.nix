@@ -17,6 +17,7 @@
 	  nativeBuildInputs = [ pkg-config ];
           buildInputs = [ cargo rustc rustfmt rust-analyzer rustup pre-commit rustPackages.clippy # Rust
 			gobject-introspection-unwrapped glib gdk-pixbuf gtk4 pango libxml2 librsvg libadwaita # GTK
+			gst_all_1.gstreamer gst_all_1.gst-plugins-base gst_all_1.gst-plugins-good # Sounds
 			bacon neovim neovide ]; # Me
           RUST_SRC_PATH = rustPlatform.rustLibSrc;
         };
@@ -0,0 +1,91 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// Settings that make the arrows easier to see and the window calmer. High
+// contrast and reduced motion are also taken from the desktop, the settings
+// only turn them on for this program. The arrow theme is kept here as well,
+// the themes are there to make the arrows easier to tell apart.
+
+use adw::prelude::*;
+use std::cell::Cell;
+use strum::IntoEnumIterator;
+
+use crate::arrow_theme::ArrowTheme;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct AccessibilitySettings {
+    pub large_arrows: bool,
+    pub high_contrast: bool,
+    pub reduce_motion: bool,
+    pub arrow_theme: ArrowTheme,
+}
+
+thread_local! {
+    /// The settings that were applied last, for the places that build
+    /// markup instead of using CSS
+    static APPLIED: Cell<AccessibilitySettings> = Cell::new(AccessibilitySettings::default());
+}
+
+/// The settings that were applied last
+pub fn get_settings() -> AccessibilitySettings {
+    APPLIED.with(Cell::get)
+}
+
+/// Whether the arrows should have high contrast, because of the setting or
+/// the desktop
+pub fn is_high_contrast() -> bool {
+    APPLIED.with(Cell::get).high_contrast || adw::StyleManager::default().is_high_contrast()
+}
+
+/// How the keys of keycodes are drawn
+pub fn get_arrow_theme() -> ArrowTheme {
+    APPLIED.with(Cell::get).arrow_theme
+}
+
+/// Whether things should move as little as possible, because of the setting
+/// or the desktop
+pub fn is_motion_reduced() -> bool {
+    APPLIED.with(Cell::get).reduce_motion
+        || gtk::Settings::default().is_some_and(|settings| !settings.is_gtk_enable_animations())
+}
+
+/// Applies the last settings again, for when the desktop changed
+pub fn reapply(widget: &impl IsA<gtk::Widget>) {
+    apply(APPLIED.with(Cell::get), widget);
+}
+
+/// Styles the window of the widget with the settings. The window's CSS
+/// classes switch the arrow styles, turning animations off is left to GTK.
+pub fn apply(settings: AccessibilitySettings, widget: &impl IsA<gtk::Widget>) {
+    APPLIED.with(|applied| applied.set(settings));
+    if let Some(root) = widget.root() {
+        let set_class = |class: &str, on: bool| match on {
+            true => root.add_css_class(class),
+            false => root.remove_css_class(class),
+        };
+        set_class("large-arrows", settings.large_arrows);
+        set_class("high-contrast-arrows", is_high_contrast());
+        for theme in ArrowTheme::iter() {
+            set_class(theme.get_css_class(), theme == settings.arrow_theme);
+        }
+    }
+    if let Some(gtk_settings) = gtk::Settings::default() {
+        if settings.reduce_motion {
+            gtk_settings.set_gtk_enable_animations(false);
+        } else {
+            // Back to what the desktop wants
+            gtk_settings.reset_property("gtk-enable-animations");
+        }
+    }
+}
@@ -0,0 +1,119 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// The ways the keys of a keycode can be drawn. The symbols are chosen here,
+// colors and sizes come from the stylesheet through the theme's CSS class on
+// the window.
+
+use crate::db;
+
+#[derive(Debug, Default, PartialEq, Eq, strum_macros::Display, strum_macros::EnumIter,
+         Copy, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArrowTheme {
+    #[default]
+    #[strum(to_string = "Arrows")]
+    Classic,
+    /// Yellow like the arrows in the game
+    #[strum(to_string = "Game-style yellow")]
+    Game,
+    #[strum(to_string = "Outlined arrows")]
+    Outlined,
+    #[strum(to_string = "Letters U D L R")]
+    Letters,
+    #[strum(to_string = "WASD keycaps")]
+    Keycaps,
+}
+
+impl ArrowTheme {
+    pub fn get_symbol(&self, code: db::Code) -> &'static str {
+        use db::Code::*;
+        match (self, code) {
+            (ArrowTheme::Classic | ArrowTheme::Game, code) => match code {
+                Down => "↓",
+                Left => "←",
+                Right => "→",
+                Up => "↑",
+            },
+            (ArrowTheme::Outlined, code) => match code {
+                Down => "⇩",
+                Left => "⇦",
+                Right => "⇨",
+                Up => "⇧",
+            },
+            (ArrowTheme::Letters, code) => match code {
+                Down => "D",
+                Left => "L",
+                Right => "R",
+                Up => "U",
+            },
+            (ArrowTheme::Keycaps, code) => match code {
+                Down => "S",
+                Left => "A",
+                Right => "D",
+                Up => "W",
+            },
+        }
+    }
+
+    /// Letters are hard to read without space between them
+    pub fn get_separator(&self) -> &'static str {
+        match self {
+            ArrowTheme::Letters | ArrowTheme::Keycaps => " ",
+            _ => "",
+        }
+    }
+
+    /// The name the theme is saved with
+    pub fn get_id(&self) -> &'static str {
+        match self {
+            ArrowTheme::Classic => "classic",
+            ArrowTheme::Game => "game",
+            ArrowTheme::Outlined => "outlined",
+            ArrowTheme::Letters => "letters",
+            ArrowTheme::Keycaps => "keycaps",
+        }
+    }
+
+    /// Set on the window, so the stylesheet can style the arrows per theme
+    pub fn get_css_class(&self) -> &'static str {
+        match self {
+            ArrowTheme::Classic => "arrow-theme-classic",
+            ArrowTheme::Game => "arrow-theme-game",
+            ArrowTheme::Outlined => "arrow-theme-outlined",
+            ArrowTheme::Letters => "arrow-theme-letters",
+            ArrowTheme::Keycaps => "arrow-theme-keycaps",
+        }
+    }
+
+    /// The keycode as plain text
+    pub fn get_text(&self, keycode: &[db::Code]) -> String {
+        let symbols: Vec<&str> = keycode.iter().map(|code| self.get_symbol(*code)).collect();
+        symbols.join(self.get_separator())
+    }
+
+    /// One key as Pango markup, drawn with the span attributes
+    pub fn get_key_markup(&self, code: db::Code, attributes: &str) -> String {
+        let symbol = self.get_symbol(code);
+        let key = match attributes.is_empty() {
+            true => symbol.to_string(),
+            false => format!("<span {attributes}>{symbol}</span>"),
+        };
+        match self {
+            // Pango can't draw borders, a dark background has to do
+            ArrowTheme::Keycaps =>
+                format!("<span background=\"#5e5c64\" foreground=\"white\"> {key} </span>"),
+            _ => key,
+        }
+    }
+}
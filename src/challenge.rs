@@ -0,0 +1,241 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// Rolls random loadouts for challenge runs. The same rules and seed always
+// give the same loadout, so a squad can share a seed instead of a loadout.
+
+use gtk::glib;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::str::FromStr;
+use strum::IntoEnumIterator;
+
+use crate::db;
+use crate::loadout;
+use crate::picker;
+
+/// What a rolled loadout has to look like. A loadout never has the same
+/// Stratagem twice, no matter the rules.
+#[derive(Debug, Copy, Clone)]
+pub struct ChallengeRules {
+    /// At least one Stratagem that can deal with tanks
+    pub require_anti_tank: bool,
+    /// At most one backpack, since you can only wear one anyway
+    pub single_backpack: bool,
+    /// At most one support weapon, since you can only carry one anyway
+    pub single_support_weapon: bool,
+    /// Warbond Stratagems are unlocked and may be rolled
+    pub warbond: bool,
+    /// Only roll Stratagems from this ship module
+    pub department: Option<db::Department>,
+    /// Only roll Stratagems a player of this level can have bought
+    pub player_level: Option<u32>,
+}
+
+impl Default for ChallengeRules {
+    fn default() -> Self {
+        ChallengeRules {
+            require_anti_tank: true,
+            single_backpack: true,
+            single_support_weapon: true,
+            warbond: true,
+            department: None,
+            player_level: None,
+        }
+    }
+}
+
+impl ChallengeRules {
+    /// Returns whether the Stratagem may be rolled at all
+    fn allows(&self, stratagem: &db::Stratagem) -> bool {
+        // Mission objectives and the general Stratagems can't be brought
+        stratagem.get_category() != db::Category::General
+            && stratagem.is_in_patch(db::GamePatch::latest())
+            && (self.warbond || stratagem.get_availability() != db::Availability::Warbond)
+            && self.department.is_none_or(|d| stratagem.get_department() == Some(d))
+            && self.player_level.is_none_or(|level| stratagem.get_unlock_level() <= level)
+    }
+
+    /// Returns whether the Stratagem can still be added to the loadout
+    fn fits(&self, loadout: &loadout::Loadout, stratagem: &db::Stratagem) -> bool {
+        // Some support weapons need the backpack slot as well
+        let is_free = |limited: bool, uses_slot: fn(&db::Stratagem) -> bool|
+            !limited || !uses_slot(stratagem) || !loadout.stratagems.iter().any(uses_slot);
+        is_free(self.single_backpack, db::Stratagem::uses_backpack_slot)
+            && is_free(self.single_support_weapon, db::Stratagem::uses_support_weapon_slot)
+    }
+
+    /// Rolls a full loadout from the given seed. Fails if the rules don't
+    /// leave enough Stratagems to fill all slots.
+    pub fn generate(&self, seed: u64) -> Result<loadout::Loadout, String> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let mut candidates: Vec<db::Stratagem> = db::Stratagem::iter()
+            .filter(|s| self.allows(s))
+            .collect();
+        candidates.shuffle(&mut rng);
+
+        let mut challenge = loadout::Loadout::new(&format!("Challenge {seed}"));
+        if self.require_anti_tank {
+            let anti_tank = candidates.iter()
+                .find(|s| s.get_roles().contains(&db::Role::AntiTank))
+                .ok_or("No anti-tank Stratagem matches the rules")?;
+            challenge.add(*anti_tank);
+        }
+        for stratagem in candidates {
+            if challenge.stratagems.len() == loadout::LOADOUT_SIZE {
+                break;
+            }
+            if self.fits(&challenge, &stratagem) {
+                challenge.add(stratagem);
+            }
+        }
+        if challenge.stratagems.len() < loadout::LOADOUT_SIZE {
+            return Err(format!("Only {} Stratagems match the rules",
+                               challenge.stratagems.len()));
+        }
+        Ok(challenge)
+    }
+}
+
+const CLI_USAGE: &str = "Usage: hd2helper challenge [OPTIONS]
+
+Rolls a random loadout and prints it.
+
+Options:
+  --seed <SEED>              Roll the loadout for this seed
+  --department <DEPARTMENT>  Only roll Stratagems from this ship module
+  --level <LEVEL>            Only roll Stratagems unlocked at this player level
+  --no-anti-tank             Don't require an anti-tank Stratagem
+  --any-backpacks            Allow more than one backpack
+  --any-support-weapons      Allow more than one support weapon
+  --no-warbond               Only roll Stratagems that don't need a warbond
+  --help                     Show this help";
+
+/// Runs `hd2helper challenge` without starting the GUI. `args` are the
+/// arguments after `challenge`.
+pub fn run_cli(args: &[String]) -> glib::ExitCode {
+    let mut rules = ChallengeRules::default();
+    let mut seed = picker::random_seed();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => match args.next().map(|s| s.parse()) {
+                Some(Ok(value)) => seed = value,
+                _ => return cli_error("--seed needs a number"),
+            },
+            "--department" => match args.next().map(|s| db::Department::from_str(s)) {
+                Some(Ok(department)) => rules.department = Some(department),
+                _ => {
+                    let known: Vec<String> = db::Department::iter()
+                        .map(|d| format!("{d:?}"))
+                        .collect();
+                    return cli_error(&format!("--department needs one of {}", known.join(", ")));
+                }
+            },
+            "--level" => match args.next().map(|s| s.parse()) {
+                Some(Ok(value)) => rules.player_level = Some(value),
+                _ => return cli_error("--level needs a number"),
+            },
+            "--no-anti-tank" => rules.require_anti_tank = false,
+            "--any-backpacks" => rules.single_backpack = false,
+            "--any-support-weapons" => rules.single_support_weapon = false,
+            "--no-warbond" => rules.warbond = false,
+            "--help" => {
+                println!("{CLI_USAGE}");
+                return glib::ExitCode::SUCCESS;
+            }
+            _ => return cli_error(&format!("Unknown option {arg}")),
+        }
+    }
+
+    match rules.generate(seed) {
+        Ok(challenge) => {
+            println!("Seed {seed}");
+            for stratagem in challenge.stratagems {
+                println!("  {stratagem:<32} {}", crate::get_keycode_str(&stratagem.get_keycode()));
+            }
+            glib::ExitCode::SUCCESS
+        }
+        Err(e) => cli_error(&e),
+    }
+}
+
+fn cli_error(message: &str) -> glib::ExitCode {
+    eprintln!("{message}\n\n{CLI_USAGE}");
+    glib::ExitCode::FAILURE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(loadout: &loadout::Loadout, uses_slot: fn(&db::Stratagem) -> bool) -> usize {
+        loadout.stratagems.iter().filter(|s| uses_slot(s)).count()
+    }
+
+    #[test]
+    fn rules_hold() {
+        let rules = ChallengeRules::default();
+        for seed in 0..200 {
+            let challenge = rules.generate(seed).unwrap();
+            let stratagems = &challenge.stratagems;
+            assert_eq!(stratagems.len(), loadout::LOADOUT_SIZE);
+            assert!(stratagems.iter().all(|s| rules.allows(s)));
+            assert!(stratagems.iter().enumerate().all(|(i, s)| !stratagems[..i].contains(s)));
+            assert!(stratagems.iter().any(|s| s.get_roles().contains(&db::Role::AntiTank)));
+            assert!(count(&challenge, db::Stratagem::uses_backpack_slot) <= 1);
+            assert!(count(&challenge, db::Stratagem::uses_support_weapon_slot) <= 1);
+        }
+    }
+
+    #[test]
+    fn narrowed_rules() {
+        let rules = ChallengeRules {
+            warbond: false,
+            player_level: Some(20),
+            ..Default::default()
+        };
+        for seed in 0..50 {
+            assert!(rules.generate(seed).unwrap().stratagems.iter().all(|s| {
+                s.get_availability() != db::Availability::Warbond && s.get_unlock_level() <= 20
+            }));
+        }
+        let rules = ChallengeRules {
+            require_anti_tank: false,
+            department: Some(db::Department::Bridge),
+            ..Default::default()
+        };
+        let challenge = rules.generate(7).unwrap();
+        assert!(challenge.stratagems.iter().all(|s| s.get_department() == Some(db::Department::Bridge)));
+    }
+
+    #[test]
+    fn same_seed_same_loadout() {
+        let rules = ChallengeRules::default();
+        assert_eq!(rules.generate(7), rules.generate(7));
+        assert_eq!(rules.generate(7).unwrap().name, "Challenge 7");
+        let rolled: Vec<loadout::Loadout> = (0..10).map(|seed| rules.generate(seed).unwrap()).collect();
+        assert!(rolled.iter().any(|l| l.stratagems != rolled[0].stratagems));
+    }
+
+    #[test]
+    fn not_enough_stratagems() {
+        let rules = ChallengeRules {
+            warbond: false,
+            player_level: Some(0),
+            ..Default::default()
+        };
+        assert!(db::Stratagem::iter().filter(|s| rules.allows(s)).count() < loadout::LOADOUT_SIZE);
+        assert!(rules.generate(7).is_err());
+    }
+}
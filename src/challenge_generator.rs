@@ -0,0 +1,267 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// The challenge page rolls random loadouts with the rules from challenge.rs,
+// so they can be trained or saved right away.
+
+use adw::prelude::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+use strum::IntoEnumIterator;
+
+use crate::challenge;
+use crate::db;
+use crate::gui_templates;
+use crate::loadout;
+use crate::picker;
+use crate::ship;
+
+/// The widgets and state of the challenge page. A clone is just another
+/// handle to the same page.
+#[derive(Clone)]
+struct ChallengeGenerator {
+    anti_tank: adw::SwitchRow,
+    single_backpack: adw::SwitchRow,
+    single_support_weapon: adw::SwitchRow,
+    warbond: adw::SwitchRow,
+    /// The first entry is "Any", the rest are the departments in order
+    department: adw::ComboRow,
+    /// Limits the Stratagems to a player level while its switch is on
+    level_limit: adw::ExpanderRow,
+    player_level: adw::SpinRow,
+    seed_entry: adw::EntryRow,
+    /// The last loadout that was rolled successfully
+    result: Rc<RefCell<Option<loadout::Loadout>>>,
+    result_list: gtk::ListBox,
+    toasts: adw::ToastOverlay,
+    on_train: Rc<dyn Fn(loadout::Loadout)>,
+}
+
+pub fn create_challenge_page(on_train: Rc<dyn Fn(loadout::Loadout)>) -> adw::NavigationPage {
+    let defaults = challenge::ChallengeRules::default();
+    let switch = |title: &str, active: bool| adw::SwitchRow::builder()
+        .title(title)
+        .active(active)
+        .build();
+    let mut departments = vec!("Any".to_string());
+    departments.extend(db::Department::iter().map(|d| d.to_string()));
+    let departments: Vec<&str> = departments.iter().map(String::as_str).collect();
+
+    let generator = ChallengeGenerator {
+        anti_tank: switch("At least one anti-tank Stratagem", defaults.require_anti_tank),
+        single_backpack: switch("At most one backpack", defaults.single_backpack),
+        single_support_weapon: switch("At most one support weapon", defaults.single_support_weapon),
+        warbond: switch("Warbond Stratagems are unlocked", defaults.warbond),
+        department: adw::ComboRow::builder()
+            .title("Only from department")
+            .model(&gtk::StringList::new(&departments))
+            .build(),
+        level_limit: adw::ExpanderRow::builder()
+            .title("Only unlocked Stratagems")
+            .subtitle("Leave out what a player of this level can't have bought yet")
+            .show_enable_switch(true)
+            .enable_expansion(false)
+            .build(),
+        player_level: adw::SpinRow::builder()
+            .title("Player level")
+            .adjustment(&gtk::Adjustment::new(1.0, 1.0, 150.0, 1.0, 10.0, 0.0))
+            .build(),
+        seed_entry: adw::EntryRow::builder()
+            .title("Seed")
+            .show_apply_button(true)
+            .input_purpose(gtk::InputPurpose::Digits)
+            .build(),
+        result: Rc::new(RefCell::new(None)),
+        result_list: gtk::ListBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .css_classes(*&["boxed-list"])
+            .build(),
+        toasts: adw::ToastOverlay::new(),
+        on_train,
+    };
+
+    let rules_list = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .css_classes(*&["boxed-list"])
+        .build();
+    rules_list.append(&generator.anti_tank);
+    rules_list.append(&generator.single_backpack);
+    rules_list.append(&generator.single_support_weapon);
+    rules_list.append(&generator.warbond);
+    rules_list.append(&generator.department);
+    generator.level_limit.add_row(&generator.player_level);
+    rules_list.append(&generator.level_limit);
+    // Changing a rule rolls again with the same seed
+    for rule in [&generator.anti_tank, &generator.single_backpack,
+                 &generator.single_support_weapon, &generator.warbond] {
+        let generator = generator.clone();
+        rule.connect_active_notify(move |_| generator.roll());
+    }
+    let department_generator = generator.clone();
+    generator.department.connect_selected_notify(move |_| department_generator.roll());
+    let level_generator = generator.clone();
+    generator.level_limit.connect_enable_expansion_notify(move |_| level_generator.roll());
+    let level_generator = generator.clone();
+    generator.player_level.connect_value_notify(move |_| level_generator.roll());
+    let rules_group = adw::PreferencesGroup::builder()
+        .title("Rules")
+        .build();
+    rules_group.add(&rules_list);
+
+    let roll_button = gtk::Button::builder()
+        .label("Roll")
+        .css_classes(*&["suggested-action"])
+        .valign(gtk::Align::Center)
+        .build();
+    let roll_generator = generator.clone();
+    roll_button.connect_clicked(move |_| {
+        roll_generator.seed_entry.set_text(&picker::random_seed().to_string());
+        roll_generator.roll();
+    });
+    let apply_generator = generator.clone();
+    generator.seed_entry.connect_apply(move |_| apply_generator.roll());
+    let seed_list = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .css_classes(*&["boxed-list"])
+        .build();
+    seed_list.append(&generator.seed_entry);
+    let seed_group = adw::PreferencesGroup::builder()
+        .title("Seed")
+        .description("Share the seed and the rules to give everyone the same loadout")
+        .header_suffix(&roll_button)
+        .build();
+    seed_group.add(&seed_list);
+
+    let result_buttons = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+    let train_button = gtk::Button::builder()
+        .label("Train")
+        .valign(gtk::Align::Center)
+        .build();
+    let train_generator = generator.clone();
+    train_button.connect_clicked(move |_| {
+        let result = train_generator.result.borrow().clone();
+        if let Some(result) = result {
+            (train_generator.on_train)(result);
+        }
+    });
+    let save_button = gtk::Button::builder()
+        .label("Save")
+        .valign(gtk::Align::Center)
+        .build();
+    let save_generator = generator.clone();
+    save_button.connect_clicked(move |_| save_generator.save());
+    result_buttons.append(&train_button);
+    result_buttons.append(&save_button);
+    let result_group = adw::PreferencesGroup::builder()
+        .title("Loadout")
+        .header_suffix(&result_buttons)
+        .build();
+    result_group.add(&generator.result_list);
+
+    // Start out with something to look at
+    generator.seed_entry.set_text(&picker::random_seed().to_string());
+    generator.roll();
+
+    let content = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(18)
+        .margin_top(12)
+        .margin_bottom(12)
+        .build();
+    content.append(&rules_group);
+    content.append(&seed_group);
+    content.append(&result_group);
+    generator.toasts.set_child(Some(&gui_templates::create_scrolled_content(&content)));
+
+    gui_templates::create_titled_page("Challenge", None, &generator.toasts)
+}
+
+impl ChallengeGenerator {
+    /// Reads the rules from the switches
+    fn get_rules(&self) -> challenge::ChallengeRules {
+        challenge::ChallengeRules {
+            require_anti_tank: self.anti_tank.is_active(),
+            single_backpack: self.single_backpack.is_active(),
+            single_support_weapon: self.single_support_weapon.is_active(),
+            warbond: self.warbond.is_active(),
+            department: match self.department.selected() {
+                0 => None,
+                selected => db::Department::iter().nth(selected as usize - 1),
+            },
+            player_level: self.level_limit.enables_expansion()
+                .then(|| self.player_level.value() as u32),
+        }
+    }
+
+    /// Rolls a loadout for the seed in the entry and shows it
+    fn roll(&self) {
+        self.result_list.remove_all();
+        let seed = match self.seed_entry.text().trim().parse::<u64>() {
+            Ok(seed) => seed,
+            Err(_) => {
+                self.result.replace(None);
+                self.toasts.add_toast(adw::Toast::new("The seed has to be a positive number"));
+                return;
+            }
+        };
+        match self.get_rules().generate(seed) {
+            Ok(result) => {
+                let (upgrades, _) = ship::load_upgrades();
+                for stratagem in result.stratagems.iter() {
+                    let stats = gui_templates::get_stats_summary(
+                        &upgrades.get_effective_stats(stratagem));
+                    let row = adw::ActionRow::builder()
+                        .title(stratagem.to_string())
+                        .subtitle(format!("{}\n{stats}",
+                                          crate::get_keycode_str(&stratagem.get_keycode())))
+                        .build();
+                    row.update_property(&[gtk::accessible::Property::Description(&format!(
+                        "{}. {stats}", gui_templates::describe_keycode(&stratagem.get_keycode())))]);
+                    row.add_prefix(&gui_templates::create_stratagem_icon(stratagem, 40));
+                    self.result_list.append(&row);
+                }
+                self.result.replace(Some(result));
+            }
+            Err(e) => {
+                self.result.replace(None);
+                self.result_list.append(&adw::ActionRow::builder()
+                                        .title(e)
+                                        .build());
+            }
+        }
+    }
+
+    /// Adds the rolled loadout to the saved loadouts. A saved loadout with the
+    /// same name is kept, the rolled one gets a number. Nothing is saved while
+    /// the saved loadouts can't all be read, as that would drop them.
+    fn save(&self) {
+        let Some(mut result) = self.result.borrow().clone() else {
+            return;
+        };
+        let (mut saved, problems) = loadout::load_loadouts();
+        if !problems.is_empty() {
+            for problem in problems {
+                self.toasts.add_toast(adw::Toast::new(&problem));
+            }
+            self.toasts.add_toast(adw::Toast::new("The loadout was not saved"));
+            return;
+        }
+        let name = loadout::get_free_name(&result.name, &saved);
+        result.name = name.clone();
+        saved.push(result);
+        match loadout::save_loadouts(&saved) {
+            Ok(()) => self.toasts.add_toast(adw::Toast::new(&format!("Saved {name}"))),
+            Err(e) => self.toasts.add_toast(adw::Toast::new(&format!("Could not save loadouts: {e}"))),
+        }
+    }
+}
@@ -0,0 +1,154 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// A simple bar chart drawn with cairo, one bar per day or week.
+
+use gtk::prelude::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Space below the bars for their labels, in pixels
+const LABEL_HEIGHT: f64 = 18.0;
+/// Space above the bars for the highest value, in pixels
+const TOP_MARGIN: f64 = 16.0;
+/// Labels are left out if they would be closer than this, in pixels
+const MIN_LABEL_DISTANCE: f64 = 48.0;
+/// The blue of Adwaita
+const BAR_COLOR: (f64, f64, f64) = (0.21, 0.52, 0.89);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bar {
+    /// Shown below the bar
+    pub label: String,
+    /// None leaves a gap, for periods without data
+    pub value: Option<f64>,
+}
+
+/// The drawing area and its bars. A clone is just another handle to the same
+/// chart.
+#[derive(Clone)]
+pub struct BarChart {
+    pub area: gtk::DrawingArea,
+    bars: Rc<RefCell<Vec<Bar>>>,
+}
+
+impl BarChart {
+    /// `max` is the value of a full bar, None scales to the highest bar.
+    /// `format` turns values into text for the scale and the tooltips.
+    pub fn new(max: Option<f64>, format: fn(f64) -> String) -> Self {
+        let chart = BarChart {
+            area: gtk::DrawingArea::builder()
+                .content_height(160)
+                .hexpand(true)
+                .has_tooltip(true)
+                .build(),
+            bars: Rc::new(RefCell::new(Vec::new())),
+        };
+
+        let bars = chart.bars.clone();
+        chart.area.set_draw_func(move |area, cr, width, height| {
+            // Drawing can only fail if cairo is in an error state already,
+            // there is nothing left to do then
+            let _ = draw(area, cr, width as f64, height as f64, &bars.borrow(), max, format);
+        });
+
+        let bars = chart.bars.clone();
+        chart.area.connect_query_tooltip(move |area, x, _, _, tooltip| {
+            let bars = bars.borrow();
+            if bars.is_empty() {
+                return false;
+            }
+            let index = (x as f64 / area.width() as f64 * bars.len() as f64) as usize;
+            match bars.get(index) {
+                Some(bar) => {
+                    let value = bar.value.map_or("No data".to_string(), format);
+                    tooltip.set_text(Some(&format!("{}: {value}", bar.label)));
+                    true
+                }
+                None => false,
+            }
+        });
+        chart
+    }
+
+    pub fn set_bars(&self, bars: Vec<Bar>) {
+        self.bars.replace(bars);
+        self.area.queue_draw();
+    }
+}
+
+fn draw(area: &gtk::DrawingArea,
+        cr: &gtk::cairo::Context,
+        width: f64,
+        height: f64,
+        bars: &[Bar],
+        max: Option<f64>,
+        format: fn(f64) -> String)
+        -> Result<(), gtk::cairo::Error> {
+    let foreground = area.color();
+    let set_foreground = |alpha: f64| cr.set_source_rgba(
+        foreground.red() as f64, foreground.green() as f64, foreground.blue() as f64,
+        foreground.alpha() as f64 * alpha);
+    cr.set_font_size(11.0);
+
+    if bars.is_empty() {
+        set_foreground(0.55);
+        let text = "No attempts yet";
+        let extents = cr.text_extents(text)?;
+        cr.move_to((width - extents.width()) / 2.0, height / 2.0);
+        return cr.show_text(text);
+    }
+
+    let highest = bars.iter().filter_map(|b| b.value).fold(0.0, f64::max);
+    let max = max.unwrap_or(highest).max(f64::EPSILON);
+    let chart_height = height - LABEL_HEIGHT - TOP_MARGIN;
+    let slot = width / bars.len() as f64;
+
+    // The scale: a line at the top for the full value and the baseline
+    set_foreground(0.2);
+    cr.set_line_width(1.0);
+    for y in [TOP_MARGIN, TOP_MARGIN + chart_height] {
+        cr.move_to(0.0, y.floor() + 0.5);
+        cr.line_to(width, y.floor() + 0.5);
+    }
+    cr.stroke()?;
+    set_foreground(0.55);
+    cr.move_to(2.0, TOP_MARGIN - 4.0);
+    cr.show_text(&format(max))?;
+
+    let (red, green, blue) = BAR_COLOR;
+    cr.set_source_rgb(red, green, blue);
+    for (index, bar) in bars.iter().enumerate() {
+        let Some(value) = bar.value else {
+            continue;
+        };
+        let bar_height = (value / max).clamp(0.0, 1.0) * chart_height;
+        cr.rectangle(index as f64 * slot + slot * 0.15,
+                     TOP_MARGIN + chart_height - bar_height,
+                     slot * 0.7,
+                     bar_height);
+    }
+    cr.fill()?;
+
+    // Only as many labels as fit next to each other
+    set_foreground(0.7);
+    let every = (MIN_LABEL_DISTANCE / slot).ceil().max(1.0) as usize;
+    for (index, bar) in bars.iter().enumerate().rev().step_by(every) {
+        let extents = cr.text_extents(&bar.label)?;
+        let x = (index as f64 * slot + (slot - extents.width()) / 2.0)
+            .clamp(0.0, (width - extents.width()).max(0.0));
+        cr.move_to(x, height - 4.0);
+        cr.show_text(&bar.label)?;
+    }
+    Ok(())
+}
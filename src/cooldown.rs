@@ -0,0 +1,98 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// Keeps track of the cooldowns of a loadout during a mission. The player
+// tells it when a Stratagem was called in, it counts down from there.
+
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::db;
+use crate::loadout;
+use crate::ship;
+
+#[derive(Debug, Clone)]
+pub struct CooldownSlot {
+    pub stratagem: db::Stratagem,
+    /// With the ship upgrades applied
+    pub cooldown: Duration,
+    /// None if the Stratagem can be used as often as the cooldown allows
+    pub uses_left: Option<u32>,
+    /// When the Stratagem was last called in
+    called_at: Option<Instant>,
+}
+
+/// The cooldowns of every Stratagem in a loadout
+#[derive(Debug, Clone)]
+pub struct CooldownTracker {
+    pub slots: Vec<CooldownSlot>,
+}
+
+impl CooldownTracker {
+    pub fn new(loadout: &loadout::Loadout, upgrades: &ship::ShipUpgrades) -> Self {
+        CooldownTracker {
+            slots: loadout.stratagems.iter()
+                .map(|stratagem| {
+                    let stats = upgrades.get_effective_stats(stratagem);
+                    CooldownSlot {
+                        stratagem: *stratagem,
+                        cooldown: stats.cooldown,
+                        uses_left: stats.uses,
+                        called_at: None,
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// Records that the Stratagem in the slot was called in. Returns false
+    /// and changes nothing if it couldn't have been, because it is still on
+    /// cooldown or has no uses left.
+    pub fn call_in(&mut self, slot: usize, now: Instant) -> bool {
+        if !self.is_ready(slot, now) {
+            return false;
+        }
+        let Some(slot) = self.slots.get_mut(slot) else {
+            return false;
+        };
+        slot.called_at = Some(now);
+        slot.uses_left = slot.uses_left.map(|uses| uses - 1);
+        true
+    }
+
+    /// How long the Stratagem in the slot is still on cooldown
+    pub fn get_remaining(&self, slot: usize, now: Instant) -> Duration {
+        match self.slots.get(slot) {
+            Some(CooldownSlot { cooldown, called_at: Some(called_at), .. }) =>
+                cooldown.saturating_sub(now.duration_since(*called_at)),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Whether the Stratagem in the slot can be called in right now
+    pub fn is_ready(&self, slot: usize, now: Instant) -> bool {
+        match self.slots.get(slot) {
+            Some(s) => s.uses_left != Some(0) && self.get_remaining(slot, now).is_zero(),
+            None => false,
+        }
+    }
+
+    /// Starts over with every Stratagem ready, like at the start of a
+    /// mission. For Eagles this is also what a rearm does.
+    pub fn reset(&mut self, upgrades: &ship::ShipUpgrades) {
+        for slot in self.slots.iter_mut() {
+            slot.called_at = None;
+            slot.uses_left = upgrades.get_effective_stats(&slot.stratagem).uses;
+        }
+    }
+}
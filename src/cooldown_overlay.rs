@@ -0,0 +1,228 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// A small window to keep next to the game. It shows the Stratagems of a
+// loadout and counts down their cooldowns after the slot's hotkey is pressed,
+// either while the window has the focus or as a global hotkey.
+//
+// GTK 4 can't keep a window above others by itself, so the window is kept
+// small and has a title the compositor's "Always on Top" can be used with.
+
+use adw::prelude::*;
+use gtk::gio;
+use gtk::glib;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+use std::time::Instant;
+use strum::IntoEnumIterator;
+
+use crate::cooldown;
+use crate::gui_templates;
+use crate::hotkeys;
+use crate::loadout;
+use crate::ship;
+
+/// How often the countdowns are redrawn
+const REDRAW_INTERVAL: Duration = Duration::from_millis(200);
+
+/// The widgets of one slot in the overlay
+#[derive(Clone)]
+struct SlotWidgets {
+    button: gtk::Button,
+    countdown: gtk::Label,
+}
+
+/// The widgets and state of the overlay. A clone is just another handle to
+/// the same window.
+#[derive(Clone)]
+pub struct CooldownOverlay {
+    tracker: Rc<RefCell<cooldown::CooldownTracker>>,
+    slots: Vec<SlotWidgets>,
+    pub window: adw::Window,
+}
+
+impl CooldownOverlay {
+    /// Creates the overlay for a loadout, with the owned ship upgrades
+    /// applied to the cooldowns. The window still has to be presented.
+    pub fn new(loadout: &loadout::Loadout, application: Option<&gtk::Application>) -> Self {
+        let (upgrades, _) = ship::load_upgrades();
+        let tracker = cooldown::CooldownTracker::new(loadout, &upgrades);
+
+        let slot_box = gtk::Box::builder()
+            .spacing(6)
+            .margin_top(6)
+            .margin_bottom(6)
+            .margin_start(6)
+            .margin_end(6)
+            .halign(gtk::Align::Center)
+            .build();
+        let mut slots = Vec::new();
+        for (index, slot) in tracker.slots.iter().enumerate() {
+            let countdown = gtk::Label::builder()
+                .label("Ready")
+                .css_classes(*&["numeric", "caption"])
+                .build();
+            let content = gtk::Box::new(gtk::Orientation::Vertical, 3);
+            content.append(&gui_templates::create_stratagem_icon(&slot.stratagem, 48));
+            content.append(&gtk::Label::builder()
+                           .label(get_hotkey_label(index))
+                           .css_classes(*&["dim-label", "caption-heading"])
+                           .build());
+            content.append(&countdown);
+            let button = gtk::Button::builder()
+                .child(&content)
+                .tooltip_text(gui_templates::get_stratagem_tooltip(&slot.stratagem, &upgrades))
+                .css_classes(*&["flat"])
+                .build();
+            slot_box.append(&button);
+            slots.push(SlotWidgets { button, countdown });
+        }
+
+        let reset_button = gtk::Button::builder()
+            .icon_name("view-refresh-symbolic")
+            .tooltip_text("New Mission")
+            .build();
+        let header = adw::HeaderBar::builder()
+            .title_widget(&adw::WindowTitle::new(&loadout.name, "Cooldowns"))
+            .css_classes(*&["flat"])
+            .build();
+        header.pack_start(&reset_button);
+        let toolbar = adw::ToolbarView::new();
+        toolbar.add_top_bar(&header);
+        toolbar.set_content(Some(&slot_box));
+
+        let window = adw::Window::builder()
+            .title(format!("{} Cooldowns", loadout.name))
+            .content(&toolbar)
+            .resizable(false)
+            .build();
+        window.set_application(application);
+
+        let overlay = CooldownOverlay {
+            tracker: Rc::new(RefCell::new(tracker)),
+            slots,
+            window,
+        };
+
+        for (index, slot) in overlay.slots.iter().enumerate() {
+            let click_overlay = overlay.clone();
+            slot.button.connect_clicked(move |_| click_overlay.call_in(index));
+        }
+        let reset_overlay = overlay.clone();
+        reset_button.connect_clicked(move |_| reset_overlay.reset());
+
+        // 1 to 4 call in the Stratagem of that slot
+        let key_overlay = overlay.clone();
+        let key_controller = gtk::EventControllerKey::new();
+        key_controller.connect_key_pressed(move |_, key, _, _| {
+            match key.to_unicode().and_then(|c| c.to_digit(10)) {
+                Some(digit) if digit >= 1 && (digit as usize) <= key_overlay.slots.len() => {
+                    key_overlay.call_in(digit as usize - 1);
+                    glib::Propagation::Stop
+                }
+                _ => glib::Propagation::Proceed,
+            }
+        });
+        overlay.window.add_controller(key_controller);
+
+        // Global hotkeys reach the overlay through these, see hotkeys.rs
+        let actions = gio::SimpleActionGroup::new();
+        for action in hotkeys::HotkeyAction::iter() {
+            let overlay_action = gio::SimpleAction::new(&action.to_string(), None);
+            let action_overlay = overlay.clone();
+            overlay_action.connect_activate(move |_, _| action_overlay.on_hotkey(action));
+            actions.add_action(&overlay_action);
+        }
+        overlay.window.insert_action_group("overlay", Some(&actions));
+
+        // Redraw until the window is closed
+        let weak_window = overlay.window.downgrade();
+        let redraw_overlay = overlay.clone();
+        glib::timeout_add_local(REDRAW_INTERVAL, move || {
+            match weak_window.upgrade() {
+                Some(window) if window.is_visible() => {
+                    redraw_overlay.redraw();
+                    glib::ControlFlow::Continue
+                }
+                _ => glib::ControlFlow::Break,
+            }
+        });
+        overlay
+    }
+
+    /// Records that the Stratagem in the slot was called in. Does nothing if
+    /// it is still on cooldown.
+    pub fn call_in(&self, slot: usize) {
+        if self.tracker.borrow_mut().call_in(slot, Instant::now()) {
+            self.redraw();
+        } else {
+            self.window.error_bell();
+        }
+    }
+
+    /// Starts a new mission with every Stratagem ready
+    pub fn reset(&self) {
+        let (upgrades, _) = ship::load_upgrades();
+        self.tracker.borrow_mut().reset(&upgrades);
+        self.redraw();
+    }
+
+    fn on_hotkey(&self, action: hotkeys::HotkeyAction) {
+        use hotkeys::HotkeyAction::*;
+        match action {
+            CallIn1 => self.call_in(0),
+            CallIn2 => self.call_in(1),
+            CallIn3 => self.call_in(2),
+            CallIn4 => self.call_in(3),
+            NewMission => self.reset(),
+        }
+    }
+
+    /// Updates the countdowns and dims everything that can't be called in
+    fn redraw(&self) {
+        let tracker = self.tracker.borrow();
+        let now = Instant::now();
+        for (index, widgets) in self.slots.iter().enumerate() {
+            let remaining = tracker.get_remaining(index, now);
+            let label = if tracker.slots[index].uses_left == Some(0) {
+                "Used up".to_string()
+            } else if remaining.is_zero() {
+                "Ready".to_string()
+            } else {
+                // Round up, so 0:00 is never shown while it isn't ready yet
+                gui_templates::format_duration(remaining + Duration::from_millis(999))
+            };
+            widgets.countdown.set_label(&label);
+            if tracker.is_ready(index, now) {
+                widgets.button.remove_css_class("on-cooldown");
+            } else {
+                widgets.button.add_css_class("on-cooldown");
+            }
+        }
+    }
+}
+
+/// The hotkey of a slot as it is printed below its icon
+fn get_hotkey_label(slot: usize) -> String {
+    (slot + 1).to_string()
+}
+
+/// Opens the overlay for a loadout next to the window the widget is in
+pub fn open_cooldown_overlay(loadout: &loadout::Loadout, widget: &impl IsA<gtk::Widget>) {
+    let application = widget.root()
+        .and_downcast::<gtk::Window>()
+        .and_then(|window| window.application());
+    let overlay = CooldownOverlay::new(loadout, application.as_ref());
+    overlay.window.present();
+}
@@ -11,8 +11,10 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
 
+use strum::IntoEnumIterator;
+
 #[derive(Debug, rand_derive2::RandGen,Eq, PartialEq, strum_macros::EnumString,
-         strum_macros::Display, Copy, Clone)]
+         strum_macros::Display, strum_macros::EnumIter, Copy, Clone)]
 pub enum Stratagem {
     // Mission Objectives
     Resupply,
@@ -25,6 +27,8 @@ pub enum Stratagem {
     SeismicProbe,
     OrbitalIlluminationFlare,
     SEAFArtillery,
+    ProspectingDrill,
+    SuperEarthFlag,
 
     // Supply: Backpacks
     LAS5GuardDogRover,
@@ -33,6 +37,10 @@ pub enum Stratagem {
     B1SupplyPack,
     SH32ShieldGeneratorPack,
     SH20BallisticShieldBackpack,
+    LIFT860HoverPack,
+    SH51DirectionalShield,
+    AXTX13GuardDogDogBreath,
+    B100PortableHellbomb,
 
     // Support: Support Weapons
     AC8Autocannon,
@@ -49,9 +57,14 @@ pub enum Stratagem {
     FAF14SPEARLauncher,
     LAS99QuasarCannon,
     MG206HeavyMachineGun,
+    RL77AirburstRocketLauncher,
+    MLS4XCommando,
+    TX41Sterilizer,
+    StAX3WASPLauncher,
 
     // Supply: Vehicles
     EXO45PatriotExosuit,
+    EXO49EmancipatorExosuit,
 
     // Defensive
     ARC3TeslaTower,
@@ -65,6 +78,8 @@ pub enum Stratagem {
     MG101HMGEmplacement,
     AC8AutocannonSentry,
     MLS4XRocketSentry,
+    EAT12AntiTankEmplacement,
+    AFLAM40FlameSentry,
 
     // Offensive: Orbital
     OrbitalPrecisionStrike,
@@ -105,6 +120,8 @@ impl Stratagem {
             SeismicProbe => vec!(Up, Up, Left, Right, Down, Down),
             OrbitalIlluminationFlare => vec!(Right, Right, Left, Left),
             SEAFArtillery => vec!(Right, Up, Up, Down),
+            ProspectingDrill => vec!(Down, Down, Left, Right, Down, Down),
+            SuperEarthFlag => vec!(Down, Up, Down, Up),
 
             LAS5GuardDogRover => vec!(Down, Up, Left, Up, Right, Right),
             AR23GuardDog => vec!(Down, Up, Left, Up, Right, Down),
@@ -112,6 +129,10 @@ impl Stratagem {
             B1SupplyPack => vec!(Down, Left, Down, Up, Up, Down),
             SH32ShieldGeneratorPack => vec!(Down, Up, Left, Right, Left, Right),
             SH20BallisticShieldBackpack => vec!(Down, Left, Down, Down, Up, Left),
+            LIFT860HoverPack => vec!(Down, Up, Up, Down, Left, Right),
+            SH51DirectionalShield => vec!(Down, Up, Left, Right, Up, Up),
+            AXTX13GuardDogDogBreath => vec!(Down, Up, Left, Up, Right, Up),
+            B100PortableHellbomb => vec!(Down, Right, Up, Up, Up),
             
             AC8Autocannon => vec!(Down, Left, Down, Up, Up, Right),
             EAT17ExpendableAntiTank => vec!(Down, Down, Left, Up, Right),
@@ -127,8 +148,13 @@ impl Stratagem {
             FAF14SPEARLauncher => vec!(Down, Down, Up, Down, Down),
             LAS99QuasarCannon => vec!(Down, Down, Up, Left, Right),
             MG206HeavyMachineGun => vec!(Down, Left, Up, Down, Down),
+            RL77AirburstRocketLauncher => vec!(Down, Up, Up, Left, Right),
+            MLS4XCommando => vec!(Down, Left, Up, Down, Right),
+            TX41Sterilizer => vec!(Down, Left, Up, Down, Left),
+            StAX3WASPLauncher => vec!(Down, Down, Up, Down, Right),
 
             EXO45PatriotExosuit => vec!(Left, Down, Right, Up, Left, Down, Down),
+            EXO49EmancipatorExosuit => vec!(Left, Down, Right, Up, Left, Down, Up),
 
             ARC3TeslaTower => vec!(Down, Up, Right, Up, Left, Right),
             M12MortarSentry => vec!(Down, Up, Right, Right, Down),
@@ -141,6 +167,8 @@ impl Stratagem {
             MG101HMGEmplacement => vec!(Down, Up, Left, Right, Right, Left),
             AC8AutocannonSentry => vec!(Down, Up, Right, Up, Left, Up),
             MLS4XRocketSentry => vec!(Down, Up, Right, Right, Left),
+            EAT12AntiTankEmplacement => vec!(Down, Up, Left, Right, Right, Right),
+            AFLAM40FlameSentry => vec!(Down, Up, Right, Down, Up, Up),
 
             OrbitalPrecisionStrike => vec!(Right, Right, Up),
             OrbitalAirburstStrike => vec!(Right, Right, Right),
@@ -165,6 +193,362 @@ impl Stratagem {
         }
     }
 
+    /// Returns the category this Stratagem is listed under on the ship
+    pub fn get_category(&self) -> Category {
+        use Stratagem::*;
+        use Category::*;
+        match self {
+            Resupply | SOSBeacon | Reinforce | Hellbomb | SSSDDelivery | UploadData
+            | EagleRearm | SeismicProbe | OrbitalIlluminationFlare | SEAFArtillery
+            | ProspectingDrill | SuperEarthFlag => General,
+
+            LAS5GuardDogRover | AR23GuardDog | LIFT850JumpPack | B1SupplyPack
+            | SH32ShieldGeneratorPack | SH20BallisticShieldBackpack | LIFT860HoverPack
+            | SH51DirectionalShield | AXTX13GuardDogDogBreath
+            | B100PortableHellbomb => Backpack,
+
+            AC8Autocannon | EAT17ExpendableAntiTank | FLAM40Flamethrower
+            | LAS98LaserCannon | M105Stalwart | MG43MachineGun | ARC3ArcThrower
+            | GL21GrenadeLauncher | APW1AntiMaterielRifle | RS422Railgun
+            | GR8RecoillessRifle | FAF14SPEARLauncher | LAS99QuasarCannon
+            | MG206HeavyMachineGun | RL77AirburstRocketLauncher | MLS4XCommando
+            | TX41Sterilizer | StAX3WASPLauncher => SupportWeapon,
+
+            EXO45PatriotExosuit | EXO49EmancipatorExosuit => Vehicle,
+
+            ARC3TeslaTower | M12MortarSentry | M23EMSMortarSentry | MG43MachineGunSentry
+            | G16GatlingSentry | MD6AntiPersonnelMinefield | MD14IncendiaryMines
+            | FX12ShieldGeneratorRelay | MG101HMGEmplacement | AC8AutocannonSentry
+            | MLS4XRocketSentry | EAT12AntiTankEmplacement
+            | AFLAM40FlameSentry => Defensive,
+
+            OrbitalPrecisionStrike | OrbitalAirburstStrike | Orbital120MMHEBarrage
+            | Orbital380MMHEBarrage | OrbitalWalkingBarrage | OrbitalLaser
+            | OrbitalRailcannonStrike | OrbitalGatlingBarrage | OrbitalGasStrike
+            | OrbitalEMSStrike | OrbitalSmokeStrike => Orbital,
+
+            EagleStrafingRun | EagleAirstrike | EagleClusterBomb | EagleNapalmStrike
+            | EagleSmokeStrike | Eagle110MMRocketPods | Eagle500kgBomb => Eagle,
+        }
+    }
+
+    /// Returns the ship module this Stratagem belongs to. This is also the
+    /// folder its icon is in. Mission objectives and the Stratagems everyone
+    /// always has don't belong to any.
+    pub fn get_department(&self) -> Option<Department> {
+        use Stratagem::*;
+        use Department::*;
+        let department = match self {
+            Resupply | SOSBeacon | Reinforce | Hellbomb | SSSDDelivery | UploadData
+            | SeismicProbe | OrbitalIlluminationFlare | SEAFArtillery | ProspectingDrill
+            | SuperEarthFlag => return None,
+
+            EagleRearm | LIFT850JumpPack | LIFT860HoverPack | EagleStrafingRun
+            | EagleAirstrike | EagleClusterBomb | EagleNapalmStrike | EagleSmokeStrike
+            | Eagle110MMRocketPods | Eagle500kgBomb => Hangar,
+
+            ARC3TeslaTower | FX12ShieldGeneratorRelay | MG101HMGEmplacement
+            | EAT12AntiTankEmplacement | OrbitalPrecisionStrike | OrbitalGasStrike
+            | OrbitalEMSStrike | OrbitalSmokeStrike => Bridge,
+
+            OrbitalAirburstStrike | Orbital120MMHEBarrage | Orbital380MMHEBarrage
+            | OrbitalWalkingBarrage | OrbitalLaser | OrbitalRailcannonStrike
+            | OrbitalGatlingBarrage => OrbitalCannons,
+
+            AR23GuardDog | AXTX13GuardDogDogBreath | EXO45PatriotExosuit
+            | EXO49EmancipatorExosuit | M12MortarSentry | M23EMSMortarSentry
+            | MG43MachineGunSentry | G16GatlingSentry | AC8AutocannonSentry
+            | MLS4XRocketSentry | AFLAM40FlameSentry => RoboticsWorkshop,
+
+            LAS5GuardDogRover | B1SupplyPack | SH32ShieldGeneratorPack
+            | SH20BallisticShieldBackpack | SH51DirectionalShield | B100PortableHellbomb
+            | LAS98LaserCannon | ARC3ArcThrower | GL21GrenadeLauncher | LAS99QuasarCannon
+            | MD6AntiPersonnelMinefield | MD14IncendiaryMines => EngineeringBay,
+
+            AC8Autocannon | EAT17ExpendableAntiTank | FLAM40Flamethrower | M105Stalwart
+            | MG43MachineGun | APW1AntiMaterielRifle | RS422Railgun | GR8RecoillessRifle
+            | FAF14SPEARLauncher | MG206HeavyMachineGun | RL77AirburstRocketLauncher
+            | MLS4XCommando | TX41Sterilizer | StAX3WASPLauncher => PatrioticAdministrationCenter,
+        };
+        Some(department)
+    }
+
+    /// Returns the gameplay numbers of this Stratagem at its base level,
+    /// before any ship module upgrades. They are best effort and have to be
+    /// adjusted when a patch balances a Stratagem.
+    pub fn get_stats(&self) -> StratagemStats {
+        use Stratagem::*;
+        // cooldown, call-in time, uses, unlock level, requisition slips
+        let (cooldown, call_in, uses, level, cost) = match self {
+            Resupply => (160, 12, None, 0, 0),
+            SOSBeacon => (0, 0, Some(1), 0, 0),
+            Reinforce => (0, 0, None, 0, 0),
+            Hellbomb => (0, 5, Some(1), 0, 0),
+            SSSDDelivery => (0, 5, Some(1), 0, 0),
+            UploadData => (0, 5, Some(1), 0, 0),
+            EagleRearm => (150, 0, None, 0, 0),
+            SeismicProbe => (0, 5, Some(1), 0, 0),
+            OrbitalIlluminationFlare => (15, 0, None, 0, 0),
+            SEAFArtillery => (0, 3, Some(1), 0, 0),
+            ProspectingDrill => (0, 5, Some(1), 0, 0),
+            SuperEarthFlag => (0, 5, Some(1), 0, 0),
+
+            LAS5GuardDogRover => (480, 5, None, 10, 7_000),
+            AR23GuardDog => (480, 5, None, 15, 7_500),
+            LIFT850JumpPack => (480, 5, None, 8, 6_000),
+            B1SupplyPack => (480, 5, None, 2, 4_000),
+            SH32ShieldGeneratorPack => (480, 5, None, 15, 7_000),
+            SH20BallisticShieldBackpack => (300, 5, None, 8, 6_000),
+            LIFT860HoverPack => (480, 5, None, 0, 0),
+            SH51DirectionalShield => (480, 5, None, 0, 0),
+            AXTX13GuardDogDogBreath => (480, 5, None, 0, 0),
+            B100PortableHellbomb => (300, 5, None, 0, 0),
+
+            AC8Autocannon => (480, 3, None, 10, 7_000),
+            EAT17ExpendableAntiTank => (70, 3, None, 3, 3_500),
+            FLAM40Flamethrower => (480, 3, None, 10, 6_000),
+            LAS98LaserCannon => (480, 3, None, 10, 6_000),
+            M105Stalwart => (480, 3, None, 2, 4_000),
+            MG43MachineGun => (480, 3, None, 1, 3_500),
+            ARC3ArcThrower => (480, 3, None, 20, 10_000),
+            GL21GrenadeLauncher => (480, 3, None, 5, 6_000),
+            APW1AntiMaterielRifle => (480, 3, None, 5, 5_000),
+            RS422Railgun => (480, 3, None, 20, 10_000),
+            GR8RecoillessRifle => (480, 3, None, 5, 6_000),
+            FAF14SPEARLauncher => (480, 3, None, 20, 9_000),
+            LAS99QuasarCannon => (480, 3, None, 25, 7_500),
+            MG206HeavyMachineGun => (480, 3, None, 10, 6_000),
+            RL77AirburstRocketLauncher => (480, 3, None, 20, 8_000),
+            MLS4XCommando => (120, 3, None, 10, 7_000),
+            TX41Sterilizer => (480, 3, None, 0, 0),
+            StAX3WASPLauncher => (480, 3, None, 15, 8_000),
+
+            EXO45PatriotExosuit => (600, 6, Some(2), 25, 20_000),
+            EXO49EmancipatorExosuit => (600, 6, Some(2), 25, 20_000),
+
+            ARC3TeslaTower => (150, 3, None, 10, 7_000),
+            M12MortarSentry => (180, 3, None, 5, 6_000),
+            M23EMSMortarSentry => (180, 3, None, 10, 6_000),
+            MG43MachineGunSentry => (190, 3, None, 2, 2_500),
+            G16GatlingSentry => (180, 3, None, 3, 4_000),
+            MD6AntiPersonnelMinefield => (120, 3, None, 2, 3_000),
+            MD14IncendiaryMines => (180, 3, None, 8, 5_000),
+            FX12ShieldGeneratorRelay => (90, 3, None, 20, 8_000),
+            MG101HMGEmplacement => (180, 3, None, 15, 7_000),
+            AC8AutocannonSentry => (150, 3, None, 15, 7_000),
+            MLS4XRocketSentry => (150, 3, None, 20, 7_500),
+            EAT12AntiTankEmplacement => (180, 3, None, 0, 0),
+            AFLAM40FlameSentry => (150, 3, None, 0, 0),
+
+            OrbitalPrecisionStrike => (100, 3, None, 0, 0),
+            OrbitalAirburstStrike => (100, 3, None, 5, 4_000),
+            Orbital120MMHEBarrage => (180, 5, None, 10, 5_000),
+            Orbital380MMHEBarrage => (240, 9, None, 20, 7_500),
+            OrbitalWalkingBarrage => (240, 5, None, 15, 6_000),
+            OrbitalLaser => (300, 5, Some(3), 25, 8_500),
+            OrbitalRailcannonStrike => (210, 3, None, 30, 10_000),
+            OrbitalGatlingBarrage => (70, 2, None, 2, 1_500),
+            OrbitalGasStrike => (75, 3, None, 5, 4_000),
+            OrbitalEMSStrike => (75, 3, None, 10, 4_000),
+            OrbitalSmokeStrike => (100, 3, None, 2, 2_500),
+
+            EagleStrafingRun => (8, 3, Some(4), 2, 1_500),
+            EagleAirstrike => (8, 3, Some(2), 2, 4_000),
+            EagleClusterBomb => (8, 3, Some(4), 4, 4_000),
+            EagleNapalmStrike => (8, 3, Some(2), 6, 5_000),
+            EagleSmokeStrike => (8, 3, Some(2), 8, 4_000),
+            Eagle110MMRocketPods => (8, 3, Some(2), 10, 4_500),
+            Eagle500kgBomb => (8, 3, Some(1), 15, 7_500),
+        };
+        StratagemStats {
+            cooldown: std::time::Duration::from_secs(cooldown),
+            call_in: std::time::Duration::from_secs(call_in),
+            uses,
+            unlock_level: level,
+            cost,
+        }
+    }
+
+    /// Seconds until the Stratagem can be called in again
+    pub fn get_cooldown(&self) -> std::time::Duration {
+        self.get_stats().cooldown
+    }
+
+    /// Time between the beacon landing and the Stratagem arriving
+    pub fn get_call_in_time(&self) -> std::time::Duration {
+        self.get_stats().call_in
+    }
+
+    /// How often the Stratagem can be used per mission, or per rearm for
+    /// Eagles. None means as often as the cooldown allows.
+    pub fn get_uses(&self) -> Option<u32> {
+        self.get_stats().uses
+    }
+
+    /// The player level needed to buy this Stratagem. 0 means it doesn't
+    /// have to be bought, because everyone has it or it comes from a warbond.
+    pub fn get_unlock_level(&self) -> u32 {
+        self.get_stats().unlock_level
+    }
+
+    /// How many requisition slips the Stratagem costs on the ship
+    pub fn get_cost(&self) -> u32 {
+        self.get_stats().cost
+    }
+
+    /// The warbond this Stratagem is unlocked from, if any
+    pub fn get_warbond(&self) -> Option<Warbond> {
+        use Stratagem::*;
+        use Warbond::*;
+        match self {
+            TX41Sterilizer | AXTX13GuardDogDogBreath => Some(ChemicalAgents),
+            SH51DirectionalShield | EAT12AntiTankEmplacement
+            | AFLAM40FlameSentry => Some(UrbanLegends),
+            B100PortableHellbomb => Some(ServantsOfFreedom),
+            LIFT860HoverPack => Some(BorderlineJustice),
+            _ => None,
+        }
+    }
+
+    /// Whether calling this in takes up the backpack slot. Some support
+    /// weapons bring their ammo in a backpack, so they take up both.
+    pub fn uses_backpack_slot(&self) -> bool {
+        use Stratagem::*;
+        self.get_category() == Category::Backpack
+            || matches!(self, AC8Autocannon | GR8RecoillessRifle | FAF14SPEARLauncher
+                        | RL77AirburstRocketLauncher | StAX3WASPLauncher)
+    }
+
+    /// Whether calling this in takes up the support weapon slot
+    pub fn uses_support_weapon_slot(&self) -> bool {
+        self.get_category() == Category::SupportWeapon
+    }
+
+    /// Returns what this Stratagem is good at in a squad. Most Stratagems have
+    /// one role, some have none or several.
+    pub fn get_roles(&self) -> Vec<Role> {
+        use Stratagem::*;
+        use Role::*;
+        let mut roles = Vec::new();
+        if matches!(self, EAT17ExpendableAntiTank | LAS99QuasarCannon | GR8RecoillessRifle
+                    | FAF14SPEARLauncher | RS422Railgun | AC8Autocannon | MLS4XCommando
+                    | StAX3WASPLauncher | EXO45PatriotExosuit | EXO49EmancipatorExosuit
+                    | EAT12AntiTankEmplacement | AC8AutocannonSentry | MLS4XRocketSentry
+                    | B100PortableHellbomb | OrbitalPrecisionStrike | OrbitalLaser
+                    | OrbitalRailcannonStrike | Orbital380MMHEBarrage | Eagle500kgBomb
+                    | Eagle110MMRocketPods) {
+            roles.push(AntiTank);
+        }
+        if matches!(self, M105Stalwart | MG43MachineGun | MG206HeavyMachineGun
+                    | FLAM40Flamethrower | ARC3ArcThrower | GL21GrenadeLauncher
+                    | LAS98LaserCannon | TX41Sterilizer | RL77AirburstRocketLauncher
+                    | EXO45PatriotExosuit | EagleStrafingRun | EagleAirstrike
+                    | EagleClusterBomb | EagleNapalmStrike | OrbitalAirburstStrike
+                    | Orbital120MMHEBarrage | Orbital380MMHEBarrage | OrbitalWalkingBarrage
+                    | OrbitalGatlingBarrage | OrbitalGasStrike | OrbitalEMSStrike
+                    | M12MortarSentry | M23EMSMortarSentry | MD6AntiPersonnelMinefield
+                    | MD14IncendiaryMines | AFLAM40FlameSentry | ARC3TeslaTower
+                    | G16GatlingSentry | MG43MachineGunSentry | MG101HMGEmplacement) {
+            roles.push(CrowdControl);
+        }
+        if matches!(self, B1SupplyPack | SH32ShieldGeneratorPack | SH20BallisticShieldBackpack
+                    | SH51DirectionalShield | LAS5GuardDogRover | AR23GuardDog
+                    | AXTX13GuardDogDogBreath | LIFT850JumpPack | LIFT860HoverPack
+                    | FX12ShieldGeneratorRelay | EagleSmokeStrike | OrbitalSmokeStrike
+                    | OrbitalEMSStrike | Resupply | Reinforce | SOSBeacon | EagleRearm
+                    | OrbitalIlluminationFlare) {
+            roles.push(Support);
+        }
+        if self.get_category() == Category::Defensive {
+            roles.push(Defensive);
+        }
+        roles
+    }
+
+    /// Returns where this Stratagem can be obtained. The trainer uses this to
+    /// include or exclude Stratagems a player might never see on the ship.
+    pub fn get_availability(&self) -> Availability {
+        use Stratagem::*;
+        use Availability::*;
+        match self {
+            Hellbomb | SSSDDelivery | UploadData | SeismicProbe
+            | OrbitalIlluminationFlare | SEAFArtillery | ProspectingDrill
+            | SuperEarthFlag => MissionOnly,
+
+            _ if self.get_warbond().is_some() => Warbond,
+            _ if self.get_valid_until().is_some() => Removed,
+            _ => Standard,
+        }
+    }
+
+    /// The first patch this Stratagem was in the game. Everything that was
+    /// around before the catalog got versioned counts as launch content.
+    pub fn get_valid_since(&self) -> GamePatch {
+        use Stratagem::*;
+        use GamePatch::*;
+        match self {
+            MLS4XCommando | RL77AirburstRocketLauncher
+            | EXO49EmancipatorExosuit => EscalationOfFreedom,
+            TX41Sterilizer | AXTX13GuardDogDogBreath => ChemicalAgents,
+            SH51DirectionalShield | EAT12AntiTankEmplacement
+            | AFLAM40FlameSentry => UrbanLegends,
+            B100PortableHellbomb | StAX3WASPLauncher => ServantsOfFreedom,
+            LIFT860HoverPack => BorderlineJustice,
+            _ => Launch,
+        }
+    }
+
+    /// The patch that took this Stratagem out of the game, if any. It is not
+    /// available in that patch anymore.
+    pub fn get_valid_until(&self) -> Option<GamePatch> {
+        None
+    }
+
+    /// Keycodes this Stratagem had before its current one, together with the
+    /// patch that replaced them. Sorted from oldest to newest.
+    pub fn get_old_keycodes(&self) -> Vec<(GamePatch, Vec<Code>)> {
+        // No changed code could be verified against the patch notes yet. Add
+        // the old keycode together with the patch that changed it here.
+        Vec::new()
+    }
+
+    /// Returns whether this Stratagem was part of the game in the given patch
+    pub fn is_in_patch(&self, patch: GamePatch) -> bool {
+        self.get_valid_since() <= patch
+            && self.get_valid_until().is_none_or(|until| patch < until)
+    }
+
+    /// Returns the keycode that was used in the given patch, or None if the
+    /// Stratagem was not in the game back then.
+    pub fn get_keycode_at(&self, patch: GamePatch) -> Option<Vec<Code>> {
+        if !self.is_in_patch(patch) {
+            return None;
+        }
+        let keycode = self.get_old_keycodes()
+            .into_iter()
+            .find(|(replaced_in, _)| patch < *replaced_in)
+            .map_or_else(|| self.get_keycode(), |(_, keycode)| keycode);
+        Some(keycode)
+    }
+
+    /// Everything that happened to this Stratagem over the patches, oldest
+    /// first
+    pub fn get_changelog(&self) -> Vec<(GamePatch, String)> {
+        let mut changelog = vec!((self.get_valid_since(), "Added to the game".to_string()));
+        for (patch, _) in self.get_old_keycodes() {
+            // The code that came with this patch is the next old one or the
+            // current one
+            if let Some(keycode) = self.get_keycode_at(patch) {
+                let arrows: String = keycode.iter().map(Code::get_arrow).collect();
+                changelog.push((patch, format!("Code changed to {arrows}")));
+            }
+        }
+        if let Some(patch) = self.get_valid_until() {
+            changelog.push((patch, "Removed from the game".to_string()));
+        }
+        changelog
+    }
+
     pub fn get_image_path(&self) -> String {
         use Stratagem::*;
         let asset_folder_path = "assets/Helldivers-2-Stratagems-icons-svg/";
@@ -176,10 +560,12 @@ impl Stratagem {
             // They are the same
             SSSDDelivery => "GeneralStratagems/Upload Data.svg",
             UploadData => "GeneralStratagems/Upload Data.svg",
-            EagleRearm => "Hangar/EagleRearm.svg",
+            EagleRearm => "Hangar/Eagle Rearm.svg",
             SeismicProbe => "GeneralStratagems/Seismic Probe.svg",
             OrbitalIlluminationFlare => "GeneralStratagems/Orbital Illumination Flare.svg",
             SEAFArtillery => "GeneralStratagems/SEAF Artillery.svg",
+            ProspectingDrill => "GeneralStratagems/Prospecting Drill.svg",
+            SuperEarthFlag => "GeneralStratagems/Super Earth Flag.svg",
 
             LAS5GuardDogRover => "EngineeringBay/Guard Dog Rover.svg",
             AR23GuardDog => "RoboticsWorkshop/Guard Dog.svg",
@@ -205,6 +591,13 @@ impl Stratagem {
 
             EXO45PatriotExosuit => "RoboticsWorkshop/Patriot Exosuit.svg",
 
+            // The asset pack does not have icons for these (yet)
+            LIFT860HoverPack | SH51DirectionalShield | AXTX13GuardDogDogBreath
+            | B100PortableHellbomb | RL77AirburstRocketLauncher | MLS4XCommando
+            | TX41Sterilizer | StAX3WASPLauncher | EXO49EmancipatorExosuit
+            | EAT12AntiTankEmplacement | AFLAM40FlameSentry =>
+                return UNKNOWN_IMAGE_PATH.to_owned(),
+
             ARC3TeslaTower => "Bridge/Tesla Tower.svg",
             M12MortarSentry => "RoboticsWorkshop/Mortar Sentry.svg",
             M23EMSMortarSentry => "RoboticsWorkshop/EMS Mortar Sentry.svg",
@@ -241,7 +634,178 @@ impl Stratagem {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+/// The categories Stratagems are sorted into on the ship
+#[derive(Debug, PartialEq, Eq, strum_macros::Display, strum_macros::EnumIter,
+         Copy, Clone)]
+pub enum Category {
+    /// Mission objectives and the Stratagems everyone always has
+    General,
+    Backpack,
+    #[strum(serialize = "Support Weapon")]
+    SupportWeapon,
+    Vehicle,
+    Defensive,
+    Orbital,
+    Eagle,
+}
+
+/// Gameplay numbers of a Stratagem, see `Stratagem::get_stats`
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct StratagemStats {
+    pub cooldown: std::time::Duration,
+    pub call_in: std::time::Duration,
+    pub uses: Option<u32>,
+    pub unlock_level: u32,
+    /// In requisition slips
+    pub cost: u32,
+}
+
+/// The warbonds that come with Stratagems
+#[derive(Debug, PartialEq, Eq, strum_macros::Display, strum_macros::EnumIter,
+         Copy, Clone)]
+pub enum Warbond {
+    #[strum(serialize = "Chemical Agents")]
+    ChemicalAgents,
+    #[strum(serialize = "Urban Legends")]
+    UrbanLegends,
+    #[strum(serialize = "Servants of Freedom")]
+    ServantsOfFreedom,
+    #[strum(serialize = "Borderline Justice")]
+    BorderlineJustice,
+}
+
+/// The modules of the Super Destroyer. Stratagems are bought from them and
+/// their upgrades make the Stratagems better.
+#[derive(Debug, PartialEq, Eq, strum_macros::EnumString, strum_macros::Display,
+         strum_macros::EnumIter, Copy, Clone)]
+#[strum(ascii_case_insensitive)]
+pub enum Department {
+    Hangar,
+    Bridge,
+    #[strum(to_string = "Orbital Cannons", serialize = "OrbitalCannons")]
+    OrbitalCannons,
+    #[strum(to_string = "Robotics Workshop", serialize = "RoboticsWorkshop")]
+    RoboticsWorkshop,
+    #[strum(to_string = "Engineering Bay", serialize = "EngineeringBay")]
+    EngineeringBay,
+    #[strum(to_string = "Patriotic Administration Center",
+            serialize = "PatrioticAdministrationCenter")]
+    PatrioticAdministrationCenter,
+}
+
+/// What a Stratagem does for a squad, used to check if a team covers
+/// everything it needs
+#[derive(Debug, PartialEq, Eq, strum_macros::Display, strum_macros::EnumIter,
+         Copy, Clone)]
+pub enum Role {
+    #[strum(serialize = "Anti-Tank")]
+    AntiTank,
+    #[strum(serialize = "Crowd Control")]
+    CrowdControl,
+    Support,
+    Defensive,
+}
+
+/// Fallback for Stratagems that have no icon in the asset pack
+const UNKNOWN_IMAGE_PATH: &str = "assets/Unknown Stratagem.svg";
+
+/// Where a Stratagem comes from. Everything that isn't `Standard` can be
+/// toggled in the trainer.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Availability {
+    /// Unlocked on the ship with requisition slips
+    Standard,
+    /// Only handed out during certain missions, like the Hellbomb
+    MissionOnly,
+    /// Has to be unlocked from a premium or free warbond
+    Warbond,
+    /// Was in the game at some point but got taken out
+    Removed,
+}
+
+/// The game updates the catalog knows about, oldest first. They are named
+/// after the major update or warbond that came with them, which is also what
+/// players remember them by.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, strum_macros::EnumString,
+         strum_macros::Display, strum_macros::EnumIter, Copy, Clone)]
+pub enum GamePatch {
+    Launch,
+    #[strum(to_string = "Escalation of Freedom", serialize = "EscalationOfFreedom")]
+    EscalationOfFreedom,
+    #[strum(to_string = "Chemical Agents", serialize = "ChemicalAgents")]
+    ChemicalAgents,
+    #[strum(to_string = "Omens of Tyranny", serialize = "OmensOfTyranny")]
+    OmensOfTyranny,
+    #[strum(to_string = "Urban Legends", serialize = "UrbanLegends")]
+    UrbanLegends,
+    #[strum(to_string = "Servants of Freedom", serialize = "ServantsOfFreedom")]
+    ServantsOfFreedom,
+    #[strum(to_string = "Borderline Justice", serialize = "BorderlineJustice")]
+    BorderlineJustice,
+}
+
+impl GamePatch {
+    /// The patch the game is currently on
+    pub fn latest() -> GamePatch {
+        GamePatch::iter().next_back().expect("There is at least one patch")
+    }
+}
+
+/// Decides which Stratagems are available to the trainer
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct StratagemFilter {
+    pub mission_only: bool,
+    pub warbond: bool,
+    pub removed: bool,
+    /// Only Stratagems from this patch are picked, with the codes they had
+    /// back then
+    pub patch: GamePatch,
+}
+
+impl Default for StratagemFilter {
+    fn default() -> Self {
+        StratagemFilter {
+            mission_only: true,
+            warbond: true,
+            removed: false,
+            patch: GamePatch::latest(),
+        }
+    }
+}
+
+impl StratagemFilter {
+    pub fn allows(&self, stratagem: &Stratagem) -> bool {
+        if stratagem.get_valid_since() > self.patch {
+            return false;
+        }
+        // Taken out of the game before the selected patch
+        if !stratagem.is_in_patch(self.patch) {
+            return self.removed;
+        }
+        match stratagem.get_availability() {
+            Availability::Standard => true,
+            Availability::MissionOnly => self.mission_only,
+            Availability::Warbond => self.warbond,
+            // Still in the game in the selected patch
+            Availability::Removed => true,
+        }
+    }
+
+    /// Returns the keycode the trainer expects for this Stratagem in the
+    /// selected patch
+    pub fn get_keycode(&self, stratagem: &Stratagem) -> Vec<Code> {
+        stratagem.get_keycode_at(self.patch)
+            .unwrap_or_else(|| stratagem.get_keycode())
+    }
+
+    /// Returns every Stratagem this filter lets through. Standard launch
+    /// Stratagems always pass, so this is never empty.
+    pub fn get_pool(&self) -> Vec<Stratagem> {
+        Stratagem::iter().filter(|s| self.allows(s)).collect()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, strum_macros::EnumString, strum_macros::Display, Copy, Clone)]
 pub enum Code {
     Down,
     Left,
@@ -260,3 +824,45 @@ impl Code {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use Stratagem::*;
+
+    #[test]
+    fn patch_ranges() {
+        assert!(Resupply.is_in_patch(GamePatch::Launch));
+        assert!(!TX41Sterilizer.is_in_patch(GamePatch::EscalationOfFreedom));
+        assert!(TX41Sterilizer.is_in_patch(GamePatch::ChemicalAgents));
+        assert!(TX41Sterilizer.is_in_patch(GamePatch::latest()));
+        assert_eq!(TX41Sterilizer.get_keycode_at(GamePatch::Launch), None);
+        assert_eq!(TX41Sterilizer.get_keycode_at(GamePatch::latest()),
+                   Some(TX41Sterilizer.get_keycode()));
+        assert_eq!(TX41Sterilizer.get_changelog(),
+                   [(GamePatch::ChemicalAgents, "Added to the game".to_string())]);
+    }
+
+    #[test]
+    fn filter_by_patch() {
+        let launch = StratagemFilter {
+            removed: true,
+            patch: GamePatch::Launch,
+            ..Default::default()
+        };
+        let pool = launch.get_pool();
+        assert!(pool.contains(&EagleAirstrike));
+        assert!(!pool.contains(&LIFT860HoverPack));
+        assert!(pool.iter().all(|s| s.get_valid_since() == GamePatch::Launch));
+        assert!(StratagemFilter::default().get_pool().contains(&LIFT860HoverPack));
+    }
+
+    #[test]
+    fn patch_names() {
+        for patch in GamePatch::iter() {
+            assert_eq!(GamePatch::from_str(&patch.to_string()), Ok(patch));
+            assert_eq!(GamePatch::from_str(&format!("{patch:?}")), Ok(patch));
+        }
+    }
+}
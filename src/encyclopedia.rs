@@ -0,0 +1,159 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// The encyclopedia lists every Stratagem in the catalog together with its
+// keycode and what changed about it over the patches.
+
+use adw::prelude::*;
+use strum::IntoEnumIterator;
+
+use crate::db;
+use crate::gui_templates;
+use crate::ship;
+use crate::stats;
+
+/// Creates the encyclopedia page. The patch selected in the top bar decides
+/// which Stratagems are listed and which keycodes are shown.
+pub fn create_encyclopedia_page() -> adw::NavigationPage {
+    let patch_select = gui_templates::create_patch_dropdown(db::GamePatch::latest());
+
+    let list = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .css_classes(*&["boxed-list"])
+        .margin_top(12)
+        .margin_bottom(12)
+        .build();
+    fill_list(&list, db::GamePatch::latest());
+    let patch_list = list.clone();
+    patch_select.connect_selected_notify(move |dropdown| {
+        fill_list(&patch_list, gui_templates::get_selected_patch(dropdown));
+    });
+    // The ship upgrades may have changed while the page was hidden
+    let map_select = patch_select.clone();
+    list.connect_map(move |list| fill_list(list, gui_templates::get_selected_patch(&map_select)));
+
+    gui_templates::create_titled_page("Encyclopedia", Some(patch_select.upcast_ref()),
+                                      &gui_templates::create_scrolled_content(&list))
+}
+
+/// Replaces the content of the list with every Stratagem that was in the game
+/// in the given patch
+fn fill_list(list: &gtk::ListBox, patch: db::GamePatch) {
+    list.remove_all();
+    let (upgrades, _) = ship::load_upgrades();
+    let (attempts, _) = stats::load_attempts();
+    for stratagem in db::Stratagem::iter().filter(|s| s.is_in_patch(patch)) {
+        list.append(&create_entry(&stratagem, patch, &upgrades, &attempts));
+    }
+}
+
+/// One row in the encyclopedia. The training mistakes, the stats and the
+/// changelog up to the given patch can be expanded below it.
+fn create_entry(stratagem: &db::Stratagem,
+                patch: db::GamePatch,
+                upgrades: &ship::ShipUpgrades,
+                attempts: &[stats::Attempt])
+                -> adw::ExpanderRow {
+    let keycode = stratagem.get_keycode_at(patch).unwrap_or_default();
+    let row = adw::ExpanderRow::builder()
+        .title(stratagem.to_string())
+        .subtitle(crate::get_keycode_str(&keycode))
+        .build();
+    // Screen readers don't read arrows well
+    row.update_property(&[gtk::accessible::Property::Description(
+        &gui_templates::describe_keycode(&keycode))]);
+    row.add_prefix(&gui_templates::create_stratagem_icon(stratagem, 40));
+
+    // Only point out the most recent change so the list stays readable
+    let last_change = stratagem.get_old_keycodes()
+        .into_iter()
+        .map(|(replaced_in, _)| replaced_in)
+        .rfind(|replaced_in| *replaced_in <= patch);
+    if let Some(changed_in) = last_change {
+        row.add_suffix(&gtk::Label::builder()
+                       .label(format!("Code changed in {changed_in}"))
+                       .css_classes(*&["dim-label"])
+                       .build());
+    }
+
+    // Which keys of the code go wrong in training, once it was trained
+    let mistakes = stats::analyze_mistakes(attempts, *stratagem);
+    if mistakes.first().is_some_and(|m| m.reached > 0) {
+        let mistake_row = adw::ActionRow::builder()
+            .title("Your mistakes")
+            .subtitle(gui_templates::describe_typical_mistake(&mistakes)
+                      .unwrap_or("No mistakes so far".to_string()))
+            .build();
+        mistake_row.add_suffix(&gui_templates::create_mistake_heatmap(&mistakes));
+        row.add_row(&mistake_row);
+    }
+
+    for (title, value) in get_details(stratagem, upgrades) {
+        let detail = adw::ActionRow::builder()
+            .title(title)
+            .build();
+        detail.add_suffix(&gtk::Label::builder()
+                          .label(value)
+                          .css_classes(*&["dim-label"])
+                          .build());
+        row.add_row(&detail);
+    }
+
+    for (entry_patch, note) in stratagem.get_changelog() {
+        if entry_patch > patch {
+            continue;
+        }
+        row.add_row(&adw::ActionRow::builder()
+                    .title(note)
+                    .subtitle(entry_patch.to_string())
+                    .build());
+    }
+    row
+}
+
+/// The gameplay details of a Stratagem as title and value, leaving out
+/// everything that doesn't apply to it. Values changed by the owned ship
+/// upgrades also show what they are without them.
+fn get_details(stratagem: &db::Stratagem, upgrades: &ship::ShipUpgrades)
+               -> Vec<(&'static str, String)> {
+    let stats = stratagem.get_stats();
+    let effective = upgrades.get_effective_stats(stratagem);
+    let with_base = |value: String, base: String| if value == base {
+        value
+    } else {
+        format!("{value} (base {base})")
+    };
+    let uses = |uses: Option<u32>| uses.map_or("Unlimited".to_string(), |uses| uses.to_string());
+    let mut details = vec!(
+        ("Cooldown", with_base(gui_templates::format_duration(effective.cooldown),
+                               gui_templates::format_duration(stats.cooldown))),
+        ("Call-in time", with_base(gui_templates::format_duration(effective.call_in),
+                                   gui_templates::format_duration(stats.call_in))),
+        ("Uses", with_base(uses(effective.uses), uses(stats.uses))),
+    );
+    if let Some(warbond) = stratagem.get_warbond() {
+        details.push(("Warbond", warbond.to_string()));
+    } else if stats.unlock_level > 0 {
+        details.push(("Unlocks at level", stats.unlock_level.to_string()));
+        details.push(("Costs", format!("{} requisition slips", stats.cost)));
+    }
+    let slots: Vec<&str> = [(stratagem.uses_backpack_slot(), "Backpack"),
+                            (stratagem.uses_support_weapon_slot(), "Support weapon")]
+        .into_iter()
+        .filter_map(|(used, slot)| used.then_some(slot))
+        .collect();
+    if !slots.is_empty() {
+        details.push(("Takes up", slots.join(" and ")));
+    }
+    details
+}
@@ -0,0 +1,80 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// A ghost is an earlier run, played back in real time next to the player who
+// races it. With the same seed both get the same Stratagems, so whoever has
+// entered more codes is ahead.
+
+use std::time::Instant;
+
+use crate::db;
+use crate::replay;
+
+#[derive(Debug, Clone)]
+pub struct Ghost {
+    /// When the race was started
+    pub started: Instant,
+    player: replay::ReplayPlayer,
+    /// The Stratagem the ghost is at
+    pub stratagem: Option<db::Stratagem>,
+    /// The keys the ghost pressed for it so far, including a wrong one
+    pub pressed: Vec<db::Code>,
+    /// Codes the ghost entered correctly
+    pub completed: usize,
+    /// Codes the player entered correctly since the race started
+    pub player_completed: usize,
+}
+
+impl Ghost {
+    /// The race starts right away
+    pub fn new(replay: replay::Replay) -> Self {
+        Ghost {
+            started: Instant::now(),
+            player: replay::ReplayPlayer::new(replay, 1.0),
+            stratagem: None,
+            pressed: Vec::new(),
+            completed: 0,
+            player_completed: 0,
+        }
+    }
+
+    /// Moves the ghost forward to the given time. `get_keycode` gives the
+    /// keycode of a Stratagem, to know when the ghost finished one.
+    pub fn advance(&mut self, now: Instant, get_keycode: impl Fn(&db::Stratagem) -> Vec<db::Code>) {
+        for event in self.player.advance(now) {
+            match event {
+                replay::ReplayEvent::Shown{stratagem, ..} => {
+                    self.stratagem = Some(stratagem);
+                    self.pressed.clear();
+                }
+                replay::ReplayEvent::Key{code, ..} => {
+                    self.pressed.push(code);
+                    if self.stratagem.is_some_and(|s| get_keycode(&s) == self.pressed) {
+                        self.completed += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    /// How many codes the ghost is ahead of the player, negative if it is
+    /// behind
+    pub fn get_lead(&self) -> i64 {
+        self.completed as i64 - self.player_completed as i64
+    }
+
+    /// Whether the ghost's run is over
+    pub fn is_done(&self) -> bool {
+        self.player.is_done()
+    }
+}
@@ -15,6 +15,26 @@
 // looks like the one in the GNOME 46 settings. This also hopefully increases
 // readability
 
+use crate::accessibility;
+use crate::challenge_generator;
+use crate::db;
+use crate::encyclopedia;
+use crate::hero;
+use crate::highscores_page;
+use crate::loadout;
+use crate::loadout_builder;
+use crate::progress_page;
+use crate::race_page;
+use crate::replay;
+use crate::replays_page;
+use crate::ship;
+use crate::ship_modules;
+use crate::stats;
+use crate::stats_page;
+use crate::squad_planner;
+use std::rc::Rc;
+use strum::IntoEnumIterator;
+
 /// This function creates a title similar to the one in the new GNOME 46
 /// settings and returns it. Because this is a Label in a CenterBox in a
 /// WindowHandle in a HeaderBar, it got a custom function.
@@ -22,17 +42,21 @@
 ///
 /// It also takes a &gtk::Label instead of a simple string so you can retain
 /// control over the text in there and change it at runtime. If you don't want
-/// that, just use gtk::Label::new() as the title
-pub fn create_adw_toolbar(title: &gtk::Label) -> gtk::WindowHandle {
-    gtk::WindowHandle::builder()
-        .child(&gtk::CenterBox::builder()
-            .margin_top(15)
-            .margin_bottom(15)
-            .center_widget(&adw::Bin::builder()
-                .css_classes(*&["adwaita-sidebar-title"])
-                .child(title)
-                .build())
+/// that, just use gtk::Label::new() as the title. `end` is put at the right
+/// of the title, like a button.
+pub fn create_adw_toolbar(title: &gtk::Label, end: Option<&gtk::Widget>) -> gtk::WindowHandle {
+    let center = gtk::CenterBox::builder()
+        .margin_top(15)
+        .margin_bottom(15)
+        .margin_end(6)
+        .center_widget(&adw::Bin::builder()
+            .css_classes(*&["adwaita-sidebar-title"])
+            .child(title)
             .build())
+        .build();
+    center.set_end_widget(end);
+    gtk::WindowHandle::builder()
+        .child(&center)
         .build() 
 }
 
@@ -61,24 +85,84 @@ pub fn create_sidebar(titlebar: &gtk::WindowHandle,
 /// example from a click on the sidebar)
 pub enum AvailablePages<'a> {
     NotYetImplemented,
+    Encyclopedia,
+    /// `on_train` is called when a saved loadout should be trained
+    LoadoutBuilder{
+        on_train: Rc<dyn Fn(loadout::Loadout)>,
+    },
+    SquadPlanner,
+    ShipModules,
+    Statistics,
+    Progress,
+    /// `on_replay` is called when a seed should be played again
+    /// and `on_race` when the replay of a score should be raced
+    HighScores{
+        on_replay: Rc<dyn Fn(hero::Difficulty, u64)>,
+        on_race: Rc<dyn Fn(replay::Replay)>,
+    },
+    /// `on_play` is called when a replay should be played back, with its
+    /// speed, and `on_race` when it should be raced
+    Replays{
+        on_play: Rc<dyn Fn(replay::Replay, f64)>,
+        on_race: Rc<dyn Fn(replay::Replay)>,
+    },
+    /// `on_start` is called when the host starts a race and `on_board`
+    /// with the scoreboard and the player's name. `get_pool` gives the
+    /// Stratagems a hosted race is run with.
+    LanRace{
+        on_start: race_page::StartRace,
+        on_board: race_page::ShowBoard,
+        get_pool: race_page::GetPool,
+    },
+    /// `on_train` is called when a rolled loadout should be trained
+    Challenge{
+        on_train: Rc<dyn Fn(loadout::Loadout)>,
+    },
     KeycodeTrainer{
         stratagem_picture: &'a gtk::Picture,
         prompt: &'a gtk::Label,
-        keycode: &'a gtk::Label
+        keycode: &'a gtk::Label,
+        last_attempt: &'a gtk::Label,
+        /// The menus and buttons left of the title
+        controls: &'a gtk::Box,
+        /// What runs right now, like a game of Stratagem Hero or a race
+        status: &'a gtk::Box,
+        ghost_arrows: &'a gtk::Label,
     }
 }
 
 pub fn create_main_page(page: AvailablePages) -> adw::NavigationPage{
     match page {
-        AvailablePages::KeycodeTrainer{stratagem_picture, prompt, keycode} =>
-            create_keycode_page(stratagem_picture, prompt, keycode),
+        AvailablePages::KeycodeTrainer{stratagem_picture, prompt, keycode, last_attempt,
+                                       controls, status, ghost_arrows} =>
+            create_keycode_page(stratagem_picture, prompt, keycode, last_attempt, controls,
+                                status, ghost_arrows),
+        AvailablePages::Encyclopedia => encyclopedia::create_encyclopedia_page(),
+        AvailablePages::LoadoutBuilder{on_train} =>
+            loadout_builder::create_loadout_page(on_train),
+        AvailablePages::SquadPlanner => squad_planner::create_squad_page(),
+        AvailablePages::ShipModules => ship_modules::create_ship_modules_page(),
+        AvailablePages::Statistics => stats_page::create_stats_page(),
+        AvailablePages::Progress => progress_page::create_progress_page(),
+        AvailablePages::HighScores{on_replay, on_race} =>
+            highscores_page::create_highscores_page(on_replay, on_race),
+        AvailablePages::Replays{on_play, on_race} =>
+            replays_page::create_replays_page(on_play, on_race),
+        AvailablePages::LanRace{on_start, on_board, get_pool} =>
+            race_page::create_race_page(on_start, on_board, get_pool),
+        AvailablePages::Challenge{on_train} =>
+            challenge_generator::create_challenge_page(on_train),
         _ => create_wip_page(),
     }
 }
 
 fn create_keycode_page(stratagem_picture: &gtk::Picture,
                        prompt: &gtk::Label,
-                       keycode: &gtk::Label)
+                       keycode: &gtk::Label,
+                       last_attempt: &gtk::Label,
+                       controls: &gtk::Box,
+                       status: &gtk::Box,
+                       ghost_arrows: &gtk::Label)
                        -> adw::NavigationPage {
     let toolbar = adw::ToolbarView::new();
     let top_bar = gtk::CenterBox::builder()
@@ -86,14 +170,18 @@ fn create_keycode_page(stratagem_picture: &gtk::Picture,
         .margin_bottom(15)
         .margin_end(15)
         .margin_start(15)
+        .start_widget(controls)
         .center_widget(prompt)
         .css_classes(*&["keycode-title"])
         .end_widget(&gtk::WindowControls::new(gtk::PackType::End))
         .build();
     toolbar.add_top_bar(&top_bar);
     let main_box = gtk::Box::new(gtk::Orientation::Vertical, 6);
+    gtk::prelude::BoxExt::append(&main_box, status);
     gtk::prelude::BoxExt::append(&main_box, stratagem_picture);
     gtk::prelude::BoxExt::append(&main_box, keycode);
+    gtk::prelude::BoxExt::append(&main_box, ghost_arrows);
+    gtk::prelude::BoxExt::append(&main_box, last_attempt);
     toolbar.set_content(Some(&main_box));
     adw::NavigationPage::builder()
         .title("Keycode Trainer")
@@ -101,6 +189,43 @@ fn create_keycode_page(stratagem_picture: &gtk::Picture,
         .build()
 }
 
+/// Creates a page with a big title in its top bar, like the keycode trainer,
+/// and the content below it. The start widget goes to the left of the title,
+/// for things like a menu that belongs to the whole page.
+pub fn create_titled_page(title: &str,
+                          start_widget: Option<&gtk::Widget>,
+                          content: &impl gtk::prelude::IsA<gtk::Widget>)
+                          -> adw::NavigationPage {
+    let toolbar = adw::ToolbarView::new();
+    let top_bar = gtk::CenterBox::builder()
+        .margin_top(15)
+        .margin_bottom(15)
+        .margin_end(15)
+        .margin_start(15)
+        .center_widget(&gtk::Label::new(Some(title)))
+        .css_classes(*&["keycode-title"])
+        .end_widget(&gtk::WindowControls::new(gtk::PackType::End))
+        .build();
+    top_bar.set_start_widget(start_widget);
+    toolbar.add_top_bar(&top_bar);
+    toolbar.set_content(Some(content));
+    adw::NavigationPage::builder()
+        .title(title)
+        .child(&toolbar)
+        .build()
+}
+
+/// Wraps the content of a page so it scrolls and doesn't get too wide
+pub fn create_scrolled_content(content: &impl gtk::prelude::IsA<gtk::Widget>)
+                               -> gtk::ScrolledWindow {
+    gtk::ScrolledWindow::builder()
+        .hscrollbar_policy(gtk::PolicyType::Never)
+        .child(&adw::Clamp::builder()
+               .child(content)
+               .build())
+        .build()
+}
+
 fn create_wip_page() -> adw::NavigationPage {
     adw::NavigationPage::builder()
         .title("Work in Progress...")
@@ -110,3 +235,202 @@ fn create_wip_page() -> adw::NavigationPage {
                .build())
         .build()
 }
+
+/// Loads the icon of a Stratagem as a square Picture with the given size in
+/// pixels, for lists and other places where the big picture is too much
+pub fn create_stratagem_icon(stratagem: &db::Stratagem, size: i32) -> gtk::Picture {
+    let scaled = gtk::gdk_pixbuf::Pixbuf::from_file_at_scale(
+        stratagem.get_image_path(), size, size, true).unwrap();
+    let icon = gtk::Picture::for_paintable(&gtk::gdk::Texture::for_pixbuf(&scaled));
+    icon.set_can_shrink(false);
+    icon.set_alternative_text(Some(&stratagem.to_string()));
+    icon
+}
+
+/// Formats a duration the way the game shows it, "3 s" for short and "8:00"
+/// for anything longer than a minute
+pub fn format_duration(duration: std::time::Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{secs} s")
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+/// Formats a measured time precisely, "412 ms" below a second and "1.84 s"
+/// above
+pub fn format_short_duration(duration: std::time::Duration) -> String {
+    if duration.as_millis() < 1000 {
+        format!("{} ms", duration.as_millis())
+    } else {
+        format!("{:.2} s", duration.as_secs_f64())
+    }
+}
+
+/// A short line with the cooldown, call-in time and uses of a Stratagem, for
+/// subtitles and tooltips
+pub fn get_stats_summary(stats: &db::StratagemStats) -> String {
+    let mut parts = Vec::new();
+    if !stats.cooldown.is_zero() {
+        parts.push(format!("Cooldown {}", format_duration(stats.cooldown)));
+    }
+    if !stats.call_in.is_zero() {
+        parts.push(format!("Call-in {}", format_duration(stats.call_in)));
+    }
+    match stats.uses {
+        Some(1) => parts.push("1 use".to_string()),
+        Some(uses) => parts.push(format!("{uses} uses")),
+        None => (),
+    }
+    parts.join(" · ")
+}
+
+/// The name of a Stratagem with its stats below, with the owned ship
+/// upgrades applied, for tooltips of icons
+pub fn get_stratagem_tooltip(stratagem: &db::Stratagem, upgrades: &ship::ShipUpgrades) -> String {
+    let summary = get_stats_summary(&upgrades.get_effective_stats(stratagem));
+    if summary.is_empty() {
+        stratagem.to_string()
+    } else {
+        format!("{stratagem}\n{summary}")
+    }
+}
+
+/// The arrows of a keycode as Pango markup, for a label that follows the keys
+/// someone presses. Correct keys are dimmed, a wrong key is shown in place of
+/// the right one in red with a wavy underline, so it stands out without
+/// telling colors apart. With high contrast, correct keys are underlined
+/// instead and a wrong key gets a red box.
+pub fn get_keycode_markup(keycode: &[db::Code], pressed: &[db::Code]) -> String {
+    let (done, wrong) = match accessibility::is_high_contrast() {
+        true => ("underline=\"double\"", "background=\"#e01b24\" foreground=\"white\""),
+        false => ("alpha=\"35%\"", "foreground=\"#e01b24\" underline=\"error\""),
+    };
+    let theme = accessibility::get_arrow_theme();
+    let keys: Vec<String> = keycode.iter()
+        .enumerate()
+        .map(|(position, code)| match pressed.get(position) {
+            Some(key) if key == code => theme.get_key_markup(*code, done),
+            Some(key) => theme.get_key_markup(*key, wrong),
+            None => theme.get_key_markup(*code, ""),
+        })
+        .collect();
+    keys.join(theme.get_separator())
+}
+
+/// A single key drawn like the arrows of the chosen theme
+pub fn get_arrow(code: &db::Code) -> &'static str {
+    accessibility::get_arrow_theme().get_symbol(*code)
+}
+
+/// A keycode in words, like "Down, Down, Up, Right", for screen readers
+pub fn describe_keycode(keycode: &[db::Code]) -> String {
+    let codes: Vec<String> = keycode.iter().map(|code| code.to_string()).collect();
+    codes.join(", ")
+}
+
+/// Shows the arrows of a keycode on a label that follows the keys someone
+/// presses, see `get_keycode_markup`. Screen readers get the keycode in words
+/// and how far it was entered.
+pub fn show_keycode(label: &gtk::Label, keycode: &[db::Code], pressed: &[db::Code]) {
+    label.set_markup(&get_keycode_markup(keycode, pressed));
+    gtk::prelude::AccessibleExtManual::update_property(label, &[gtk::accessible::Property::Label(
+        &describe_keycode_progress(keycode, pressed))]);
+}
+
+/// A keycode in words and how far it was entered
+pub fn describe_keycode_progress(keycode: &[db::Code], pressed: &[db::Code]) -> String {
+    let correct = keycode.iter().zip(pressed).take_while(|(code, key)| code == key).count();
+    let progress = match pressed.get(correct) {
+        Some(wrong) => format!("key {} was {wrong}", correct + 1),
+        None if correct == 0 => String::new(),
+        None => format!("{correct} of {} entered", keycode.len()),
+    };
+    match progress.is_empty() {
+        true => describe_keycode(keycode),
+        false => format!("{}; {progress}", describe_keycode(keycode)),
+    }
+}
+
+/// Shows the arrows of a keycode, each on a background that gets redder the
+/// more often that key is entered wrong. The tooltips tell the numbers.
+pub fn create_mistake_heatmap(mistakes: &[stats::PositionMistakes]) -> gtk::Box {
+    let heatmap = gtk::Box::builder()
+        .spacing(3)
+        .halign(gtk::Align::Start)
+        .valign(gtk::Align::Center)
+        .build();
+    for m in mistakes {
+        // Five steps from "never wrong" to "wrong at least every second time"
+        let heat = ((m.get_rate() * 8.0).ceil() as u32).min(4);
+        let mut tooltip = format!("{} of {} wrong", m.mistakes, m.reached);
+        if let Some((wrong, _)) = m.wrong_keys.first() {
+            tooltip += &format!(", mostly {} instead of {}", get_arrow(wrong), get_arrow(&m.expected));
+        }
+        let cell = gtk::Label::builder()
+            .label(get_arrow(&m.expected))
+            .tooltip_text(tooltip)
+            .css_classes(*&["heatmap-cell"])
+            .build();
+        gtk::prelude::WidgetExt::add_css_class(&cell, &format!("heat-{heat}"));
+        gtk::prelude::BoxExt::append(&heatmap, &cell);
+    }
+    heatmap
+}
+
+/// Describes the mistake made most often on a Stratagem, like "At key 2 you
+/// press ↑ instead of ↓"
+pub fn describe_typical_mistake(mistakes: &[stats::PositionMistakes]) -> Option<String> {
+    stats::get_typical_mistake(mistakes).map(|(position, expected, wrong)| format!(
+        "At key {} you press {} instead of {}", position + 1, get_arrow(&wrong), get_arrow(&expected)))
+}
+
+/// Creates a DropDown listing every known game patch, oldest first, with the
+/// given patch selected. Use `get_selected_patch` to read it back.
+pub fn create_patch_dropdown(selected: db::GamePatch) -> gtk::DropDown {
+    let names: Vec<String> = db::GamePatch::iter().map(|p| p.to_string()).collect();
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    let dropdown = gtk::DropDown::from_strings(&names);
+    let position = db::GamePatch::iter().position(|p| p == selected).unwrap_or(0);
+    dropdown.set_selected(position as u32);
+    dropdown
+}
+
+/// Returns the patch currently selected in a DropDown made by
+/// `create_patch_dropdown`
+pub fn get_selected_patch(dropdown: &gtk::DropDown) -> db::GamePatch {
+    db::GamePatch::iter()
+        .nth(dropdown.selected() as usize)
+        .unwrap_or_else(db::GamePatch::latest)
+}
+
+/// Creates a grid with a button for every Stratagem that is currently in the
+/// game. `on_pick` is called with the Stratagem whose button was clicked.
+pub fn create_catalog_picker(on_pick: impl Fn(db::Stratagem) + Clone + 'static) -> gtk::FlowBox {
+    let catalog = gtk::FlowBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .homogeneous(true)
+        .max_children_per_line(8)
+        .build();
+    let mut buttons = Vec::new();
+    for stratagem in db::Stratagem::iter().filter(|s| s.is_in_patch(db::GamePatch::latest())) {
+        let button = gtk::Button::builder()
+            .child(&create_stratagem_icon(&stratagem, 48))
+            .css_classes(*&["flat"])
+            .build();
+        let on_pick = on_pick.clone();
+        gtk::prelude::ButtonExt::connect_clicked(&button, move |_| on_pick(stratagem));
+        catalog.append(&button);
+        buttons.push((button, stratagem));
+    }
+    // The ship upgrades may have changed while the page was hidden
+    gtk::prelude::WidgetExt::connect_map(&catalog, move |_| {
+        let (upgrades, _) = ship::load_upgrades();
+        for (button, stratagem) in buttons.iter() {
+            gtk::prelude::WidgetExt::set_tooltip_text(
+                button, Some(&get_stratagem_tooltip(stratagem, &upgrades)));
+        }
+    });
+    catalog
+}
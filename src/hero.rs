@@ -0,0 +1,236 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// Stratagem Hero, like the arcade machine on the Super Destroyer: codes have
+// to be entered against the clock, every correct code buys a little time and
+// every finished round fills the clock up again. The game ends when the time
+// runs out. The scoring loosely follows the arcade machine.
+
+use std::time::Duration;
+use std::time::SystemTime;
+
+use crate::db;
+use crate::picker;
+use crate::replay;
+use crate::stats;
+
+/// Points for every key of a correct code
+const POINTS_PER_KEY: u32 = 5;
+/// Points for finishing a round, on top of `POINTS_PER_ROUND` for every round
+/// before it
+const ROUND_BONUS: u32 = 75;
+const POINTS_PER_ROUND: u32 = 25;
+/// Points for a round without a wrong key
+const PERFECT_BONUS: u32 = 100;
+/// Points for every second left on the clock when a round is finished
+const POINTS_PER_SECOND_LEFT: u32 = 10;
+/// The first round has this many codes, every round after it one more
+const FIRST_ROUND_SIZE: u32 = 6;
+const MAX_ROUND_SIZE: u32 = 16;
+
+#[derive(Debug, PartialEq, Eq, strum_macros::EnumString, strum_macros::Display,
+         strum_macros::EnumIter, Copy, Clone, Default)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    /// The time on the clock at the start of every round
+    pub fn get_round_time(&self) -> Duration {
+        match self {
+            Difficulty::Easy => Duration::from_secs(15),
+            Difficulty::Normal => Duration::from_secs(10),
+            Difficulty::Hard => Duration::from_secs(7),
+        }
+    }
+
+    /// The time added to the clock for every correct code
+    pub fn get_time_bonus(&self) -> Duration {
+        match self {
+            Difficulty::Easy => Duration::from_millis(1500),
+            Difficulty::Normal => Duration::from_millis(1000),
+            Difficulty::Hard => Duration::from_millis(600),
+        }
+    }
+}
+
+/// One game of Stratagem Hero. The same seed, difficulty and pool always
+/// give the same Stratagems in the same order.
+#[derive(Debug, Clone)]
+pub struct HeroGame {
+    pub seed: u64,
+    pub difficulty: Difficulty,
+    /// When the game was started
+    pub started: SystemTime,
+    pub score: u32,
+    /// Starting at 1
+    pub round: u32,
+    /// The codes that still have to be entered to finish the round
+    pub codes_left: u32,
+    pub time_left: Duration,
+    /// The Stratagems whose personal best was beaten during the game
+    pub personal_bests: Vec<db::Stratagem>,
+    /// Every Stratagem and key of the game
+    pub recorder: replay::ReplayRecorder,
+    picker: picker::StratagemPicker,
+    /// Whether the round had no wrong key so far
+    perfect: bool,
+}
+
+impl HeroGame {
+    pub fn new(seed: u64, difficulty: Difficulty) -> Self {
+        HeroGame {
+            seed,
+            difficulty,
+            started: SystemTime::now(),
+            score: 0,
+            round: 1,
+            codes_left: get_round_size(1),
+            time_left: difficulty.get_round_time(),
+            personal_bests: Vec::new(),
+            // Stratagem Hero always picks from the default pool
+            recorder: replay::ReplayRecorder::new(stats::TrainerMode::StratagemHero,
+                                                  picker::SeedCode {
+                                                      seed,
+                                                      pool: picker::SharedPool::default(),
+                                                  },
+                                                  Some(difficulty)),
+            picker: picker::StratagemPicker::new(seed),
+            perfect: true,
+        }
+    }
+
+    /// Picks the next Stratagem out of the pool
+    pub fn pick_stratagem(&mut self, pool: &[db::Stratagem]) -> db::Stratagem {
+        self.picker.pick(pool)
+    }
+
+    /// Scores a correct code with the given number of keys. Returns true if
+    /// it finished the round.
+    pub fn on_success(&mut self, keys: usize) -> bool {
+        if self.is_over() {
+            return false;
+        }
+        self.score += POINTS_PER_KEY * keys as u32;
+        self.codes_left = self.codes_left.saturating_sub(1);
+        if self.codes_left > 0 {
+            self.time_left = (self.time_left + self.difficulty.get_time_bonus())
+                .min(self.difficulty.get_round_time());
+            return false;
+        }
+
+        self.score += ROUND_BONUS + POINTS_PER_ROUND * (self.round - 1);
+        self.score += POINTS_PER_SECOND_LEFT * self.time_left.as_secs() as u32;
+        if self.perfect {
+            self.score += PERFECT_BONUS;
+        }
+        self.round += 1;
+        self.codes_left = get_round_size(self.round);
+        self.time_left = self.difficulty.get_round_time();
+        self.perfect = true;
+        true
+    }
+
+    /// A wrong key costs the perfect bonus of the round
+    pub fn on_mistake(&mut self) {
+        self.perfect = false;
+    }
+
+    /// Lets time pass on the clock
+    pub fn tick(&mut self, elapsed: Duration) {
+        self.time_left = self.time_left.saturating_sub(elapsed);
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.time_left.is_zero()
+    }
+
+    /// How full the clock is, from 0 to 1
+    pub fn get_time_share(&self) -> f64 {
+        self.time_left.as_secs_f64() / self.difficulty.get_round_time().as_secs_f64()
+    }
+}
+
+fn get_round_size(round: u32) -> u32 {
+    (FIRST_ROUND_SIZE + round - 1).min(MAX_ROUND_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_sizes() {
+        assert_eq!(get_round_size(1), FIRST_ROUND_SIZE);
+        assert_eq!(get_round_size(2), FIRST_ROUND_SIZE + 1);
+        assert_eq!(get_round_size(11), MAX_ROUND_SIZE);
+        assert_eq!(get_round_size(40), MAX_ROUND_SIZE);
+    }
+
+    #[test]
+    fn perfect_first_round() {
+        let mut game = HeroGame::new(1, Difficulty::Normal);
+        for _ in 1..FIRST_ROUND_SIZE {
+            assert!(!game.on_success(4));
+        }
+        assert_eq!(game.score, (FIRST_ROUND_SIZE - 1) * 4 * POINTS_PER_KEY);
+        assert_eq!(game.codes_left, 1);
+
+        game.tick(Duration::from_secs(3));
+        assert!(game.on_success(4));
+        assert_eq!(game.score, FIRST_ROUND_SIZE * 4 * POINTS_PER_KEY + ROUND_BONUS
+                   + 7 * POINTS_PER_SECOND_LEFT + PERFECT_BONUS);
+        assert_eq!(game.round, 2);
+        assert_eq!(game.codes_left, get_round_size(2));
+        assert_eq!(game.time_left, Difficulty::Normal.get_round_time());
+    }
+
+    #[test]
+    fn later_round_with_a_mistake() {
+        let mut game = HeroGame::new(1, Difficulty::Hard);
+        game.round = 3;
+        game.codes_left = 2;
+        game.on_mistake();
+        game.tick(Duration::from_secs(4));
+        assert!(!game.on_success(3));
+        // Correct codes buy time, but never more than a round starts with
+        assert_eq!(game.time_left, Duration::from_millis(3600));
+
+        game.time_left = Duration::from_millis(4500);
+        assert!(game.on_success(2));
+        assert_eq!(game.score, 5 * POINTS_PER_KEY + ROUND_BONUS + 2 * POINTS_PER_ROUND
+                   + 4 * POINTS_PER_SECOND_LEFT);
+        assert_eq!(game.round, 4);
+
+        // The next round may be perfect again
+        game.codes_left = 1;
+        let before = game.score;
+        assert!(game.on_success(1));
+        assert_eq!(game.score - before, POINTS_PER_KEY + ROUND_BONUS + 3 * POINTS_PER_ROUND
+                   + 7 * POINTS_PER_SECOND_LEFT + PERFECT_BONUS);
+    }
+
+    #[test]
+    fn clock_runs_out() {
+        let mut game = HeroGame::new(1, Difficulty::Easy);
+        game.tick(Duration::from_secs(5));
+        assert!(!game.is_over());
+        game.tick(Duration::from_secs(20));
+        assert!(game.is_over());
+        assert!(!game.on_success(4));
+        assert_eq!(game.score, 0);
+    }
+}
@@ -0,0 +1,221 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// The local high-score table of the timed modes. Every score remembers its
+// seed, so the same game can be played again.
+
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use crate::hero;
+use crate::stats;
+
+/// How many scores a table shows
+pub const TABLE_SIZE: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HighScore {
+    pub name: String,
+    pub mode: stats::TrainerMode,
+    pub difficulty: hero::Difficulty,
+    pub score: u32,
+    /// The round the game ended in
+    pub round: u32,
+    pub date: SystemTime,
+    pub seed: u64,
+    /// The file name of the game's replay in the replay directory
+    pub replay: Option<String>,
+}
+
+/// How a score looks on disk, the date is in seconds since the Unix epoch
+#[derive(serde::Serialize, serde::Deserialize)]
+struct HighScoreFile {
+    name: String,
+    mode: String,
+    difficulty: String,
+    score: u32,
+    round: u32,
+    date: u64,
+    seed: u64,
+    #[serde(default)]
+    replay: Option<String>,
+}
+
+impl HighScore {
+    fn from_file(file: HighScoreFile) -> Result<Self, String> {
+        Ok(HighScore {
+            mode: stats::TrainerMode::from_str(&file.mode)
+                .map_err(|_| format!("Unknown mode {} of a score was dropped", file.mode))?,
+            difficulty: hero::Difficulty::from_str(&file.difficulty)
+                .map_err(|_| format!("Unknown difficulty {} of a score was dropped",
+                                     file.difficulty))?,
+            name: file.name,
+            score: file.score,
+            round: file.round,
+            date: SystemTime::UNIX_EPOCH + Duration::from_secs(file.date),
+            seed: file.seed,
+            replay: file.replay,
+        })
+    }
+
+    fn to_file(&self) -> HighScoreFile {
+        HighScoreFile {
+            name: self.name.clone(),
+            mode: self.mode.to_string(),
+            difficulty: self.difficulty.to_string(),
+            score: self.score,
+            round: self.round,
+            date: self.date.duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            seed: self.seed,
+            replay: self.replay.clone(),
+        }
+    }
+}
+
+pub fn get_highscores_path() -> PathBuf {
+    gtk::glib::user_data_dir().join("hd2helper").join("highscores.json")
+}
+
+/// Reads every score. Scores that can't be read anymore are reported instead
+/// of breaking the whole table. A missing file just means nobody played yet.
+pub fn load_highscores() -> (Vec<HighScore>, Vec<String>) {
+    load_highscores_from(&get_highscores_path())
+}
+
+/// Writes all scores to the data file
+pub fn save_highscores(scores: &[HighScore]) -> std::io::Result<()> {
+    save_highscores_to(&get_highscores_path(), scores)
+}
+
+/// A file that can't be read as scores is moved next to itself as
+/// `highscores.json.broken`, so the next game over doesn't replace it
+fn load_highscores_from(path: &Path) -> (Vec<HighScore>, Vec<String>) {
+    let mut problems = Vec::new();
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return (Vec::new(), problems),
+        Err(e) => {
+            problems.push(format!("Could not read high scores: {e}"));
+            return (Vec::new(), problems);
+        }
+    };
+    let files: Vec<HighScoreFile> = match serde_json::from_str(&content) {
+        Ok(files) => files,
+        Err(e) => {
+            let backup = path.with_extension("json.broken");
+            problems.push(match std::fs::rename(path, &backup) {
+                Ok(()) => format!("Saved high scores are broken, they were moved to {}: {e}",
+                                  backup.display()),
+                Err(rename_error) => format!("Saved high scores are broken: {e}. \
+                                              Could not move them aside: {rename_error}"),
+            });
+            return (Vec::new(), problems);
+        }
+    };
+    let scores = files.into_iter()
+        .filter_map(|file| HighScore::from_file(file)
+                    .map_err(|problem| problems.push(problem))
+                    .ok())
+        .collect();
+    (scores, problems)
+}
+
+fn save_highscores_to(path: &Path, scores: &[HighScore]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let files: Vec<HighScoreFile> = scores.iter().map(HighScore::to_file).collect();
+    std::fs::write(path, serde_json::to_string_pretty(&files)?)
+}
+
+/// The best scores of one mode and difficulty, the highest first. Equal
+/// scores are ordered by who got there first.
+pub fn get_table(scores: &[HighScore], mode: stats::TrainerMode, difficulty: hero::Difficulty)
+                 -> Vec<&HighScore> {
+    let mut table: Vec<&HighScore> = scores.iter()
+        .filter(|s| s.mode == mode && s.difficulty == difficulty)
+        .collect();
+    table.sort_by(|a, b| b.score.cmp(&a.score).then(a.date.cmp(&b.date)));
+    table.truncate(TABLE_SIZE);
+    table
+}
+
+/// The place a new score would get in its table, starting at 0. None if it
+/// isn't good enough for the table.
+pub fn get_rank(scores: &[HighScore], new: &HighScore) -> Option<usize> {
+    let table = get_table(scores, new.mode, new.difficulty);
+    let rank = table.iter().filter(|s| s.score >= new.score).count();
+    (rank < TABLE_SIZE).then_some(rank)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hero::Difficulty;
+
+    fn score(score: u32, secs: u64, difficulty: Difficulty) -> HighScore {
+        HighScore {
+            name: format!("Diver {score}"),
+            mode: stats::TrainerMode::StratagemHero,
+            difficulty,
+            score,
+            round: 1,
+            date: SystemTime::UNIX_EPOCH + Duration::from_secs(secs),
+            seed: 7,
+            replay: None,
+        }
+    }
+
+    #[test]
+    fn tables() {
+        let mut scores: Vec<HighScore> = (0..12)
+            .map(|i| score(100 * i, i as u64, Difficulty::Normal))
+            .collect();
+        scores.push(score(500, 100, Difficulty::Normal));
+        scores.push(score(5000, 0, Difficulty::Hard));
+
+        let table = get_table(&scores, stats::TrainerMode::StratagemHero, Difficulty::Normal);
+        assert_eq!(table.len(), TABLE_SIZE);
+        let points: Vec<u32> = table.iter().map(|s| s.score).collect();
+        assert_eq!(points, [1100, 1000, 900, 800, 700, 600, 500, 500, 400, 300]);
+        // The first one to get 500 stays ahead
+        assert_eq!(table[6].date, SystemTime::UNIX_EPOCH + Duration::from_secs(5));
+
+        assert_eq!(get_rank(&scores, &score(2000, 200, Difficulty::Normal)), Some(0));
+        assert_eq!(get_rank(&scores, &score(500, 200, Difficulty::Normal)), Some(8));
+        assert_eq!(get_rank(&scores, &score(300, 200, Difficulty::Normal)), None);
+        assert_eq!(get_rank(&scores, &score(10, 200, Difficulty::Hard)), Some(1));
+    }
+
+    #[test]
+    fn broken_files_are_kept() {
+        let dir = std::env::temp_dir().join(format!("hd2helper-highscores-{}", std::process::id()));
+        let path = dir.join("highscores.json");
+        let scores = [score(300, 1, Difficulty::Normal)];
+        save_highscores_to(&path, &scores).unwrap();
+        assert_eq!(load_highscores_from(&path), (scores.to_vec(), Vec::new()));
+
+        std::fs::write(&path, "[{").unwrap();
+        let (loaded, problems) = load_highscores_from(&path);
+        assert!(loaded.is_empty());
+        assert_eq!(problems.len(), 1);
+        save_highscores_to(&path, &[]).unwrap();
+        assert_eq!(std::fs::read_to_string(path.with_extension("json.broken")).unwrap(), "[{");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
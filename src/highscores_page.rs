@@ -0,0 +1,260 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// The high-score tables of the timed modes, and the dialog at the end of a
+// game where a new score gets its name.
+
+use adw::prelude::*;
+use gtk::glib;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::SystemTime;
+use strum::IntoEnumIterator;
+
+use crate::gui_templates;
+use crate::hero;
+use crate::highscores;
+use crate::replay;
+use crate::stats;
+
+/// `on_replay` is called with the difficulty and seed of a score that should
+/// be played again, `on_race` with the replay of a score that should be
+/// raced
+pub fn create_highscores_page(on_replay: Rc<dyn Fn(hero::Difficulty, u64)>,
+                              on_race: Rc<dyn Fn(replay::Replay)>)
+                              -> adw::NavigationPage {
+    let modes: Vec<stats::TrainerMode> = stats::TrainerMode::iter()
+        .filter(|mode| mode.is_timed())
+        .collect();
+    let mode_names: Vec<String> = modes.iter().map(|m| m.to_string()).collect();
+    let mode_names: Vec<&str> = mode_names.iter().map(String::as_str).collect();
+    let difficulties: Vec<String> = hero::Difficulty::iter().map(|d| d.to_string()).collect();
+    let difficulties: Vec<&str> = difficulties.iter().map(String::as_str).collect();
+
+    let mode_row = adw::ComboRow::builder()
+        .title("Mode")
+        .model(&gtk::StringList::new(&mode_names))
+        .build();
+    let difficulty_row = adw::ComboRow::builder()
+        .title("Difficulty")
+        .model(&gtk::StringList::new(&difficulties))
+        .selected(hero::Difficulty::iter()
+                  .position(|d| d == hero::Difficulty::default())
+                  .unwrap_or(0) as u32)
+        .build();
+    let filter_list = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .css_classes(*&["boxed-list"])
+        .build();
+    filter_list.append(&mode_row);
+    filter_list.append(&difficulty_row);
+    let filter_group = adw::PreferencesGroup::builder()
+        .title("Table")
+        .build();
+    filter_group.add(&filter_list);
+
+    let score_list = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .css_classes(*&["boxed-list"])
+        .build();
+    score_list.set_placeholder(Some(&gtk::Label::builder()
+        .label("No scores yet")
+        .css_classes(*&["dim-label"])
+        .margin_top(12)
+        .margin_bottom(12)
+        .build()));
+    let score_group = adw::PreferencesGroup::builder()
+        .title("High Scores")
+        .description("Play a seed again to get the same Stratagems in the same order")
+        .build();
+    score_group.add(&score_list);
+
+    let content = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(18)
+        .margin_top(12)
+        .margin_bottom(12)
+        .build();
+    content.append(&filter_group);
+    content.append(&score_group);
+    let toasts = adw::ToastOverlay::new();
+    toasts.set_child(Some(&gui_templates::create_scrolled_content(&content)));
+
+    let refresh = {
+        let (mode_row, difficulty_row, toasts) =
+            (mode_row.clone(), difficulty_row.clone(), toasts.clone());
+        Rc::new(move || {
+            let (scores, problems) = highscores::load_highscores();
+            for problem in problems {
+                toasts.add_toast(adw::Toast::new(&problem));
+            }
+            let (Some(mode), Some(difficulty)) =
+                (modes.get(mode_row.selected() as usize),
+                 hero::Difficulty::iter().nth(difficulty_row.selected() as usize)) else {
+                return;
+            };
+            fill_scores(&score_list,
+                        &highscores::get_table(&scores, *mode, difficulty),
+                        on_replay.clone(),
+                        on_race.clone(),
+                        &toasts);
+        })
+    };
+    for row in [&mode_row, &difficulty_row] {
+        let refresh = refresh.clone();
+        row.connect_selected_notify(move |_| refresh());
+    }
+    // New scores are added at the end of every game, so read them every time
+    // the page is shown
+    content.connect_map(move |_| refresh());
+
+    gui_templates::create_titled_page("High Scores", None, &toasts)
+}
+
+fn fill_scores(list: &gtk::ListBox,
+               table: &[&highscores::HighScore],
+               on_replay: Rc<dyn Fn(hero::Difficulty, u64)>,
+               on_race: Rc<dyn Fn(replay::Replay)>,
+               toasts: &adw::ToastOverlay) {
+    list.remove_all();
+    for (rank, score) in table.iter().enumerate() {
+        let row = adw::ActionRow::builder()
+            .title(format!("{}. {}", rank + 1, glib::markup_escape_text(&score.name)))
+            .subtitle(format!("Round {} · {} · Seed {}",
+                              score.round, format_date(score.date), score.seed))
+            .build();
+        row.add_suffix(&gtk::Label::builder()
+                       .label(score.score.to_string())
+                       .css_classes(*&["numeric", "heading"])
+                       .build());
+        let replay = gtk::Button::builder()
+            .icon_name("media-playback-start-symbolic")
+            .tooltip_text("Play This Seed")
+            .valign(gtk::Align::Center)
+            .css_classes(*&["flat"])
+            .build();
+        let (difficulty, seed) = (score.difficulty, score.seed);
+        let on_replay = on_replay.clone();
+        replay.connect_clicked(move |_| on_replay(difficulty, seed));
+        row.add_suffix(&replay);
+
+        // Older scores and deleted replays have nothing to race
+        let path = score.replay.as_ref()
+            .map(|name| replay::get_replays_dir().join(name))
+            .filter(|path| path.exists());
+        if let Some(path) = path {
+            let race = gtk::Button::builder()
+                .icon_name("system-run-symbolic")
+                .tooltip_text("Race This Game")
+                .valign(gtk::Align::Center)
+                .css_classes(*&["flat"])
+                .build();
+            let (on_race, toasts) = (on_race.clone(), toasts.clone());
+            race.connect_clicked(move |_| match replay::load_replay(&path) {
+                Ok(replay) => on_race(replay),
+                Err(problem) => toasts.add_toast(adw::Toast::new(&problem)),
+            });
+            row.add_suffix(&race);
+        }
+        list.append(&row);
+    }
+}
+
+/// The local date of a score, in the format of the user's locale
+fn format_date(date: SystemTime) -> String {
+    let secs = date.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs();
+    glib::DateTime::from_unix_local(secs as i64)
+        .and_then(|date| date.format("%x"))
+        .map(|date| date.to_string())
+        .unwrap_or_default()
+}
+
+/// Shows the score of a finished game on top of the window the widget is in.
+/// If it is good enough for the table, it can be saved with a name and the
+/// path of the game's replay.
+pub fn open_game_over(game: &hero::HeroGame,
+                      replay_path: Option<&Path>,
+                      widget: &impl IsA<gtk::Widget>) {
+    let (scores, problems) = highscores::load_highscores();
+    for problem in problems {
+        eprintln!("{problem}");
+    }
+    let score = highscores::HighScore {
+        // The name of the last score is the most likely one
+        name: scores.iter()
+            .max_by_key(|s| s.date)
+            .map(|s| s.name.clone())
+            .unwrap_or_default(),
+        mode: stats::TrainerMode::StratagemHero,
+        difficulty: game.difficulty,
+        score: game.score,
+        round: game.round,
+        date: SystemTime::now(),
+        seed: game.seed,
+        replay: replay_path
+            .and_then(|path| path.file_name())
+            .map(|name| name.to_string_lossy().to_string()),
+    };
+    let rank = highscores::get_rank(&scores, &score);
+
+    let mut body = format!("{} points in round {} on {}",
+                           score.score, score.round, score.difficulty);
+    match rank {
+        Some(rank) => body += &format!(", place {} of the table.", rank + 1),
+        None => body += ".",
+    }
+    if !game.personal_bests.is_empty() {
+        let names: Vec<String> = game.personal_bests.iter().map(|s| s.to_string()).collect();
+        body += &format!("\n\nNew personal bests: {}", names.join(", "));
+    }
+
+    let dialog = adw::MessageDialog::builder()
+        .heading("Game Over")
+        .body(body)
+        .modal(true)
+        .build();
+    dialog.set_transient_for(widget.root().and_downcast::<gtk::Window>().as_ref());
+    dialog.add_response("close", "Close");
+    dialog.set_close_response("close");
+    let Some(_) = rank else {
+        dialog.present();
+        return;
+    };
+
+    let name_row = adw::EntryRow::builder()
+        .title("Name")
+        .text(&score.name)
+        .build();
+    let name_list = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .css_classes(*&["boxed-list"])
+        .build();
+    name_list.append(&name_row);
+    dialog.set_extra_child(Some(&name_list));
+    dialog.add_response("save", "Save Score");
+    dialog.set_response_appearance("save", adw::ResponseAppearance::Suggested);
+    dialog.set_default_response(Some("save"));
+
+    dialog.connect_response(Some("save"), move |_, _| {
+        let name = name_row.text().trim().to_string();
+        let mut scores = scores.clone();
+        scores.push(highscores::HighScore {
+            name: if name.is_empty() { "Helldiver".to_string() } else { name },
+            ..score.clone()
+        });
+        if let Err(e) = highscores::save_highscores(&scores) {
+            eprintln!("Could not save the high score: {e}");
+        }
+    });
+    dialog.present();
+}
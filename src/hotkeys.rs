@@ -0,0 +1,608 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// Global hotkeys, so the cooldown overlay can be used while the game has the
+// focus. Hotkeys come either from the XDG GlobalShortcuts portal, where the
+// desktop decides which keys trigger them, or straight from an evdev device
+// with our own key bindings. Either way they end up as named actions of the
+// application. The configuration is saved as JSON in the user config
+// directory. Evdev only exists on Linux, elsewhere the bindings are kept but
+// never read.
+
+use futures_util::StreamExt;
+use gtk::gio;
+use gtk::glib;
+use gtk::prelude::*;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::str::FromStr;
+#[cfg(target_os = "linux")]
+use std::time::Duration;
+use strum::IntoEnumIterator;
+
+/// The name of the keyboard `hotkeys simulate` creates
+#[cfg(target_os = "linux")]
+pub const VIRTUAL_DEVICE_NAME: &str = "hd2helper virtual keyboard";
+
+/// How long to wait before looking for an evdev device again that isn't
+/// there (yet)
+#[cfg(target_os = "linux")]
+const DEVICE_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Everything a hotkey can do. The names are also the names of the
+/// application actions, so `call-in-1` is `app.call-in-1`.
+#[derive(Debug, PartialEq, Eq, strum_macros::EnumString, strum_macros::Display,
+         strum_macros::EnumIter, Copy, Clone)]
+pub enum HotkeyAction {
+    #[strum(serialize = "call-in-1")]
+    CallIn1,
+    #[strum(serialize = "call-in-2")]
+    CallIn2,
+    #[strum(serialize = "call-in-3")]
+    CallIn3,
+    #[strum(serialize = "call-in-4")]
+    CallIn4,
+    #[strum(serialize = "new-mission")]
+    NewMission,
+}
+
+impl HotkeyAction {
+    pub fn get_description(&self) -> &'static str {
+        use HotkeyAction::*;
+        match self {
+            CallIn1 => "Call in the first Stratagem of the loadout",
+            CallIn2 => "Call in the second Stratagem of the loadout",
+            CallIn3 => "Call in the third Stratagem of the loadout",
+            CallIn4 => "Call in the fourth Stratagem of the loadout",
+            NewMission => "Reset all cooldowns for a new mission",
+        }
+    }
+
+    /// The key bound to the action when reading from an evdev device, until
+    /// the user binds another one
+    pub fn get_default_key(&self) -> &'static str {
+        use HotkeyAction::*;
+        match self {
+            CallIn1 => "KEY_KP1",
+            CallIn2 => "KEY_KP2",
+            CallIn3 => "KEY_KP3",
+            CallIn4 => "KEY_KP4",
+            NewMission => "KEY_KP0",
+        }
+    }
+
+    /// The trigger suggested to the portal. The desktop may ask the user for
+    /// another one.
+    pub fn get_preferred_trigger(&self) -> &'static str {
+        use HotkeyAction::*;
+        match self {
+            CallIn1 => "ALT+1",
+            CallIn2 => "ALT+2",
+            CallIn3 => "ALT+3",
+            CallIn4 => "ALT+4",
+            NewMission => "ALT+0",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default, strum_macros::Display,
+         strum_macros::EnumIter, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HotkeyBackend {
+    /// No global hotkeys, the overlay only reacts while it has the focus
+    #[default]
+    #[strum(to_string = "Off")]
+    Disabled,
+    /// The XDG GlobalShortcuts portal
+    #[strum(to_string = "Desktop shortcuts")]
+    Portal,
+    /// An evdev device, which needs read access to it. Only on Linux.
+    #[strum(to_string = "Input device")]
+    Evdev,
+}
+
+impl HotkeyBackend {
+    /// Whether the backend works on this system at all
+    pub fn is_supported(&self) -> bool {
+        *self != HotkeyBackend::Evdev || cfg!(target_os = "linux")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HotkeyConfig {
+    pub backend: HotkeyBackend,
+    /// The evdev device, either its path below /dev/input or its name
+    pub device: Option<String>,
+    /// The evdev key of every action, by its name like `KEY_F1`
+    pub bindings: Vec<(HotkeyAction, String)>,
+}
+
+impl Default for HotkeyConfig {
+    fn default() -> Self {
+        HotkeyConfig {
+            backend: HotkeyBackend::Disabled,
+            device: None,
+            bindings: HotkeyAction::iter()
+                .map(|a| (a, a.get_default_key().to_string()))
+                .collect(),
+        }
+    }
+}
+
+/// How the configuration looks on disk. Actions and keys are stored by
+/// name, like `"call-in-1": "KEY_KP1"`.
+#[derive(serde::Serialize, serde::Deserialize, Default)]
+struct HotkeyFile {
+    #[serde(default)]
+    backend: HotkeyBackend,
+    #[serde(default)]
+    device: Option<String>,
+    #[serde(default)]
+    bindings: std::collections::BTreeMap<String, String>,
+}
+
+impl HotkeyConfig {
+    /// Returns the action bound to an evdev key
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub fn get_action(&self, key: &str) -> Option<HotkeyAction> {
+        self.bindings.iter()
+            .find(|(_, bound)| bound == key)
+            .map(|(action, _)| *action)
+    }
+
+    /// Returns the evdev key bound to an action
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub fn get_key(&self, action: HotkeyAction) -> Option<&str> {
+        self.bindings.iter()
+            .find(|(bound, _)| *bound == action)
+            .map(|(_, key)| key.as_str())
+    }
+
+    /// Binds the key to the action instead of the key it had
+    pub fn bind(&mut self, action: HotkeyAction, key: &str) {
+        self.bindings.retain(|(bound, _)| *bound != action);
+        self.bindings.push((action, key.to_string()));
+    }
+
+    /// Turns the file into a configuration. Unknown actions and keys are
+    /// dropped and reported, actions without a binding keep their default key.
+    fn from_file(file: HotkeyFile, problems: &mut Vec<String>) -> Self {
+        let mut config = HotkeyConfig {
+            backend: file.backend,
+            device: file.device,
+            ..HotkeyConfig::default()
+        };
+        for (action_name, key_name) in file.bindings {
+            let Ok(action) = HotkeyAction::from_str(&action_name) else {
+                problems.push(format!("Unknown hotkey action {action_name} was dropped"));
+                continue;
+            };
+            if !is_known_key(&key_name) {
+                problems.push(format!("Unknown key {key_name} for {action} was dropped"));
+                continue;
+            }
+            config.bind(action, &key_name);
+        }
+        config
+    }
+
+    fn to_file(&self) -> HotkeyFile {
+        HotkeyFile {
+            backend: self.backend,
+            device: self.device.clone(),
+            bindings: self.bindings.iter()
+                .map(|(action, key)| (action.to_string(), key.clone()))
+                .collect(),
+        }
+    }
+}
+
+/// Whether evdev has a key with the name. Without evdev every name is kept,
+/// so the configuration survives a trip to another system.
+fn is_known_key(name: &str) -> bool {
+    #[cfg(target_os = "linux")]
+    return evdev::Key::from_str(name).is_ok();
+    #[cfg(not(target_os = "linux"))]
+    return !name.is_empty();
+}
+
+pub fn get_hotkeys_path() -> PathBuf {
+    glib::user_config_dir().join("hd2helper").join("hotkeys.json")
+}
+
+/// Reads the hotkey configuration. A missing file means hotkeys were never
+/// set up, so they are disabled.
+pub fn load_config() -> (HotkeyConfig, Vec<String>) {
+    let mut problems = Vec::new();
+    let content = match std::fs::read_to_string(get_hotkeys_path()) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound =>
+            return (HotkeyConfig::default(), problems),
+        Err(e) => {
+            problems.push(format!("Could not read hotkeys: {e}"));
+            return (HotkeyConfig::default(), problems);
+        }
+    };
+    match serde_json::from_str(&content) {
+        Ok(file) => {
+            let config = HotkeyConfig::from_file(file, &mut problems);
+            (config, problems)
+        }
+        Err(e) => {
+            problems.push(format!("Saved hotkeys are broken: {e}"));
+            (HotkeyConfig::default(), problems)
+        }
+    }
+}
+
+pub fn save_config(config: &HotkeyConfig) -> std::io::Result<()> {
+    let path = get_hotkeys_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(&config.to_file())?)
+}
+
+/// Adds an application action for every hotkey action and starts listening
+/// for the configured hotkeys. The actions are passed on to the first window
+/// that has them in its `overlay` action group, windows that were focused
+/// last come first.
+pub fn start(application: &gtk::Application) {
+    for action in HotkeyAction::iter() {
+        let app_action = gio::SimpleAction::new(&action.to_string(), None);
+        let weak_application = application.downgrade();
+        app_action.connect_activate(move |_, _| {
+            let Some(application) = weak_application.upgrade() else {
+                return;
+            };
+            let name = format!("overlay.{action}");
+            for window in application.windows() {
+                if window.activate_action(&name, None).is_ok() {
+                    break;
+                }
+            }
+        });
+        application.add_action(&app_action);
+    }
+
+    let (config, problems) = load_config();
+    for problem in problems {
+        eprintln!("{problem}");
+    }
+    let weak_application = application.downgrade();
+    listen(&config, move |action| {
+        if let Some(application) = weak_application.upgrade() {
+            application.activate_action(&action.to_string(), None);
+        }
+    });
+}
+
+/// Calls `on_action` on the main context for every hotkey the configured
+/// backend reports. Problems are printed, hotkeys are nice to have and not
+/// worth interrupting anyone for.
+pub fn listen(config: &HotkeyConfig, on_action: impl Fn(HotkeyAction) + 'static) {
+    match config.backend {
+        HotkeyBackend::Disabled => (),
+        HotkeyBackend::Portal => {
+            glib::spawn_future_local(async move {
+                if let Err(e) = listen_portal(Rc::new(on_action)).await {
+                    eprintln!("Global shortcuts portal failed: {e}");
+                }
+            });
+        }
+        #[cfg(not(target_os = "linux"))]
+        HotkeyBackend::Evdev => eprintln!("Evdev hotkeys only work on Linux"),
+        #[cfg(target_os = "linux")]
+        HotkeyBackend::Evdev => {
+            let Some(device) = config.device.clone() else {
+                eprintln!("Evdev hotkeys need a device, see `hd2helper hotkeys devices`");
+                return;
+            };
+            // Reading the device blocks, so it gets its own thread
+            let (sender, receiver) = async_channel::unbounded();
+            let config = config.clone();
+            std::thread::spawn(move || listen_evdev(&device, &config, sender));
+            glib::spawn_future_local(async move {
+                while let Ok(action) = receiver.recv().await {
+                    on_action(action);
+                }
+            });
+        }
+    }
+}
+
+async fn listen_portal(on_action: Rc<dyn Fn(HotkeyAction)>) -> ashpd::Result<()> {
+    use ashpd::desktop::global_shortcuts::{GlobalShortcuts, NewShortcut};
+
+    let portal = GlobalShortcuts::new().await?;
+    let session = portal.create_session().await?;
+    let shortcuts: Vec<NewShortcut> = HotkeyAction::iter()
+        .map(|action| NewShortcut::new(action.to_string(), action.get_description())
+             .preferred_trigger(action.get_preferred_trigger()))
+        .collect();
+    portal.bind_shortcuts(&session, &shortcuts, &ashpd::WindowIdentifier::default())
+        .await?
+        .response()?;
+
+    let mut activated = portal.receive_activated().await?;
+    while let Some(event) = activated.next().await {
+        if let Ok(action) = HotkeyAction::from_str(event.shortcut_id()) {
+            on_action(action);
+        }
+    }
+    Ok(())
+}
+
+/// Reads key presses from the device and sends the bound actions until the
+/// receiver is gone. A device that isn't there (yet) or goes away is looked
+/// for again, so virtual devices can come and go.
+#[cfg(target_os = "linux")]
+fn listen_evdev(device: &str, config: &HotkeyConfig, sender: async_channel::Sender<HotkeyAction>) {
+    let mut reported = false;
+    loop {
+        let Some(mut input) = open_device(device) else {
+            if !reported {
+                eprintln!("Waiting for input device {device}");
+                reported = true;
+            }
+            std::thread::sleep(DEVICE_RETRY_INTERVAL);
+            continue;
+        };
+        reported = false;
+        loop {
+            let events = match input.fetch_events() {
+                Ok(events) => events,
+                Err(e) => {
+                    eprintln!("Lost input device {device}: {e}");
+                    break;
+                }
+            };
+            for event in events {
+                // 1 is a key press, 0 a release and 2 a repeat
+                let evdev::InputEventKind::Key(key) = event.kind() else {
+                    continue;
+                };
+                if event.value() != 1 {
+                    continue;
+                }
+                if let Some(action) = config.get_action(&format!("{key:?}")) {
+                    if sender.send_blocking(action).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Opens an evdev device by its path or by its name
+#[cfg(target_os = "linux")]
+fn open_device(device: &str) -> Option<evdev::Device> {
+    if device.starts_with('/') {
+        return evdev::Device::open(device).ok();
+    }
+    evdev::enumerate()
+        .find(|(_, input)| input.name() == Some(device))
+        .map(|(_, input)| input)
+}
+
+const CLI_USAGE: &str = "Usage: hd2helper hotkeys <COMMAND>
+
+Sets up global hotkeys for the cooldown overlay.
+
+Commands:
+  devices                Lists the evdev devices that have keys (Linux)
+  enable portal          Uses the desktop's global shortcuts portal
+  enable evdev <DEVICE>  Reads keys from an evdev device, by path or name
+  bind <ACTION> <KEY>    Binds an evdev key like KEY_F1 to an action
+  disable                Turns global hotkeys off
+  listen                 Prints the actions of the configured hotkeys (Linux)
+  simulate <ACTION>      Presses the key bound to the action on a virtual
+                         uinput keyboard, to test the evdev setup (Linux)
+
+Actions: call-in-1, call-in-2, call-in-3, call-in-4, new-mission";
+
+/// Runs `hd2helper hotkeys` without starting the GUI. `args` are the
+/// arguments after `hotkeys`.
+pub fn run_cli(args: &[String]) -> glib::ExitCode {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let (mut config, problems) = load_config();
+    for problem in problems {
+        eprintln!("{problem}");
+    }
+    match args.as_slice() {
+        #[cfg(target_os = "linux")]
+        ["devices"] => {
+            for (path, input) in evdev::enumerate() {
+                if input.supported_keys().is_some() {
+                    println!("{}  {}", path.display(), input.name().unwrap_or("Unnamed"));
+                }
+            }
+            glib::ExitCode::SUCCESS
+        }
+        ["enable", "portal"] => {
+            config.backend = HotkeyBackend::Portal;
+            store_config(&config)
+        }
+        ["enable", "evdev", device] => {
+            config.backend = HotkeyBackend::Evdev;
+            config.device = Some(device.to_string());
+            store_config(&config)
+        }
+        ["bind", action, key] => {
+            let Ok(action) = HotkeyAction::from_str(action) else {
+                return cli_error(&format!("Unknown action {action}"));
+            };
+            if !is_known_key(key) {
+                return cli_error(&format!("Unknown key {key}, keys look like KEY_F1"));
+            }
+            config.bind(action, key);
+            store_config(&config)
+        }
+        ["disable"] => {
+            config.backend = HotkeyBackend::Disabled;
+            store_config(&config)
+        }
+        #[cfg(target_os = "linux")]
+        ["listen"] => {
+            if config.backend == HotkeyBackend::Disabled {
+                return cli_error("Hotkeys are disabled, enable them first");
+            }
+            listen(&config, |action| println!("{action}"));
+            glib::MainLoop::new(None, false).run();
+            glib::ExitCode::SUCCESS
+        }
+        #[cfg(target_os = "linux")]
+        ["simulate", action] => {
+            let Ok(action) = HotkeyAction::from_str(action) else {
+                return cli_error(&format!("Unknown action {action}"));
+            };
+            let Some(key) = config.get_key(action) else {
+                return cli_error(&format!("No key is bound to {action}"));
+            };
+            match simulate(key) {
+                Ok(()) => glib::ExitCode::SUCCESS,
+                Err(e) => {
+                    eprintln!("Could not use a virtual keyboard: {e}");
+                    glib::ExitCode::FAILURE
+                }
+            }
+        }
+        ["--help"] | [] => {
+            println!("{CLI_USAGE}");
+            glib::ExitCode::SUCCESS
+        }
+        _ => cli_error(&format!("Unknown command {}", args.join(" "))),
+    }
+}
+
+/// Creates a virtual keyboard, presses the key once and removes the keyboard
+/// again. Listeners need a moment to find a new device, so this waits before
+/// and after pressing.
+#[cfg(target_os = "linux")]
+fn simulate(key: &str) -> std::io::Result<()> {
+    let key = evdev::Key::from_str(key).map_err(|_| std::io::Error::new(
+        std::io::ErrorKind::InvalidInput, format!("Unknown key {key}")))?;
+    let mut keys = evdev::AttributeSet::<evdev::Key>::new();
+    keys.insert(key);
+    let mut keyboard = evdev::uinput::VirtualDeviceBuilder::new()?
+        .name(VIRTUAL_DEVICE_NAME)
+        .with_keys(&keys)?
+        .build()?;
+    std::thread::sleep(DEVICE_RETRY_INTERVAL * 2);
+    keyboard.emit(&[evdev::InputEvent::new(evdev::EventType::KEY, key.code(), 1)])?;
+    keyboard.emit(&[evdev::InputEvent::new(evdev::EventType::KEY, key.code(), 0)])?;
+    std::thread::sleep(DEVICE_RETRY_INTERVAL);
+    Ok(())
+}
+
+fn store_config(config: &HotkeyConfig) -> glib::ExitCode {
+    match save_config(config) {
+        Ok(()) => glib::ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Could not save hotkeys: {e}");
+            glib::ExitCode::FAILURE
+        }
+    }
+}
+
+fn cli_error(message: &str) -> glib::ExitCode {
+    eprintln!("{message}\n\n{CLI_USAGE}");
+    glib::ExitCode::FAILURE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_keeps_known_bindings_and_drops_the_rest() {
+        let file: HotkeyFile = serde_json::from_str(r#"{
+            "backend": "evdev",
+            "device": "/dev/input/event3",
+            "bindings": {
+                "call-in-1": "KEY_F1",
+                "call-in-9": "KEY_F2",
+                "new-mission": ""
+            }
+        }"#).unwrap();
+        let mut problems = Vec::new();
+        let config = HotkeyConfig::from_file(file, &mut problems);
+        assert_eq!(config.backend, HotkeyBackend::Evdev);
+        assert_eq!(config.device.as_deref(), Some("/dev/input/event3"));
+        assert_eq!(config.get_key(HotkeyAction::CallIn1), Some("KEY_F1"));
+        // Dropped bindings keep their default key
+        assert_eq!(config.get_key(HotkeyAction::NewMission), Some("KEY_KP0"));
+        assert_eq!(problems.len(), 2);
+    }
+
+    #[test]
+    fn empty_file_is_the_default() {
+        let file: HotkeyFile = serde_json::from_str("{}").unwrap();
+        let mut problems = Vec::new();
+        assert_eq!(HotkeyConfig::from_file(file, &mut problems), HotkeyConfig::default());
+        assert!(problems.is_empty());
+    }
+
+    #[test]
+    fn file_round_trip() {
+        let mut config = HotkeyConfig {
+            backend: HotkeyBackend::Portal,
+            ..HotkeyConfig::default()
+        };
+        config.bind(HotkeyAction::CallIn3, "KEY_F3");
+        let file = serde_json::to_string(&config.to_file()).unwrap();
+        let mut problems = Vec::new();
+        let loaded = HotkeyConfig::from_file(serde_json::from_str(&file).unwrap(), &mut problems);
+        assert!(problems.is_empty());
+        assert_eq!(loaded.backend, HotkeyBackend::Portal);
+        assert_eq!(loaded.get_key(HotkeyAction::CallIn3), Some("KEY_F3"));
+    }
+
+    #[test]
+    fn actions_and_keys_are_found_both_ways() {
+        let mut config = HotkeyConfig::default();
+        assert_eq!(config.get_action("KEY_KP2"), Some(HotkeyAction::CallIn2));
+        assert_eq!(config.get_action("KEY_F12"), None);
+
+        config.bind(HotkeyAction::CallIn2, "KEY_F12");
+        assert_eq!(config.get_action("KEY_F12"), Some(HotkeyAction::CallIn2));
+        assert_eq!(config.get_action("KEY_KP2"), None);
+        assert_eq!(config.get_key(HotkeyAction::CallIn2), Some("KEY_F12"));
+        assert_eq!(config.bindings.len(), HotkeyAction::iter().count());
+    }
+
+    /// Needs write access to /dev/uinput and read access to the new device,
+    /// run it with `cargo test -- --ignored`
+    #[cfg(target_os = "linux")]
+    #[test]
+    #[ignore]
+    fn virtual_keyboard_triggers_the_bound_action() {
+        let mut config = HotkeyConfig {
+            backend: HotkeyBackend::Evdev,
+            device: Some(VIRTUAL_DEVICE_NAME.to_string()),
+            ..HotkeyConfig::default()
+        };
+        config.bind(HotkeyAction::NewMission, "KEY_F9");
+
+        let (sender, receiver) = async_channel::unbounded();
+        let listening = config.clone();
+        std::thread::spawn(move || listen_evdev(VIRTUAL_DEVICE_NAME, &listening, sender));
+        simulate("KEY_F9").unwrap();
+
+        let action = std::thread::spawn(move || receiver.recv_blocking())
+            .join()
+            .unwrap();
+        assert_eq!(action, Ok(HotkeyAction::NewMission));
+    }
+}
@@ -0,0 +1,292 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// Loadouts are sets of up to four Stratagems, just like the ones you pick
+// before dropping into a mission. They are saved as JSON in the user config
+// directory.
+
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::db;
+
+/// How many Stratagems fit into one loadout
+pub const LOADOUT_SIZE: usize = 4;
+
+/// Every share code starts with this, so random text from the clipboard is
+/// not mistaken for a loadout
+const SHARE_CODE_PREFIX: &str = "HD2";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Loadout {
+    pub name: String,
+    pub stratagems: Vec<db::Stratagem>,
+    pub favorite: bool,
+}
+
+/// How a loadout looks on disk. Stratagems are stored by name, so entries
+/// that were renamed or removed from the catalog can be reported instead of
+/// breaking the whole file.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct LoadoutFile {
+    name: String,
+    stratagems: Vec<String>,
+    #[serde(default)]
+    favorite: bool,
+}
+
+impl Loadout {
+    pub fn new(name: &str) -> Self {
+        Loadout {
+            name: name.to_string(),
+            stratagems: Vec::new(),
+            favorite: false,
+        }
+    }
+
+    /// Adds a Stratagem to the loadout. Returns false if the loadout is
+    /// already full or has this Stratagem in it.
+    pub fn add(&mut self, stratagem: db::Stratagem) -> bool {
+        if self.stratagems.len() >= LOADOUT_SIZE || self.stratagems.contains(&stratagem) {
+            return false;
+        }
+        self.stratagems.push(stratagem);
+        true
+    }
+
+    /// Names of all Stratagems in this loadout, separated by commas
+    pub fn get_summary(&self) -> String {
+        self.stratagems.iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    /// Checks a loadout read from disk against the catalog. Unknown and
+    /// duplicate Stratagems as well as everything past the fourth slot are
+    /// dropped, and a message is added to `problems` for each of them.
+    fn from_file(file: LoadoutFile, problems: &mut Vec<String>) -> Self {
+        let mut loadout = Loadout::new(&file.name);
+        loadout.favorite = file.favorite;
+        for name in file.stratagems {
+            match db::Stratagem::from_str(&name) {
+                Ok(stratagem) => {
+                    if !loadout.add(stratagem) {
+                        problems.push(format!("{}: dropped {name}, it is a duplicate or the loadout is full",
+                                              file.name));
+                    }
+                }
+                Err(_) => problems.push(format!("{}: unknown Stratagem {name}", file.name)),
+            }
+        }
+        loadout
+    }
+
+    /// Returns a short code for sharing this loadout in chats, like
+    /// `HD2:Bug Hunt:EagleAirstrike,OrbitalLaser`
+    pub fn to_share_code(&self) -> String {
+        let stratagems: Vec<String> = self.stratagems.iter().map(|s| s.to_string()).collect();
+        format!("{SHARE_CODE_PREFIX}:{}:{}", self.name, stratagems.join(","))
+    }
+
+    /// Returns this loadout as JSON, in the same format as the config file
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.to_file())
+            .expect("A loadout can always be turned into JSON")
+    }
+
+    /// Reads a loadout from a share code or a JSON export. Stratagems that
+    /// are not in the catalog are reported in the returned messages and left
+    /// out. Fails if the text is neither of the two formats.
+    pub fn import(text: &str) -> Result<(Loadout, Vec<String>), String> {
+        let text = text.trim();
+        let file = if text.starts_with('{') {
+            serde_json::from_str(text).map_err(|e| format!("Not a valid loadout: {e}"))?
+        } else {
+            let rest = text.strip_prefix(SHARE_CODE_PREFIX)
+                .and_then(|rest| rest.strip_prefix(':'))
+                .ok_or_else(|| format!("Share codes start with {SHARE_CODE_PREFIX}:"))?;
+            // The name may contain colons itself, the Stratagems never do
+            let (name, stratagems) = rest.rsplit_once(':')
+                .ok_or("The share code has no Stratagems")?;
+            LoadoutFile {
+                name: name.to_string(),
+                stratagems: stratagems.split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(String::from)
+                    .collect(),
+                favorite: false,
+            }
+        };
+        let mut problems = Vec::new();
+        let loadout = Loadout::from_file(file, &mut problems);
+        Ok((loadout, problems))
+    }
+
+    fn to_file(&self) -> LoadoutFile {
+        LoadoutFile {
+            name: self.name.clone(),
+            stratagems: self.stratagems.iter().map(|s| s.to_string()).collect(),
+            favorite: self.favorite,
+        }
+    }
+}
+
+/// Returns the name, or the name with the first free number after it like
+/// `Bug Hunt (2)` if a loadout is already called that
+pub fn get_free_name(name: &str, loadouts: &[Loadout]) -> String {
+    let is_taken = |candidate: &str| loadouts.iter().any(|l| l.name == candidate);
+    if !is_taken(name) {
+        return name.to_string();
+    }
+    (2..)
+        .map(|number| format!("{name} ({number})"))
+        .find(|candidate| !is_taken(candidate))
+        .expect("There is always a free number")
+}
+
+/// Where the saved loadouts live
+pub fn get_loadouts_path() -> PathBuf {
+    gtk::glib::user_config_dir().join("hd2helper").join("loadouts.json")
+}
+
+/// Reads all saved loadouts. Everything that had to be fixed while checking
+/// them against the catalog is returned as human readable messages. A missing
+/// file is not a problem, there just are no loadouts yet.
+pub fn load_loadouts() -> (Vec<Loadout>, Vec<String>) {
+    load_loadouts_from(&get_loadouts_path())
+}
+
+/// Writes all loadouts to the config file, replacing what was there before
+pub fn save_loadouts(loadouts: &[Loadout]) -> std::io::Result<()> {
+    save_loadouts_to(&get_loadouts_path(), loadouts)
+}
+
+/// A file that can't be read as loadouts is moved next to itself as
+/// `loadouts.json.broken`, so the next save doesn't replace it
+fn load_loadouts_from(path: &Path) -> (Vec<Loadout>, Vec<String>) {
+    let mut problems = Vec::new();
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return (Vec::new(), problems),
+        Err(e) => {
+            problems.push(format!("Could not read saved loadouts: {e}"));
+            return (Vec::new(), problems);
+        }
+    };
+    let files: Vec<LoadoutFile> = match serde_json::from_str(&content) {
+        Ok(files) => files,
+        Err(e) => {
+            let backup = path.with_extension("json.broken");
+            problems.push(match std::fs::rename(path, &backup) {
+                Ok(()) => format!("Saved loadouts are broken, they were moved to {}: {e}",
+                                  backup.display()),
+                Err(rename_error) => format!("Saved loadouts are broken: {e}. \
+                                              Could not move them aside: {rename_error}"),
+            });
+            return (Vec::new(), problems);
+        }
+    };
+    let loadouts = files.into_iter()
+        .map(|file| Loadout::from_file(file, &mut problems))
+        .collect();
+    (loadouts, problems)
+}
+
+fn save_loadouts_to(path: &Path, loadouts: &[Loadout]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let files: Vec<LoadoutFile> = loadouts.iter().map(Loadout::to_file).collect();
+    let content = serde_json::to_string_pretty(&files)?;
+    std::fs::write(path, content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use db::Stratagem::*;
+
+    fn bug_hunt() -> Loadout {
+        Loadout {
+            name: "Bug: Hunt".to_string(),
+            stratagems: vec![EagleAirstrike, OrbitalLaser],
+            favorite: false,
+        }
+    }
+
+    #[test]
+    fn share_code_round_trip() {
+        let code = bug_hunt().to_share_code();
+        assert_eq!(code, "HD2:Bug: Hunt:EagleAirstrike,OrbitalLaser");
+        assert_eq!(Loadout::import(&code), Ok((bug_hunt(), Vec::new())));
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let mut loadout = bug_hunt();
+        loadout.favorite = true;
+        assert_eq!(Loadout::import(&loadout.to_json()), Ok((loadout, Vec::new())));
+    }
+
+    #[test]
+    fn unknown_and_extra_stratagems_are_reported() {
+        let (loadout, problems) =
+            Loadout::import(" HD2:Bug: Hunt:EagleAirstrike, Nuke,EagleAirstrike,OrbitalLaser,, \n").unwrap();
+        assert_eq!(loadout, bug_hunt());
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].contains("unknown Stratagem Nuke"));
+        assert!(problems[1].contains("dropped EagleAirstrike"));
+
+        let (full, problems) = Loadout::import(
+            "HD2:Full:Resupply,Reinforce,EagleAirstrike,OrbitalLaser,SOSBeacon").unwrap();
+        assert_eq!(full.stratagems.len(), LOADOUT_SIZE);
+        assert_eq!(problems.len(), 1);
+    }
+
+    #[test]
+    fn free_names() {
+        let mut saved = vec![bug_hunt()];
+        assert_eq!(get_free_name("Bots", &saved), "Bots");
+        assert_eq!(get_free_name("Bug: Hunt", &saved), "Bug: Hunt (2)");
+        saved.push(Loadout::new("Bug: Hunt (2)"));
+        assert_eq!(get_free_name("Bug: Hunt", &saved), "Bug: Hunt (3)");
+    }
+
+    #[test]
+    fn broken_files_are_kept() {
+        let dir = std::env::temp_dir().join(format!("hd2helper-loadouts-{}", std::process::id()));
+        let path = dir.join("loadouts.json");
+        save_loadouts_to(&path, &[bug_hunt()]).unwrap();
+        assert_eq!(load_loadouts_from(&path), (vec![bug_hunt()], Vec::new()));
+
+        std::fs::write(&path, "[{\"name\": \"Bug Hunt\", ").unwrap();
+        let (loadouts, problems) = load_loadouts_from(&path);
+        assert!(loadouts.is_empty());
+        assert_eq!(problems.len(), 1);
+        // Saving again doesn't lose what was in the broken file
+        save_loadouts_to(&path, &[]).unwrap();
+        assert_eq!(std::fs::read_to_string(path.with_extension("json.broken")).unwrap(),
+                   "[{\"name\": \"Bug Hunt\", ");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn broken_imports() {
+        assert!(Loadout::import("Bug Hunt:EagleAirstrike").is_err());
+        assert!(Loadout::import("HD2:Bug Hunt").is_err());
+        assert!(Loadout::import("{\"name\": \"Bug Hunt\"").is_err());
+    }
+}
@@ -0,0 +1,363 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// The loadout builder lets users put together loadouts from the catalog, save
+// them and start the trainer on them.
+
+use adw::prelude::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::cooldown_overlay;
+use crate::gui_templates;
+use crate::loadout;
+use crate::ship;
+
+/// The widgets and state of the loadout page. A clone is just another handle
+/// to the same page.
+#[derive(Clone)]
+struct LoadoutBuilder {
+    saved: Rc<RefCell<Vec<loadout::Loadout>>>,
+    /// The loadout that is being put together right now
+    draft: Rc<RefCell<loadout::Loadout>>,
+    name_entry: adw::EntryRow,
+    slots: gtk::Box,
+    saved_list: gtk::ListBox,
+    toasts: adw::ToastOverlay,
+    on_train: Rc<dyn Fn(loadout::Loadout)>,
+}
+
+/// Creates the loadout page. Saved loadouts are read from the config file right
+/// away and everything that didn't match the catalog is shown as a toast.
+pub fn create_loadout_page(on_train: Rc<dyn Fn(loadout::Loadout)>) -> adw::NavigationPage {
+    let (saved, problems) = loadout::load_loadouts();
+    let builder = LoadoutBuilder {
+        saved: Rc::new(RefCell::new(saved)),
+        draft: Rc::new(RefCell::new(loadout::Loadout::new(""))),
+        name_entry: adw::EntryRow::builder().title("Name").build(),
+        slots: gtk::Box::builder()
+            .spacing(12)
+            .halign(gtk::Align::Center)
+            .build(),
+        saved_list: gtk::ListBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .css_classes(*&["boxed-list"])
+            .build(),
+        toasts: adw::ToastOverlay::new(),
+        on_train,
+    };
+    for problem in problems {
+        builder.toasts.add_toast(adw::Toast::new(&problem));
+    }
+    builder.saved_list.set_placeholder(Some(&gtk::Label::builder()
+        .label("No saved loadouts yet")
+        .css_classes(*&["dim-label"])
+        .margin_top(12)
+        .margin_bottom(12)
+        .build()));
+
+    // Other pages save loadouts and ship upgrades as well, pick them up
+    // before they would be overwritten or shown outdated
+    let reload_builder = builder.clone();
+    builder.saved_list.connect_map(move |_| {
+        reload_builder.saved.replace(loadout::load_loadouts().0);
+        reload_builder.refresh_saved();
+        reload_builder.refresh_slots();
+    });
+
+    let import_button = gtk::Button::builder()
+        .label("Import")
+        .tooltip_text("Import a share code or JSON from the clipboard")
+        .valign(gtk::Align::Center)
+        .build();
+    let import_builder = builder.clone();
+    import_button.connect_clicked(move |button| {
+        let builder = import_builder.clone();
+        button.clipboard().read_text_async(gtk::gio::Cancellable::NONE, move |text| {
+            match text {
+                Ok(Some(text)) => builder.import(&text),
+                _ => builder.toasts.add_toast(adw::Toast::new("The clipboard has no text in it")),
+            }
+        });
+    });
+    let saved_group = adw::PreferencesGroup::builder()
+        .title("Saved Loadouts")
+        .header_suffix(&import_button)
+        .build();
+    saved_group.add(&builder.saved_list);
+
+    let save_button = gtk::Button::builder()
+        .label("Save")
+        .css_classes(*&["suggested-action"])
+        .valign(gtk::Align::Center)
+        .build();
+    let save_builder = builder.clone();
+    save_button.connect_clicked(move |_| save_builder.save_draft());
+    let new_group = adw::PreferencesGroup::builder()
+        .title("New Loadout")
+        .description("Click a Stratagem below to add it, click a slot to empty it")
+        .header_suffix(&save_button)
+        .build();
+    let name_list = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .css_classes(*&["boxed-list"])
+        .build();
+    name_list.append(&builder.name_entry);
+    new_group.add(&name_list);
+    new_group.add(&builder.slots);
+
+    let catalog_builder = builder.clone();
+    let catalog = gui_templates::create_catalog_picker(move |stratagem| {
+        if !catalog_builder.draft.borrow_mut().add(stratagem) {
+            catalog_builder.toasts.add_toast(adw::Toast::new(
+                "The loadout is full or already has this Stratagem"));
+        }
+        catalog_builder.refresh_slots();
+    });
+    let catalog_group = adw::PreferencesGroup::builder()
+        .title("Catalog")
+        .build();
+    catalog_group.add(&catalog);
+
+    builder.refresh_slots();
+    builder.refresh_saved();
+
+    let content = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(18)
+        .margin_top(12)
+        .margin_bottom(12)
+        .build();
+    content.append(&saved_group);
+    content.append(&new_group);
+    content.append(&catalog_group);
+    builder.toasts.set_child(Some(&gui_templates::create_scrolled_content(&content)));
+
+    gui_templates::create_titled_page("Loadouts", None, &builder.toasts)
+}
+
+impl LoadoutBuilder {
+    /// Redraws the four slots of the loadout that is being put together
+    fn refresh_slots(&self) {
+        while let Some(child) = self.slots.first_child() {
+            self.slots.remove(&child);
+        }
+        let draft = self.draft.borrow().clone();
+        let (upgrades, _) = ship::load_upgrades();
+        for slot in 0..loadout::LOADOUT_SIZE {
+            let button = match draft.stratagems.get(slot) {
+                Some(stratagem) => {
+                    let button = gtk::Button::builder()
+                        .child(&gui_templates::create_stratagem_icon(stratagem, 64))
+                        .tooltip_text(gui_templates::get_stratagem_tooltip(stratagem, &upgrades))
+                        .build();
+                    let builder = self.clone();
+                    button.connect_clicked(move |_| {
+                        builder.draft.borrow_mut().stratagems.remove(slot);
+                        builder.refresh_slots();
+                    });
+                    button
+                }
+                None => gtk::Button::builder()
+                    .icon_name("list-add-symbolic")
+                    .width_request(64)
+                    .height_request(64)
+                    .sensitive(false)
+                    .build(),
+            };
+            self.slots.append(&button);
+        }
+    }
+
+    /// Rebuilds the list of saved loadouts. Favorites are always on top.
+    fn refresh_saved(&self) {
+        self.saved_list.remove_all();
+        for (index, saved) in self.saved.borrow().iter().enumerate() {
+            let row = adw::ActionRow::builder()
+                .title(saved.name.as_str())
+                .subtitle(saved.get_summary())
+                .build();
+            let icons = gtk::Box::new(gtk::Orientation::Horizontal, 3);
+            for stratagem in saved.stratagems.iter() {
+                icons.append(&gui_templates::create_stratagem_icon(stratagem, 24));
+            }
+            row.add_prefix(&icons);
+
+            let favorite = gtk::ToggleButton::builder()
+                .icon_name(if saved.favorite { "starred-symbolic" } else { "non-starred-symbolic" })
+                .tooltip_text("Favorite")
+                .active(saved.favorite)
+                .valign(gtk::Align::Center)
+                .css_classes(*&["flat"])
+                .build();
+            let builder = self.clone();
+            favorite.connect_toggled(move |button| {
+                builder.saved.borrow_mut()[index].favorite = button.is_active();
+                builder.store();
+            });
+            row.add_suffix(&favorite);
+            row.add_suffix(&self.create_share_menu(saved));
+
+            let buttons: [(&str, &str, LoadoutAction); 4] = [
+                ("document-edit-symbolic", "Edit", LoadoutBuilder::edit),
+                ("media-playback-start-symbolic", "Train", LoadoutBuilder::train),
+                ("alarm-symbolic", "Cooldown Overlay", LoadoutBuilder::open_overlay),
+                ("user-trash-symbolic", "Delete", LoadoutBuilder::delete),
+            ];
+            for (icon, tooltip, action) in buttons {
+                let button = gtk::Button::builder()
+                    .icon_name(icon)
+                    .tooltip_text(tooltip)
+                    .valign(gtk::Align::Center)
+                    .css_classes(*&["flat"])
+                    .build();
+                let builder = self.clone();
+                button.connect_clicked(move |_| action(&builder, index));
+                row.add_suffix(&button);
+            }
+            self.saved_list.append(&row);
+        }
+    }
+
+    /// Saves the loadout that is being put together. A saved loadout with the
+    /// same name is replaced.
+    fn save_draft(&self) {
+        let name = self.name_entry.text().trim().to_string();
+        if name.is_empty() {
+            self.toasts.add_toast(adw::Toast::new("Give the loadout a name first"));
+            return;
+        }
+        let mut draft = self.draft.borrow().clone();
+        if draft.stratagems.is_empty() {
+            self.toasts.add_toast(adw::Toast::new("Add at least one Stratagem first"));
+            return;
+        }
+        draft.name = name.clone();
+        self.add_loadout(draft);
+        self.toasts.add_toast(adw::Toast::new(&format!("Saved {name}")));
+    }
+
+    /// Adds a loadout to the saved ones, replacing a loadout with the same
+    /// name but keeping whether it is a favorite
+    fn add_loadout(&self, mut loadout: loadout::Loadout) {
+        {
+            let mut saved = self.saved.borrow_mut();
+            match saved.iter_mut().find(|l| l.name == loadout.name) {
+                Some(existing) => {
+                    loadout.favorite = existing.favorite;
+                    *existing = loadout;
+                }
+                None => {
+                    loadout.favorite = false;
+                    saved.push(loadout);
+                }
+            }
+        }
+        self.store();
+    }
+
+    /// Saves a loadout that was shared as a code or as JSON. Every Stratagem
+    /// that had to be left out is shown as a toast. A saved loadout with the
+    /// same name is kept, the imported one gets a number.
+    fn import(&self, text: &str) {
+        match loadout::Loadout::import(text) {
+            Ok((mut loadout, problems)) => {
+                for problem in problems {
+                    self.toasts.add_toast(adw::Toast::new(&problem));
+                }
+                if loadout.stratagems.is_empty() {
+                    self.toasts.add_toast(adw::Toast::new("The loadout has no known Stratagems"));
+                    return;
+                }
+                let name = loadout::get_free_name(&loadout.name, &self.saved.borrow());
+                let message = match name == loadout.name {
+                    true => format!("Imported {name}"),
+                    false => format!("Imported {} as {name}, the name was taken", loadout.name),
+                };
+                loadout.name = name;
+                self.add_loadout(loadout);
+                self.toasts.add_toast(adw::Toast::new(&message));
+            }
+            Err(e) => self.toasts.add_toast(adw::Toast::new(&e)),
+        }
+    }
+
+    /// A menu to copy a loadout to the clipboard, either as a short share
+    /// code or as JSON
+    fn create_share_menu(&self, loadout: &loadout::Loadout) -> gtk::MenuButton {
+        let options = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        let exports = [
+            ("Copy Share Code", loadout.to_share_code()),
+            ("Copy as JSON", loadout.to_json()),
+        ];
+        for (label, text) in exports {
+            let button = gtk::Button::builder()
+                .label(label)
+                .css_classes(*&["flat"])
+                .build();
+            let toasts = self.toasts.clone();
+            button.connect_clicked(move |button| {
+                button.clipboard().set_text(&text);
+                toasts.add_toast(adw::Toast::new("Copied to the clipboard"));
+            });
+            options.append(&button);
+        }
+        gtk::MenuButton::builder()
+            .icon_name("send-to-symbolic")
+            .tooltip_text("Share")
+            .valign(gtk::Align::Center)
+            .css_classes(*&["flat"])
+            .popover(&gtk::Popover::builder().child(&options).build())
+            .build()
+    }
+
+    /// Loads a saved loadout into the editor
+    fn edit(&self, index: usize) {
+        let loadout = self.saved.borrow()[index].clone();
+        self.name_entry.set_text(&loadout.name);
+        self.draft.replace(loadout);
+        self.refresh_slots();
+    }
+
+    fn train(&self, index: usize) {
+        let loadout = self.saved.borrow()[index].clone();
+        (self.on_train)(loadout);
+    }
+
+    /// Opens a window that counts down the cooldowns of the loadout while
+    /// playing
+    fn open_overlay(&self, index: usize) {
+        let loadout = self.saved.borrow()[index].clone();
+        cooldown_overlay::open_cooldown_overlay(&loadout, &self.saved_list);
+    }
+
+    fn delete(&self, index: usize) {
+        let removed = self.saved.borrow_mut().remove(index);
+        self.store();
+        self.toasts.add_toast(adw::Toast::new(&format!("Deleted {}", removed.name)));
+    }
+
+    /// Sorts the saved loadouts, writes them to disk and redraws the list
+    fn store(&self) {
+        self.saved.borrow_mut().sort_by_key(|l| !l.favorite);
+        if let Err(e) = loadout::save_loadouts(&self.saved.borrow()) {
+            self.toasts.add_toast(adw::Toast::new(&format!("Could not save loadouts: {e}")));
+        }
+        // Redrawing removes the row that triggered this, so wait until its
+        // signal handler is done
+        let builder = self.clone();
+        gtk::glib::idle_add_local_once(move || builder.refresh_saved());
+    }
+}
+
+type LoadoutAction = fn(&LoadoutBuilder, usize);
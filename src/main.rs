@@ -14,74 +14,221 @@
 use adw::prelude::*;
 use gtk::{glib, Application};
 use std::rc::Rc;
-use std::cell::Cell;
-use std::cell::RefCell;
 
+mod accessibility;
+mod arrow_theme;
+mod challenge;
+mod challenge_generator;
+mod chart;
+mod cooldown;
+mod cooldown_overlay;
 mod db;
+mod encyclopedia;
+mod ghost;
 mod gui_templates;
+mod hero;
+mod highscores;
+mod highscores_page;
+mod hotkeys;
+mod loadout;
+mod loadout_builder;
+mod picker;
+mod preferences;
+mod progress;
+mod progress_page;
+mod race;
+mod race_page;
+mod replay;
+mod replays_page;
+mod session;
+mod session_summary;
+mod settings;
+mod ship;
+mod ship_modules;
+mod sound;
+mod speech;
+mod squad;
+mod squad_planner;
+mod stats;
+mod stats_page;
+mod trainer;
+mod versus;
 use gui_templates::AvailablePages::*;
 
 fn main() -> glib::ExitCode {
+    // Some things also work without the GUI
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("challenge") => return challenge::run_cli(&args[2..]),
+        Some("hotkeys") => return hotkeys::run_cli(&args[2..]),
+        _ => (),
+    }
+
+    // Normally a second start just shows the running window. To race
+    // yourself on one computer, every window needs its own instance.
+    let new_instance = args.iter().any(|arg| arg == "--new-instance");
+    let args: Vec<String> = args.into_iter().filter(|arg| arg != "--new-instance").collect();
     let application = Application::builder()
         .application_id("com.github.BjarneSeger.hd2helper")
+        .flags(if new_instance {
+            gtk::gio::ApplicationFlags::NON_UNIQUE
+        } else {
+            gtk::gio::ApplicationFlags::empty()
+        })
         .build();
 
-    application.connect_startup(|_| load_css());
+    application.connect_startup(|app| {
+        load_css();
+        hotkeys::start(app.upcast_ref());
+    });
 
     application.connect_activate(|app| {
-        // #TODO:
-        // Allow users to select only specific Stratagems
-        let mut avail_stratagems: &mut Vec<db::Stratagem> = &mut Vec::new();
-        for _ in 0..10 {
-            avail_stratagems.push(rand::random());
-        }
-
-        let global_stratagem = 
-            Rc::new(Cell::new(rand::random::<db::Stratagem>()));
+        let settings = settings::SettingsStore::load();
+        let trainer = trainer::Trainer::new(picker::StratagemPicker::new(picker::random_seed()),
+                                            settings.clone());
 
-        let current_stratagem = global_stratagem.get();
-        let prompt = gtk::Label::new(Some(
-            format!("Enter Keycode for {current_stratagem}").as_str())
-        );
+        // Which kinds of Stratagems should show up at all, sessions and games
+        let controls = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        controls.append(&trainer.create_filter_menu());
+        controls.append(&trainer.create_session_controls());
+        controls.append(&trainer.create_hero_menu());
+        controls.append(&trainer.create_versus_menu());
+        controls.append(&trainer.create_sound_menu());
+        controls.append(&trainer.create_accessibility_menu());
 
-        // Graphical representation of the stratagem...
-        let stratagem_scaled = gtk::gdk_pixbuf::Pixbuf::from_file_at_scale(
-            global_stratagem.get().get_image_path(), 3840, 2160, true).unwrap();
-        let stratagem_image = gtk::Picture::for_paintable(&gtk::gdk::Texture::for_pixbuf(&stratagem_scaled));
-        stratagem_image.set_can_shrink(true);
-        let keycode = global_stratagem.get().get_keycode();
-        // grid.attach(&stratagem_image, 0,2,1,1); 
-
-        // ... and the keycode
-        let keycode_visual = gtk::Label::new(Some(get_keycode_str(&keycode).as_str()));
-        keycode_visual.add_css_class("arrows");
-        // grid.attach(&keycode_visual, 0, 1, 1, 1);
         let right_page = gui_templates::create_main_page(KeycodeTrainer{
-            stratagem_picture: &stratagem_image,
-            prompt: &prompt,
-            keycode: &keycode_visual,
+            stratagem_picture: &trainer.picture,
+            prompt: &trainer.prompt,
+            keycode: &trainer.arrows,
+            last_attempt: &trainer.last_attempt,
+            controls: &controls,
+            status: &trainer.status,
+            ghost_arrows: &trainer.ghost_arrows,
         });
 
         let overview = gtk::ListBox::builder()
             .css_classes(*&["navigation-sidebar"])
             .build();
-        overview.append(&gtk::ListBoxRow::builder()
-                        .child(&gtk::Label::new(Some("Keycode Trainer")))
-                        .build());
-        overview.append(&gtk::ListBoxRow::builder()
-                        .child(&gtk::Label::new(Some("Coming soon...")))
-                        .build());
 
+        // Starting a loadout jumps back to the trainer, which is the first page
+        let start_training = {
+            let trainer = trainer.clone();
+            let overview = overview.clone();
+            Rc::new(move |loadout: loadout::Loadout| {
+                trainer.train_loadout(Some(loadout));
+                overview.select_row(overview.row_at_index(0).as_ref());
+            })
+        };
+
+        let replay_seed = {
+            let trainer = trainer.clone();
+            let overview = overview.clone();
+            Rc::new(move |difficulty: hero::Difficulty, seed: u64| {
+                overview.select_row(overview.row_at_index(0).as_ref());
+                trainer.start_hero(difficulty, seed);
+            })
+        };
+
+        let play_replay = {
+            let trainer = trainer.clone();
+            let overview = overview.clone();
+            Rc::new(move |replay: replay::Replay, speed: f64| {
+                overview.select_row(overview.row_at_index(0).as_ref());
+                trainer.play_replay(replay, speed);
+            })
+        };
+
+        let race_ghost = {
+            let trainer = trainer.clone();
+            let overview = overview.clone();
+            Rc::new(move |replay: replay::Replay| {
+                overview.select_row(overview.row_at_index(0).as_ref());
+                trainer.race_ghost(replay);
+            })
+        };
+
+        let start_lan_race = {
+            let trainer = trainer.clone();
+            let overview = overview.clone();
+            Rc::new(move |start: race::RaceStart, connection: Rc<race::Connection>| {
+                overview.select_row(overview.row_at_index(0).as_ref());
+                trainer.start_lan_race(start, connection);
+            })
+        };
+
+        let show_race_board = {
+            let trainer = trainer.clone();
+            Rc::new(move |board: &[race::Standing], own_name: &str| {
+                trainer.show_race_board(board, own_name);
+            })
+        };
+
+        let race_pool = {
+            let trainer = trainer.clone();
+            Rc::new(move || trainer.get_pool())
+        };
+
+        // Every page together with its title in the sidebar
+        let pages = [
+            ("Keycode Trainer", right_page),
+            ("Statistics", gui_templates::create_main_page(Statistics)),
+            ("Progress", gui_templates::create_main_page(Progress)),
+            ("High Scores", gui_templates::create_main_page(HighScores{
+                on_replay: replay_seed,
+                on_race: race_ghost.clone(),
+            })),
+            ("Replays", gui_templates::create_main_page(Replays{
+                on_play: play_replay,
+                on_race: race_ghost,
+            })),
+            ("LAN Race", gui_templates::create_main_page(LanRace{
+                on_start: start_lan_race,
+                on_board: show_race_board,
+                get_pool: race_pool,
+            })),
+            ("Encyclopedia", gui_templates::create_main_page(Encyclopedia)),
+            ("Loadouts", gui_templates::create_main_page(LoadoutBuilder{
+                on_train: start_training.clone(),
+            })),
+            ("Squad Planner", gui_templates::create_main_page(SquadPlanner)),
+            ("Ship Modules", gui_templates::create_main_page(ShipModules)),
+            ("Challenge", gui_templates::create_main_page(Challenge{
+                on_train: start_training,
+            })),
+        ];
+
+        for (title, _) in pages.iter() {
+            overview.append(&gtk::ListBoxRow::builder()
+                            .child(&gtk::Label::new(Some(title)))
+                            .build());
+        }
+
+        let preferences_button = gtk::Button::builder()
+            .icon_name("preferences-system-symbolic")
+            .tooltip_text("Preferences")
+            .action_name("win.preferences")
+            .css_classes(*&["flat"])
+            .build();
         let sidebar = gui_templates::create_sidebar(
-            &gui_templates::create_adw_toolbar(&gtk::Label::new(Some("Helldivers 2 Helper"))),
+            &gui_templates::create_adw_toolbar(&gtk::Label::new(Some("Helldivers 2 Helper")),
+                                               Some(preferences_button.upcast_ref())),
             &overview
             );
 
         let main_content = adw::NavigationSplitView::builder()
-            .content(&right_page)
+            .content(&pages[0].1)
             .sidebar(&sidebar)
             .build();
 
+        overview.select_row(overview.row_at_index(0).as_ref());
+        let split_view = main_content.clone();
+        overview.connect_row_selected(move |_, row| {
+            if let Some(row) = row {
+                split_view.set_content(Some(&pages[row.index() as usize].1));
+                split_view.set_show_content(true);
+            }
+        });
+
         let window = adw::ApplicationWindow::builder()
             .application(app)
             .title("Helldivers 2 Helper")
@@ -91,104 +238,93 @@ fn main() -> glib::ExitCode {
             .default_height(550)
             .build();
         
-        // Shortcuts for training Stratagem codes
-        let input_buffer = Rc::new(RefCell::new(global_stratagem.get().get_keycode()));
+        // The window moves the focus with the arrow keys before the trainer
+        // sees them, so during a versus game or when the trainer takes the
+        // arrow keys, the trainer gets the keys first. Other pages keep their
+        // keys for typing.
+        let versus_controller = gtk::EventControllerKey::builder()
+            .propagation_phase(gtk::PropagationPhase::Capture)
+            .build();
+        let versus_trainer = trainer.clone();
+        versus_controller.connect_key_pressed(move |controller, key, _, _| {
+            let time = controller.current_event_time();
+            if versus_trainer.is_versus() && versus_trainer.arrows.is_mapped() {
+                trainer::keybinds_magic(key, time, &versus_trainer)
+            } else if versus_trainer.takes_arrow_key(key) {
+                trainer::keybinds_magic(key, time, &versus_trainer);
+                glib::Propagation::Stop
+            } else {
+                glib::Propagation::Proceed
+            }
+        });
+        window.add_controller(versus_controller);
+
+        let preferences_action = gtk::gio::SimpleAction::new("preferences", None);
+        let preferences_window = window.clone();
+        preferences_action.connect_activate(move |_, _| {
+            preferences::open_preferences(&settings, &preferences_window);
+        });
+        window.add_action(&preferences_action);
+        app.set_accels_for_action("win.preferences", &["<Control>comma"]);
+
+        // The desktop can turn high contrast on and off at any time
+        let style_trainer = trainer.clone();
+        adw::StyleManager::default().connect_high_contrast_notify(move |_| {
+            accessibility::reapply(&style_trainer.arrows);
+            style_trainer.redraw_keycode();
+        });
+
+        // Shortcuts for training Stratagem codes, only while the trainer is
+        // shown so typing on other pages doesn't count as attempts
         let event_controller = gtk::EventControllerKey::new();
-        event_controller.connect_key_pressed( move |_, key, _, _| {
-           keybinds_magic(key, input_buffer.clone(), &prompt, &stratagem_image, &keycode_visual, global_stratagem.clone())
+        event_controller.connect_key_pressed( move |controller, key, _, _| {
+            if !trainer.arrows.is_mapped() {
+                return glib::Propagation::Proceed;
+            }
+            trainer::keybinds_magic(key, controller.current_event_time(), &trainer)
         });
 
         window.add_controller(event_controller);
         window.present();
     });
 
-    application.run()
+    application.run_with_args(&args)
 }
 
-fn build_ui(application: &Application) {
-
+/// A stylesheet put here is loaded after the bundled one, so it can restyle
+/// the arrows and everything else
+fn get_user_css_path() -> std::path::PathBuf {
+    glib::user_config_dir().join("hd2helper").join("style.css")
 }
 
 // Get CSS for some visual things
 fn load_css() {
+    let display = gtk::gdk::Display::default().expect("Could not connect to a display.");
     let provider = gtk::CssProvider::new();
-    provider.load_from_path("src/style.css");
+    provider.load_from_string(include_str!("style.css"));
 
     // Add the provider to the default screen
     gtk::style_context_add_provider_for_display(
-        &gtk::gdk::Display::default().expect("Could not connect to a display."),
+        &display,
         &provider,
         gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
     );
-}
 
-/// This refreshes the Label, picture, Arrows and Stratagem when the UI needs
-/// to be refreshed
-fn refresh_stratagem(current_stratagem: Rc<Cell<db::Stratagem>>,
-                     current_input: Rc<RefCell<Vec<db::Code>>>,
-                     label: &gtk::Label,
-                     picture: &gtk::Picture,
-                     arrows: &gtk::Label,
-                     ) {
-    current_stratagem.set(rand::random());
-    let stratagem_scaled = gtk::gdk_pixbuf::Pixbuf::from_file_at_scale(
-        current_stratagem.get().get_image_path(), 3840, 2160, true).unwrap();
-
-    current_input.replace(current_stratagem.get().get_keycode());
-
-    picture.set_paintable(Some(&gtk::gdk::Texture::for_pixbuf(&stratagem_scaled)));
-    
-    let printable = current_stratagem.get().to_string();
-    label.set_text(format!("Enter Keycode for {printable}").as_str());
-
-    let keycode = current_stratagem.get().get_keycode();
-    arrows.set_text(get_keycode_str(&keycode).as_str());
-}
-
-/// This function checks the combination and modifies the input buffer as well as
-/// the ui if it needs to be cleared
-fn keybinds_magic(key: gtk::gdk::Key,
-                  input_buffer: Rc<RefCell<Vec<db::Code>>>,
-                  label: &gtk::Label,
-                  picture: &gtk::Picture,
-                  arrows: &gtk::Label,
-                  current_stratagem: Rc<Cell<db::Stratagem>>
-                  ) -> glib::Propagation{
-    use gtk::gdk::Key;
-    use db::Code;
-    let refresh = match key {
-        Key::w => check_refresh(Code::Up, input_buffer.clone()),
-        Key::a => check_refresh(Code::Left, input_buffer.clone()),
-        Key::s => check_refresh(Code::Down, input_buffer.clone()),
-        Key::d => check_refresh(Code::Right, input_buffer.clone()),
-        Key::F5 => true,
-        _ => false,
-    };
-    if refresh == true {
-        refresh_stratagem(current_stratagem, input_buffer.clone(), label, picture, arrows);
+    let user_css = get_user_css_path();
+    if user_css.exists() {
+        let user_provider = gtk::CssProvider::new();
+        user_provider.connect_parsing_error(|_, section, error| {
+            eprintln!("Problem in your stylesheet at {}: {error}", section.to_str());
+        });
+        user_provider.load_from_path(&user_css);
+        gtk::style_context_add_provider_for_display(
+            &display,
+            &user_provider,
+            gtk::STYLE_PROVIDER_PRIORITY_USER,
+        );
     }
-    glib::Propagation::Proceed
 }
 
 fn get_keycode_str(keycode_vec: &Vec<db::Code>) -> String {
-    if keycode_vec.len() == 0 {return "".to_string()}
-    let mut string = keycode_vec[0].get_arrow().to_owned();
-    for i in 1..keycode_vec.len() {
-        string = string + keycode_vec[i].get_arrow();
-    }
-    string
-}
-
-fn check_refresh(current_input: db::Code,
-                 keycode: Rc<RefCell<Vec<db::Code>>>) -> bool {
-    if keycode.borrow().len() == 0 {
-        println!("keycode buffer was empty!");
-        return true;
-    }
-    if keycode.borrow_mut().remove(0) == current_input {
-        if keycode.borrow().len() != 0 {
-            return false;
-        }
-    }
-    true
+    accessibility::get_arrow_theme().get_text(keycode_vec)
 }
@@ -0,0 +1,236 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// Picks the Stratagems the trainer asks for. All of it comes from a seeded
+// generator, so the same seed and the same pool always give the same
+// Stratagems in the same order and a run can be shared by its seed code.
+
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::str::FromStr;
+
+use crate::db;
+
+#[derive(Debug, Clone)]
+pub struct StratagemPicker {
+    seed: u64,
+    rng: rand::rngs::StdRng,
+}
+
+impl StratagemPicker {
+    pub fn new(seed: u64) -> Self {
+        StratagemPicker {
+            seed,
+            rng: rand::rngs::StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// The seed the picker was started with
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Picks the next Stratagem out of the pool
+    pub fn pick(&mut self, pool: &[db::Stratagem]) -> db::Stratagem {
+        *pool.choose(&mut self.rng)
+            .expect("Standard Stratagems are always in the pool")
+    }
+}
+
+/// A new seed for a run nobody has played yet
+pub fn random_seed() -> u64 {
+    rand::random()
+}
+
+/// What players share to get the same Stratagems in the same order: the seed
+/// and the Stratagems it picks from. It looks like `1234` for the default
+/// pool, `1234:mission` or `1234:warbond+removed@ChemicalAgents` for other
+/// kinds of Stratagems and patches, or `1234:EagleAirstrike,OrbitalLaser` for
+/// a loadout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeedCode {
+    pub seed: u64,
+    pub pool: SharedPool,
+}
+
+/// The Stratagems a seed code picks from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SharedPool {
+    /// Everything the filter lets through, with the codes of its patch
+    Filter(db::StratagemFilter),
+    /// Only these, like the ones of a loadout
+    Stratagems(Vec<db::Stratagem>),
+}
+
+impl Default for SharedPool {
+    fn default() -> Self {
+        SharedPool::Filter(db::StratagemFilter::default())
+    }
+}
+
+impl SharedPool {
+    /// Reads the part of a seed code after the seed. Unknown Stratagems are
+    /// an error, leaving them out would give a different order than the one
+    /// that was shared.
+    pub fn parse(pool: &str) -> Result<Self, String> {
+        let pool = pool.trim();
+        Ok(match parse_filter(pool)? {
+            Some(filter) => SharedPool::Filter(filter),
+            None => SharedPool::Stratagems(pool.split(',')
+                .map(|name| db::Stratagem::from_str(name.trim())
+                     .map_err(|_| format!("{} is not a Stratagem", name.trim())))
+                .collect::<Result<_, _>>()?),
+        })
+    }
+
+    /// Returns the part of a seed code after the seed, which `parse` reads
+    /// back. The default pool has none.
+    pub fn to_code(&self) -> Option<String> {
+        match self {
+            SharedPool::Filter(filter) if *filter == db::StratagemFilter::default() => None,
+            SharedPool::Filter(filter) => {
+                let kinds: Vec<&str> = [
+                    (filter.mission_only, "mission"),
+                    (filter.warbond, "warbond"),
+                    (filter.removed, "removed"),
+                ].into_iter().filter(|(on, _)| *on).map(|(_, kind)| kind).collect();
+                // Standard Stratagems are always in
+                let kinds = match kinds.is_empty() {
+                    true => "standard".to_string(),
+                    false => kinds.join("+"),
+                };
+                Some(match filter.patch == db::GamePatch::latest() {
+                    true => kinds,
+                    false => format!("{kinds}@{:?}", filter.patch),
+                })
+            }
+            SharedPool::Stratagems(stratagems) => {
+                let names: Vec<String> = stratagems.iter().map(|s| s.to_string()).collect();
+                Some(names.join(","))
+            }
+        }
+    }
+}
+
+impl SeedCode {
+    /// Reads a seed code
+    pub fn parse(code: &str) -> Result<Self, String> {
+        let (seed, pool) = match code.trim().split_once(':') {
+            Some((seed, pool)) => (seed.trim(), SharedPool::parse(pool)?),
+            None => (code.trim(), SharedPool::default()),
+        };
+        let seed = seed.parse().map_err(|_| format!("{seed} is not a seed"))?;
+        Ok(SeedCode{seed, pool})
+    }
+
+    /// Returns the code to share, which `parse` reads back
+    pub fn to_code(&self) -> String {
+        match self.pool.to_code() {
+            Some(pool) => format!("{}:{pool}", self.seed),
+            None => self.seed.to_string(),
+        }
+    }
+}
+
+/// Reads the kinds of Stratagems and the patch of a seed code. Returns None
+/// if the pool is a list of Stratagems instead.
+fn parse_filter(pool: &str) -> Result<Option<db::StratagemFilter>, String> {
+    let (kinds, patch) = match pool.split_once('@') {
+        Some((kinds, patch)) => {
+            let patch = db::GamePatch::from_str(patch.trim())
+                .map_err(|_| format!("{} is not a patch", patch.trim()))?;
+            (kinds, Some(patch))
+        }
+        None => (pool, None),
+    };
+    let mut filter = db::StratagemFilter {
+        mission_only: false,
+        warbond: false,
+        removed: false,
+        patch: patch.unwrap_or_else(db::GamePatch::latest),
+    };
+    for kind in kinds.split('+').map(str::trim) {
+        match kind {
+            "standard" => {}
+            "mission" => filter.mission_only = true,
+            "warbond" => filter.warbond = true,
+            "removed" => filter.removed = true,
+            _ if patch.is_none() => return Ok(None),
+            _ => return Err(format!("{kind} is not a kind of Stratagems")),
+        }
+    }
+    Ok(Some(filter))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pick(seed: u64, pool: &[db::Stratagem]) -> Vec<db::Stratagem> {
+        let mut picker = StratagemPicker::new(seed);
+        (0..30).map(|_| picker.pick(pool)).collect()
+    }
+
+    #[test]
+    fn same_seed_same_order() {
+        let pool = db::StratagemFilter::default().get_pool();
+        assert_eq!(pick(42, &pool), pick(42, &pool));
+        assert_ne!(pick(42, &pool), pick(43, &pool));
+    }
+
+    #[test]
+    fn seed_codes_round_trip() {
+        let filter = |mission_only, warbond, removed, patch| SharedPool::Filter(db::StratagemFilter {
+            mission_only,
+            warbond,
+            removed,
+            patch,
+        });
+        let latest = db::GamePatch::latest();
+        let codes = [
+            (SeedCode{seed: 7, pool: filter(true, true, false, latest)}, "7"),
+            (SeedCode{seed: 7, pool: filter(true, false, false, latest)}, "7:mission"),
+            (SeedCode{seed: 7, pool: filter(false, true, false, latest)}, "7:warbond"),
+            (SeedCode{seed: 7, pool: filter(false, false, false, latest)}, "7:standard"),
+            (SeedCode{seed: 7, pool: filter(true, true, true, latest)}, "7:mission+warbond+removed"),
+            (SeedCode {
+                seed: 7,
+                pool: filter(false, true, false, db::GamePatch::ChemicalAgents),
+            }, "7:warbond@ChemicalAgents"),
+            (SeedCode {
+                seed: 7,
+                pool: filter(false, false, false, db::GamePatch::Launch),
+            }, "7:standard@Launch"),
+            (SeedCode {
+                seed: 7,
+                pool: SharedPool::Stratagems(vec!(db::Stratagem::EagleAirstrike,
+                                                  db::Stratagem::Resupply)),
+            }, "7:EagleAirstrike,Resupply"),
+        ];
+        for (code, text) in codes {
+            assert_eq!(code.to_code(), text);
+            assert_eq!(SeedCode::parse(text), Ok(code));
+        }
+        // A plain seed is the default pool
+        assert_eq!(SeedCode::parse(" 12 ").map(|code| code.pool), Ok(SharedPool::default()));
+    }
+
+    #[test]
+    fn broken_seed_codes() {
+        assert!(SeedCode::parse("").is_err());
+        assert!(SeedCode::parse("seed").is_err());
+        assert!(SeedCode::parse("7:EagleAirstrike,NotAStratagem").is_err());
+        assert!(SeedCode::parse("7:warbond@NextPatch").is_err());
+        assert!(SeedCode::parse("7:warbond+EagleAirstrike@Launch").is_err());
+    }
+}
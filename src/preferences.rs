@@ -0,0 +1,274 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// The preferences window. Every change is saved right away, the rest of the
+// program follows it through the settings store.
+
+use adw::prelude::*;
+use strum::IntoEnumIterator;
+
+use crate::arrow_theme::ArrowTheme;
+use crate::hotkeys;
+use crate::settings;
+use crate::sound;
+use crate::speech;
+
+type Getter<T> = fn(&settings::Settings) -> T;
+type Setter<T> = fn(&mut settings::Settings, T);
+
+/// Shows the preferences in a window on top of the one the widget is in
+pub fn open_preferences(store: &settings::SettingsStore, widget: &impl IsA<gtk::Widget>) {
+    let window = adw::PreferencesWindow::builder()
+        .title("Preferences")
+        .modal(true)
+        .search_enabled(true)
+        .default_width(550)
+        .default_height(600)
+        .build();
+    window.add(&create_input_page(store, &window));
+    window.add(&create_trainer_page(store));
+    window.add(&create_appearance_page(store));
+    window.add(&create_audio_page(store));
+    window.set_transient_for(widget.root().and_downcast::<gtk::Window>().as_ref());
+    window.present();
+}
+
+fn create_input_page(store: &settings::SettingsStore, window: &adw::PreferencesWindow)
+                     -> adw::PreferencesPage {
+    let trainer_group = adw::PreferencesGroup::builder()
+        .title("Keycode Trainer")
+        .build();
+    let keys_row = create_choice_row(store, "Keys",
+                                     |s| s.input.trainer_keys,
+                                     |s, keys| s.input.trainer_keys = keys);
+    keys_row.set_subtitle("Versus games always use both");
+    trainer_group.add(&keys_row);
+
+    // Hotkeys have their own configuration, the key bindings are only set
+    // on the command line
+    let (config, problems) = hotkeys::load_config();
+    for problem in problems {
+        eprintln!("{problem}");
+    }
+    let hotkeys_group = adw::PreferencesGroup::builder()
+        .title("Global Hotkeys")
+        .description("Call in Stratagems on the cooldown overlay while the game has the \
+                      focus. Keys are bound with \"hd2helper hotkeys bind\". Changes take \
+                      effect after a restart.")
+        .build();
+    let backends: Vec<hotkeys::HotkeyBackend> = hotkeys::HotkeyBackend::iter()
+        .filter(hotkeys::HotkeyBackend::is_supported)
+        .collect();
+    let names: Vec<String> = backends.iter().map(|backend| backend.to_string()).collect();
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    let backend_row = adw::ComboRow::builder()
+        .title("Hotkeys")
+        .model(&gtk::StringList::new(&names))
+        .selected(backends.iter()
+                  .position(|backend| *backend == config.backend)
+                  .unwrap_or(0) as u32)
+        .build();
+    let device_row = adw::EntryRow::builder()
+        .title("Input device, a path below /dev/input or its name")
+        .text(config.device.clone().unwrap_or_default())
+        .show_apply_button(true)
+        .sensitive(config.backend == hotkeys::HotkeyBackend::Evdev)
+        .build();
+    hotkeys_group.add(&backend_row);
+    hotkeys_group.add(&device_row);
+
+    let (device, toasts) = (device_row.clone(), window.clone());
+    backend_row.connect_selected_notify(move |row| {
+        let backend = backends.get(row.selected() as usize).copied().unwrap_or_default();
+        device.set_sensitive(backend == hotkeys::HotkeyBackend::Evdev);
+        update_hotkeys(&toasts, |config| config.backend = backend);
+    });
+    let toasts = window.clone();
+    device_row.connect_apply(move |row| {
+        let device = row.text().trim().to_string();
+        update_hotkeys(&toasts, |config| {
+            config.device = if device.is_empty() { None } else { Some(device) };
+        });
+    });
+
+    let storage_group = adw::PreferencesGroup::new();
+    storage_group.add(&adw::ActionRow::builder()
+        .title("Settings")
+        .subtitle(store.describe_storage())
+        .subtitle_selectable(true)
+        .build());
+
+    let page = adw::PreferencesPage::builder()
+        .title("Input")
+        .icon_name("input-keyboard-symbolic")
+        .build();
+    page.add(&trainer_group);
+    page.add(&hotkeys_group);
+    page.add(&storage_group);
+    page
+}
+
+/// Changes the saved hotkey configuration
+fn update_hotkeys(toasts: &adw::PreferencesWindow, change: impl FnOnce(&mut hotkeys::HotkeyConfig)) {
+    let (mut config, _) = hotkeys::load_config();
+    change(&mut config);
+    if let Err(e) = hotkeys::save_config(&config) {
+        toasts.add_toast(adw::Toast::new(&format!("Could not save the hotkeys: {e}")));
+    }
+}
+
+fn create_trainer_page(store: &settings::SettingsStore) -> adw::PreferencesPage {
+    let stratagems_group = adw::PreferencesGroup::builder()
+        .title("Stratagems")
+        .description("Which Stratagems the keycode trainer asks for")
+        .build();
+    stratagems_group.add(&create_switch_row(store, "Mission Stratagems",
+                                            |s| s.trainer.mission_only,
+                                            |s, on| s.trainer.mission_only = on));
+    stratagems_group.add(&create_switch_row(store, "Warbond Stratagems",
+                                            |s| s.trainer.warbond,
+                                            |s, on| s.trainer.warbond = on));
+    stratagems_group.add(&create_switch_row(store, "Removed Stratagems",
+                                            |s| s.trainer.removed,
+                                            |s, on| s.trainer.removed = on));
+
+    let call_out_group = adw::PreferencesGroup::builder()
+        .title("Call-out Mode")
+        .build();
+    let call_out_row = create_switch_row(store, "Call out Stratagems instead of showing them",
+                                         |s| s.trainer.call_out,
+                                         |s, on| s.trainer.call_out = on);
+    if speech::SpeechEngine::find().is_none() {
+        call_out_row.set_sensitive(false);
+        call_out_row.set_subtitle("Install speech-dispatcher or espeak-ng to hear the Stratagems");
+    }
+    call_out_group.add(&call_out_row);
+
+    let page = adw::PreferencesPage::builder()
+        .title("Trainer")
+        .icon_name("applications-games-symbolic")
+        .build();
+    page.add(&stratagems_group);
+    page.add(&call_out_group);
+    page
+}
+
+fn create_appearance_page(store: &settings::SettingsStore) -> adw::PreferencesPage {
+    let arrows_group = adw::PreferencesGroup::builder()
+        .title("Arrows")
+        .build();
+    arrows_group.add(&create_choice_row::<ArrowTheme>(store, "Arrow style",
+                                                      |s| s.appearance.arrow_theme,
+                                                      |s, theme| s.appearance.arrow_theme = theme));
+    arrows_group.add(&create_switch_row(store, "Large arrows",
+                                        |s| s.appearance.large_arrows,
+                                        |s, on| s.appearance.large_arrows = on));
+    arrows_group.add(&create_switch_row(store, "High-contrast arrows",
+                                        |s| s.appearance.high_contrast,
+                                        |s, on| s.appearance.high_contrast = on));
+
+    let motion_group = adw::PreferencesGroup::builder()
+        .title("Motion")
+        .build();
+    motion_group.add(&create_switch_row(store, "Reduce motion",
+                                        |s| s.appearance.reduce_motion,
+                                        |s, on| s.appearance.reduce_motion = on));
+
+    let stylesheet_group = adw::PreferencesGroup::builder()
+        .title("Stylesheet")
+        .description("A stylesheet here is loaded after the bundled one when the program starts")
+        .build();
+    stylesheet_group.add(&adw::ActionRow::builder()
+        .title("Your own stylesheet")
+        .subtitle(crate::get_user_css_path().display().to_string())
+        .subtitle_selectable(true)
+        .build());
+
+    let page = adw::PreferencesPage::builder()
+        .title("Appearance")
+        .icon_name("preferences-desktop-appearance-symbolic")
+        .build();
+    page.add(&arrows_group);
+    page.add(&motion_group);
+    page.add(&stylesheet_group);
+    page
+}
+
+fn create_audio_page(store: &settings::SettingsStore) -> adw::PreferencesPage {
+    let group = adw::PreferencesGroup::builder()
+        .title("Sounds")
+        .build();
+    let volume = gtk::Scale::builder()
+        .adjustment(&gtk::Adjustment::new(store.get().audio.volume * 100.0, 0.0, 100.0,
+                                          5.0, 10.0, 0.0))
+        .hexpand(true)
+        .valign(gtk::Align::Center)
+        .build();
+    let volume_store = store.clone();
+    volume.connect_value_changed(move |volume| {
+        volume_store.update(|settings| settings.audio.volume = volume.value() / 100.0);
+    });
+    let volume_row = adw::ActionRow::builder()
+        .title("Volume")
+        .build();
+    volume_row.add_suffix(&volume);
+    group.add(&volume_row);
+    group.add(&create_switch_row(store, "Mute",
+                                 |s| s.audio.muted,
+                                 |s, on| s.audio.muted = on));
+    group.add(&adw::ActionRow::builder()
+        .title("Your own sounds")
+        .subtitle(sound::get_sounds_dir().display().to_string())
+        .subtitle_selectable(true)
+        .build());
+
+    let page = adw::PreferencesPage::builder()
+        .title("Audio")
+        .icon_name("audio-speakers-symbolic")
+        .build();
+    page.add(&group);
+    page
+}
+
+/// A switch that saves the setting it is for
+fn create_switch_row(store: &settings::SettingsStore, title: &str,
+                     get: Getter<bool>, set: Setter<bool>) -> adw::SwitchRow {
+    let row = adw::SwitchRow::builder()
+        .title(title)
+        .active(get(&store.get()))
+        .build();
+    let store = store.clone();
+    row.connect_active_notify(move |row| store.update(|settings| set(settings, row.is_active())));
+    row
+}
+
+/// A row to choose one of the variants of an enum, which is saved right away
+fn create_choice_row<T>(store: &settings::SettingsStore, title: &str,
+                        get: Getter<T>, set: Setter<T>) -> adw::ComboRow
+where T: IntoEnumIterator + ToString + PartialEq + Copy + 'static {
+    let names: Vec<String> = T::iter().map(|choice| choice.to_string()).collect();
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    let current = get(&store.get());
+    let row = adw::ComboRow::builder()
+        .title(title)
+        .model(&gtk::StringList::new(&names))
+        .selected(T::iter().position(|choice| choice == current).unwrap_or(0) as u32)
+        .build();
+    let store = store.clone();
+    row.connect_selected_notify(move |row| {
+        if let Some(choice) = T::iter().nth(row.selected() as usize) {
+            store.update(|settings| set(settings, choice));
+        }
+    });
+    row
+}
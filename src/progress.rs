@@ -0,0 +1,244 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// Groups the recorded attempts by day or week, to see how the training goes
+// over time.
+
+use gtk::glib;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use crate::db;
+use crate::stats;
+
+/// How many of the last attempts at a Stratagem have to be correct for it to
+/// count as mastered
+pub const MASTERY_STREAK: usize = 5;
+
+/// At most this many days or weeks are looked at, the most recent ones
+pub const MAX_PERIODS: usize = 30;
+
+#[derive(Debug, PartialEq, Eq, strum_macros::Display, strum_macros::EnumIter, Copy, Clone)]
+pub enum Period {
+    Day,
+    Week,
+}
+
+/// Everything that happened in one day or week
+#[derive(Debug, Clone)]
+pub struct ProgressPoint {
+    /// Local midnight of the first day
+    pub start: glib::DateTime,
+    /// From 0 to 1, None if there were no attempts
+    pub accuracy: Option<f64>,
+    /// Of the successful attempts
+    pub average_total: Option<Duration>,
+    /// How many Stratagems were mastered at the end of the period
+    pub mastered: usize,
+}
+
+/// Which attempts should be looked at
+#[derive(Debug, Copy, Clone, Default)]
+pub struct ProgressFilter {
+    pub category: Option<db::Category>,
+    pub mode: Option<stats::TrainerMode>,
+}
+
+impl ProgressFilter {
+    pub fn allows(&self, attempt: &stats::Attempt) -> bool {
+        self.category.is_none_or(|c| attempt.stratagem.get_category() == c)
+            && self.mode.is_none_or(|m| attempt.mode == m)
+    }
+}
+
+/// Returns the local midnight that starts the period the time is in. Weeks
+/// start on Monday.
+pub fn get_period_start(time: SystemTime, period: Period) -> Option<glib::DateTime> {
+    let secs = time.duration_since(SystemTime::UNIX_EPOCH).ok()?.as_secs() as i64;
+    let local = glib::DateTime::from_unix_local(secs).ok()?;
+    let midnight = glib::DateTime::from_local(
+        local.year(), local.month(), local.day_of_month(), 0, 0, 0.0).ok()?;
+    match period {
+        Period::Day => Some(midnight),
+        Period::Week => midnight.add_days(1 - local.day_of_week()).ok(),
+    }
+}
+
+fn get_next_start(start: &glib::DateTime, period: Period) -> Option<glib::DateTime> {
+    match period {
+        Period::Day => start.add_days(1).ok(),
+        Period::Week => start.add_weeks(1).ok(),
+    }
+}
+
+/// Whether the Stratagem's last attempts up to the given time were all
+/// correct
+fn is_mastered(attempts: &[&stats::Attempt], stratagem: db::Stratagem, until: SystemTime) -> bool {
+    let recent: Vec<&&stats::Attempt> = attempts.iter()
+        .filter(|a| a.stratagem == stratagem && a.started < until)
+        .rev()
+        .take(MASTERY_STREAK)
+        .collect();
+    recent.len() == MASTERY_STREAK && recent.iter().all(|a| a.is_success())
+}
+
+/// Sums up the attempts the filter allows per day or week, from the period of
+/// the first attempt to the period of the last one, but at most `MAX_PERIODS`.
+/// Periods without attempts are included, so the result can be drawn as it
+/// is. Attempts have to be oldest first.
+pub fn get_progress(attempts: &[stats::Attempt], filter: ProgressFilter, period: Period)
+                    -> Vec<ProgressPoint> {
+    let attempts: Vec<&stats::Attempt> = attempts.iter().filter(|a| filter.allows(a)).collect();
+    let (Some(first), Some(last)) = (attempts.first(), attempts.last()) else {
+        return Vec::new();
+    };
+    let (Some(mut start), Some(last_start)) = (get_period_start(first.started, period),
+                                               get_period_start(last.started, period)) else {
+        return Vec::new();
+    };
+    // Only the most recent periods are of interest
+    let back = 1 - MAX_PERIODS as i32;
+    let earliest = match period {
+        Period::Day => last_start.add_days(back),
+        Period::Week => last_start.add_weeks(back),
+    };
+    if let Ok(earliest) = earliest {
+        if earliest.to_unix() > start.to_unix() {
+            start = earliest;
+        }
+    }
+
+    let mut stratagems: Vec<db::Stratagem> = Vec::new();
+    for attempt in attempts.iter() {
+        if !stratagems.contains(&attempt.stratagem) {
+            stratagems.push(attempt.stratagem);
+        }
+    }
+
+    let mut points = Vec::new();
+    while start.to_unix() <= last_start.to_unix() {
+        let Some(end) = get_next_start(&start, period) else {
+            break;
+        };
+        let begins = SystemTime::UNIX_EPOCH + Duration::from_secs(start.to_unix() as u64);
+        let ends = SystemTime::UNIX_EPOCH + Duration::from_secs(end.to_unix() as u64);
+        let inside: Vec<&&stats::Attempt> = attempts.iter()
+            .filter(|a| a.started >= begins && a.started < ends)
+            .collect();
+        let successes = inside.iter().filter(|a| a.is_success()).count();
+        points.push(ProgressPoint {
+            start,
+            accuracy: (!inside.is_empty()).then(|| successes as f64 / inside.len() as f64),
+            average_total: stats::average(inside.iter()
+                                          .filter(|a| a.is_success())
+                                          .map(|a| a.total)),
+            mastered: stratagems.iter()
+                .filter(|s| is_mastered(&attempts, **s, ends))
+                .count(),
+        });
+        start = end;
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use db::Stratagem::*;
+
+    /// A time in May 2024 in the local time zone, the 6th is a Monday
+    fn may(day: i32, hour: i32) -> SystemTime {
+        let time = glib::DateTime::from_local(2024, 5, day, hour, 0, 0.0).unwrap();
+        SystemTime::UNIX_EPOCH + Duration::from_secs(time.to_unix() as u64)
+    }
+
+    fn attempt(stratagem: db::Stratagem, started: SystemTime, total_ms: u64, success: bool)
+               -> stats::Attempt {
+        stats::Attempt {
+            stratagem,
+            mode: stats::TrainerMode::Practice,
+            started,
+            reaction: Duration::from_millis(200),
+            intervals: Vec::new(),
+            total: Duration::from_millis(total_ms),
+            mistake_at: (!success).then_some(0),
+            wrong_key: None,
+        }
+    }
+
+    fn get_days(points: &[ProgressPoint]) -> Vec<i32> {
+        points.iter().map(|p| p.start.day_of_month()).collect()
+    }
+
+    #[test]
+    fn period_starts() {
+        let day = get_period_start(may(8, 15), Period::Day).unwrap();
+        assert_eq!((day.month(), day.day_of_month(), day.hour()), (5, 8, 0));
+        let week = get_period_start(may(8, 15), Period::Week).unwrap();
+        assert_eq!((week.month(), week.day_of_month(), week.hour()), (5, 6, 0));
+        let sunday = get_period_start(may(12, 23), Period::Week).unwrap();
+        assert_eq!(sunday.day_of_month(), 6);
+    }
+
+    #[test]
+    fn days_without_attempts_are_kept() {
+        let attempts = [
+            attempt(Resupply, may(6, 9), 1000, true),
+            attempt(Reinforce, may(6, 20), 3000, false),
+            attempt(Resupply, may(8, 12), 500, true),
+        ];
+        let points = get_progress(&attempts, ProgressFilter::default(), Period::Day);
+        assert_eq!(get_days(&points), [6, 7, 8]);
+        let accuracy: Vec<Option<f64>> = points.iter().map(|p| p.accuracy).collect();
+        assert_eq!(accuracy, [Some(0.5), None, Some(1.0)]);
+        let totals: Vec<Option<Duration>> = points.iter().map(|p| p.average_total).collect();
+        assert_eq!(totals, [Some(Duration::from_millis(1000)), None,
+                            Some(Duration::from_millis(500))]);
+
+        let weeks = get_progress(&attempts, ProgressFilter::default(), Period::Week);
+        assert_eq!(get_days(&weeks), [6]);
+        assert_eq!(weeks[0].accuracy, Some(2.0 / 3.0));
+    }
+
+    #[test]
+    fn mastery_streak() {
+        let mut attempts: Vec<stats::Attempt> = (0..MASTERY_STREAK - 1)
+            .map(|hour| attempt(Resupply, may(6, hour as i32), 1000, true))
+            .collect();
+        attempts.push(attempt(Resupply, may(7, 10), 1000, true));
+        attempts.push(attempt(Resupply, may(8, 10), 1000, false));
+        attempts.push(attempt(Reinforce, may(8, 11), 1000, true));
+        let points = get_progress(&attempts, ProgressFilter::default(), Period::Day);
+        let mastered: Vec<usize> = points.iter().map(|p| p.mastered).collect();
+        assert_eq!(mastered, [0, 1, 0]);
+    }
+
+    #[test]
+    fn filtered_and_capped() {
+        let attempts = [
+            attempt(Resupply, may(1, 12), 1000, true),
+            attempt(EagleAirstrike, may(31, 12), 1000, true),
+        ];
+        let filter = ProgressFilter {
+            mode: Some(stats::TrainerMode::StratagemHero),
+            ..Default::default()
+        };
+        assert!(get_progress(&attempts, filter, Period::Day).is_empty());
+
+        // Only the last days count, not the whole month
+        let points = get_progress(&attempts, ProgressFilter::default(), Period::Day);
+        assert_eq!(points.len(), MAX_PERIODS);
+        assert_eq!(points[0].start.day_of_month(), 2);
+        assert_eq!(points.last().map(|p| p.accuracy), Some(Some(1.0)));
+    }
+}
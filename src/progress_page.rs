@@ -0,0 +1,158 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// The progress page charts how accuracy, speed and the number of mastered
+// Stratagems develop from day to day or week to week.
+
+use adw::prelude::*;
+use std::time::Duration;
+use strum::IntoEnumIterator;
+
+use crate::chart;
+use crate::db;
+use crate::gui_templates;
+use crate::progress;
+use crate::stats;
+
+/// The filters and charts of the page. A clone is just another handle to the
+/// same page.
+#[derive(Clone)]
+struct ProgressPage {
+    category: adw::ComboRow,
+    mode: adw::ComboRow,
+    period: adw::ComboRow,
+    accuracy: chart::BarChart,
+    average_total: chart::BarChart,
+    mastered: chart::BarChart,
+    toasts: adw::ToastOverlay,
+}
+
+pub fn create_progress_page() -> adw::NavigationPage {
+    // The first entry of the category and mode filters allows everything
+    let mut categories = vec!("All categories".to_string());
+    categories.extend(db::Category::iter().map(|c| c.to_string()));
+    let categories: Vec<&str> = categories.iter().map(String::as_str).collect();
+    let mut modes = vec!("All modes".to_string());
+    modes.extend(stats::TrainerMode::iter().map(|m| m.to_string()));
+    let modes: Vec<&str> = modes.iter().map(String::as_str).collect();
+    let periods: Vec<String> = progress::Period::iter().map(|p| p.to_string()).collect();
+    let periods: Vec<&str> = periods.iter().map(String::as_str).collect();
+
+    let page = ProgressPage {
+        category: adw::ComboRow::builder()
+            .title("Category")
+            .model(&gtk::StringList::new(&categories))
+            .build(),
+        mode: adw::ComboRow::builder()
+            .title("Trainer mode")
+            .model(&gtk::StringList::new(&modes))
+            .build(),
+        period: adw::ComboRow::builder()
+            .title("One bar per")
+            .model(&gtk::StringList::new(&periods))
+            .build(),
+        accuracy: chart::BarChart::new(Some(1.0), |v| format!("{:.0}%", v * 100.0)),
+        average_total: chart::BarChart::new(
+            None, |v| gui_templates::format_short_duration(Duration::from_secs_f64(v))),
+        mastered: chart::BarChart::new(None, |v| format!("{v:.0}")),
+        toasts: adw::ToastOverlay::new(),
+    };
+
+    let filter_list = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .css_classes(*&["boxed-list"])
+        .build();
+    filter_list.append(&page.category);
+    filter_list.append(&page.mode);
+    filter_list.append(&page.period);
+    for row in [&page.category, &page.mode, &page.period] {
+        let page = page.clone();
+        row.connect_selected_notify(move |_| page.refresh());
+    }
+    let filter_group = adw::PreferencesGroup::builder()
+        .title("Filter")
+        .build();
+    filter_group.add(&filter_list);
+
+    let content = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(18)
+        .margin_top(12)
+        .margin_bottom(12)
+        .build();
+    content.append(&filter_group);
+    let charts = [
+        ("Accuracy", "Share of codes entered correctly", &page.accuracy),
+        ("Average completion time", "Of the codes entered correctly", &page.average_total),
+        ("Stratagems mastered",
+         &*format!("The last {} attempts were correct", progress::MASTERY_STREAK),
+         &page.mastered),
+    ];
+    for (title, description, chart) in charts {
+        let group = adw::PreferencesGroup::builder()
+            .title(title)
+            .description(description)
+            .build();
+        group.add(&gtk::Frame::builder()
+                  .child(&chart.area)
+                  .css_classes(*&["view"])
+                  .build());
+        chart.area.set_margin_top(6);
+        content.append(&group);
+    }
+    page.toasts.set_child(Some(&gui_templates::create_scrolled_content(&content)));
+
+    // The trainer adds attempts all the time, so read them every time the
+    // page is shown
+    let map_page = page.clone();
+    content.connect_map(move |_| map_page.refresh());
+
+    gui_templates::create_titled_page("Progress", None, &page.toasts)
+}
+
+impl ProgressPage {
+    fn get_filter(&self) -> progress::ProgressFilter {
+        // Index 0 is the entry that allows everything
+        let pick = |row: &adw::ComboRow| (row.selected() as usize).checked_sub(1);
+        progress::ProgressFilter {
+            category: pick(&self.category).and_then(|i| db::Category::iter().nth(i)),
+            mode: pick(&self.mode).and_then(|i| stats::TrainerMode::iter().nth(i)),
+        }
+    }
+
+    fn get_period(&self) -> progress::Period {
+        progress::Period::iter()
+            .nth(self.period.selected() as usize)
+            .unwrap_or(progress::Period::Day)
+    }
+
+    /// Reads the attempts again and redraws the charts
+    fn refresh(&self) {
+        let (attempts, problems) = stats::load_attempts();
+        for problem in problems {
+            self.toasts.add_toast(adw::Toast::new(&problem));
+        }
+        let points = progress::get_progress(&attempts, self.get_filter(), self.get_period());
+        let bars = |value: &dyn Fn(&progress::ProgressPoint) -> Option<f64>| points.iter()
+            .map(|point| chart::Bar {
+                label: point.start.format("%b %e")
+                    .map(|label| label.to_string())
+                    .unwrap_or_default(),
+                value: value(point),
+            })
+            .collect();
+        self.accuracy.set_bars(bars(&|p| p.accuracy));
+        self.average_total.set_bars(bars(&|p| p.average_total.map(|t| t.as_secs_f64())));
+        self.mastered.set_bars(bars(&|p| Some(p.mastered as f64)));
+    }
+}
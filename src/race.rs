@@ -0,0 +1,730 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// Races between players on the same network. One player hosts, the others
+// join with the host's address. The host picks a seed and sends the
+// Stratagems to pick from, so everyone gets the same Stratagems, and tells
+// when the race starts. Every player reports how
+// many codes they entered and the host sends everyone the scoreboard.
+//
+// Players talk over TCP, one JSON message per line. Reading blocks, so every
+// connection gets its own thread, and what happens is sent to the GTK main
+// loop over a channel like the evdev hotkeys do. The host writes to every
+// player from a thread of its own too, a player who stops reading must not
+// hold up the host's window.
+
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::net::ToSocketAddrs;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::mpsc;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::db;
+
+/// The port a race is hosted on unless the host chooses another one
+pub const DEFAULT_PORT: u16 = 47623;
+/// How many codes a race can be long
+pub const RACE_LENGTHS: [usize; 3] = [10, 20, 30];
+/// How long everyone gets to get ready after the host started the race
+const COUNTDOWN: Duration = Duration::from_secs(3);
+/// How long joining waits for the host to answer
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// How long the host waits for a player to take a message before they are
+/// dropped
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long the host waits for someone who connected to say who they are
+const HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What players send each other
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message {
+    /// The first thing a player who joins sends
+    Hello{name: String},
+    /// The name the host gave the player, it differs if the name was taken
+    Welcome{name: String},
+    /// The start is relative, the clocks of different PCs don't agree. The
+    /// pool holds the names of the Stratagems, like the saved files do.
+    Start{seed: u64, length: usize, countdown_ms: u64, pool: Vec<String>},
+    Progress{completed: usize, mistakes: usize},
+    Scoreboard{players: Vec<Standing>},
+}
+
+/// How far one player is in the race
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Standing {
+    pub name: String,
+    /// Codes entered correctly
+    pub completed: usize,
+    pub mistakes: usize,
+    /// How long the player took for the whole race, if they are through
+    pub finished_ms: Option<u64>,
+    /// Players who left during a race stay on the scoreboard
+    pub connected: bool,
+}
+
+impl Standing {
+    fn new(name: String) -> Self {
+        Standing {
+            name,
+            completed: 0,
+            mistakes: 0,
+            finished_ms: None,
+            connected: true,
+        }
+    }
+}
+
+/// What the GUI hears about the race
+#[derive(Debug, Clone)]
+pub enum RaceEvent {
+    /// The name the player has on the scoreboard
+    Joined(String),
+    Started(RaceStart),
+    Scoreboard(Vec<Standing>),
+    /// The connection to the host is gone, with the reason
+    Disconnected(String),
+    /// A race the player can't take part in, with the reason. They stay
+    /// connected for the next one.
+    Rejected(String),
+}
+
+/// A race that was started by the host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RaceStart {
+    pub seed: u64,
+    /// How many codes have to be entered
+    pub length: usize,
+    /// When the first Stratagem is shown
+    pub starts_at: Instant,
+    /// The host's Stratagems, everyone picks from them instead of their own
+    pub pool: Vec<db::Stratagem>,
+}
+
+/// A player's race as the trainer runs it
+pub struct RaceProgress {
+    pub start: RaceStart,
+    pub completed: usize,
+    pub mistakes: usize,
+    pub connection: Rc<Connection>,
+}
+
+impl RaceProgress {
+    pub fn new(start: RaceStart, connection: Rc<Connection>) -> Self {
+        RaceProgress {
+            start,
+            completed: 0,
+            mistakes: 0,
+            connection,
+        }
+    }
+
+    /// Whether the countdown is over
+    pub fn has_started(&self, now: Instant) -> bool {
+        now >= self.start.starts_at
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.completed >= self.start.length
+    }
+
+    /// Tells the others how far the player is
+    pub fn report(&self) {
+        self.connection.report(self.completed, self.mistakes);
+    }
+}
+
+/// Best first: who finished first, then who entered the most codes with the
+/// fewest mistakes
+fn sort_scoreboard(board: &mut [Standing]) {
+    board.sort_by(|a, b| match (a.finished_ms, b.finished_ms) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => b.completed.cmp(&a.completed).then(a.mistakes.cmp(&b.mistakes)),
+    });
+}
+
+/// Writes one message as a line
+fn send(mut stream: &TcpStream, message: &Message) -> std::io::Result<()> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())
+}
+
+/// Tells a player about a race, the countdown is what is left of it
+fn get_start_message(start: &RaceStart) -> Message {
+    Message::Start {
+        seed: start.seed,
+        length: start.length,
+        countdown_ms: start.starts_at.saturating_duration_since(Instant::now()).as_millis() as u64,
+        pool: start.pool.iter().map(db::Stratagem::to_string).collect(),
+    }
+}
+
+/// Sends what is put into the returned channel on a thread of its own. A
+/// connection that can't take a message in time is hung up, the thread
+/// reading from it notices that.
+fn spawn_writer(stream: &TcpStream) -> std::io::Result<mpsc::Sender<Message>> {
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    let writer = stream.try_clone()?;
+    let (outbox, messages) = mpsc::channel::<Message>();
+    std::thread::spawn(move || {
+        for message in messages {
+            if send(&writer, &message).is_err() {
+                let _ = writer.shutdown(std::net::Shutdown::Both);
+                return;
+            }
+        }
+    });
+    Ok(outbox)
+}
+
+/// Finds the host, with the default port if the address has none
+fn get_address(address: &str) -> std::io::Result<SocketAddr> {
+    let address = address.trim();
+    address.to_socket_addrs()
+        .or_else(|_| (address, DEFAULT_PORT).to_socket_addrs())?
+        .next()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound,
+                                           format!("{address} could not be found")))
+}
+
+/// Either end of a race
+pub enum Connection {
+    Host(RaceHost),
+    Client(RaceClient),
+}
+
+impl Connection {
+    pub fn report(&self, completed: usize, mistakes: usize) {
+        match self {
+            Connection::Host(host) => host.report(completed, mistakes),
+            Connection::Client(client) => client.report(completed, mistakes),
+        }
+    }
+
+    /// Stops hosting or leaves the host
+    pub fn leave(&self) {
+        match self {
+            Connection::Host(host) => host.stop(),
+            Connection::Client(client) => client.leave(),
+        }
+    }
+}
+
+struct Peer {
+    name: String,
+    /// Only used to hang up, the writer thread does the sending
+    stream: TcpStream,
+    outbox: mpsc::Sender<Message>,
+}
+
+impl Peer {
+    fn new(name: String, stream: TcpStream) -> std::io::Result<Self> {
+        let outbox = spawn_writer(&stream)?;
+        Ok(Peer{name, stream, outbox})
+    }
+
+    /// Queues a message for the player, false if their connection broke
+    fn send(&self, message: &Message) -> bool {
+        self.outbox.send(message.clone()).is_ok()
+    }
+}
+
+struct HostState {
+    /// The host's own name on the scoreboard
+    name: String,
+    peers: Vec<Peer>,
+    board: Vec<Standing>,
+    /// The race that was started last
+    race: Option<RaceStart>,
+    closed: bool,
+}
+
+impl HostState {
+    /// Sends the scoreboard to everyone, including the host's GUI. Players
+    /// whose connection broke are dropped, their own thread notices it too.
+    fn broadcast(&mut self, events: &async_channel::Sender<RaceEvent>) {
+        sort_scoreboard(&mut self.board);
+        let message = Message::Scoreboard{players: self.board.clone()};
+        self.peers.retain(|peer| peer.send(&message));
+        let _ = events.send_blocking(RaceEvent::Scoreboard(self.board.clone()));
+    }
+
+    /// A name nobody on the scoreboard has yet
+    fn get_free_name(&self, wanted: &str) -> String {
+        let wanted = match wanted.trim() {
+            "" => "Helldiver",
+            wanted => wanted,
+        };
+        let taken = |name: &str| self.board.iter().any(|s| s.name == name);
+        let mut name = wanted.to_string();
+        let mut number = 2;
+        while taken(&name) {
+            name = format!("{wanted} {number}");
+            number += 1;
+        }
+        name
+    }
+
+    /// Whether someone who is still there has not finished the race yet
+    fn is_running(&self) -> bool {
+        self.race.is_some()
+            && self.board.iter().any(|s| s.connected && s.finished_ms.is_none())
+    }
+
+    fn update(&mut self, name: &str, completed: usize, mistakes: usize) {
+        let finished_ms = match &self.race {
+            Some(race) if completed >= race.length =>
+                Some(Instant::now().saturating_duration_since(race.starts_at).as_millis() as u64),
+            _ => None,
+        };
+        if let Some(standing) = self.board.iter_mut().find(|s| s.name == name) {
+            standing.completed = completed;
+            standing.mistakes = mistakes;
+            if standing.finished_ms.is_none() {
+                standing.finished_ms = finished_ms;
+            }
+        }
+    }
+}
+
+/// Hosts a race. The host plays too.
+#[derive(Clone)]
+pub struct RaceHost {
+    state: Arc<Mutex<HostState>>,
+    events: async_channel::Sender<RaceEvent>,
+    address: SocketAddr,
+}
+
+impl RaceHost {
+    /// Waits for players on the given port of every network interface
+    pub fn start(name: &str, port: u16, events: async_channel::Sender<RaceEvent>)
+                 -> std::io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        let mut state = HostState {
+            name: String::new(),
+            peers: Vec::new(),
+            board: Vec::new(),
+            race: None,
+            closed: false,
+        };
+        state.name = state.get_free_name(name);
+        state.board.push(Standing::new(state.name.clone()));
+        let _ = events.send_blocking(RaceEvent::Joined(state.name.clone()));
+        state.broadcast(&events);
+        let host = RaceHost {
+            state: Arc::new(Mutex::new(state)),
+            events,
+            address: listener.local_addr()?,
+        };
+
+        let accepting = host.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if accepting.state.lock().unwrap().closed {
+                    return;
+                }
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                let peer = accepting.clone();
+                std::thread::spawn(move || peer.serve(stream));
+            }
+        });
+        Ok(host)
+    }
+
+    /// The port players join on
+    pub fn get_port(&self) -> u16 {
+        self.address.port()
+    }
+
+    /// Talks to one player until they leave
+    fn serve(&self, stream: TcpStream) {
+        let Ok(reader) = stream.try_clone() else {
+            return;
+        };
+        // Something that connects and never says hello must not keep a
+        // thread busy forever
+        if stream.set_read_timeout(Some(HELLO_TIMEOUT)).is_err() {
+            return;
+        }
+        let mut lines = BufReader::new(reader).lines();
+        let name = match lines.next().and_then(Result::ok)
+            .and_then(|line| serde_json::from_str(&line).ok()) {
+            Some(Message::Hello{name}) => name,
+            _ => return,
+        };
+        // Players may wait a long time for the next race
+        if stream.set_read_timeout(None).is_err() {
+            return;
+        }
+        let name = {
+            let mut state = self.state.lock().unwrap();
+            if state.closed {
+                return;
+            }
+            let name = state.get_free_name(&name);
+            let Ok(peer) = Peer::new(name.clone(), stream) else {
+                return;
+            };
+            peer.send(&Message::Welcome{name: name.clone()});
+            // Someone who joins late still races, with the time that is left
+            if state.is_running() {
+                if let Some(race) = &state.race {
+                    peer.send(&get_start_message(race));
+                }
+            }
+            state.board.push(Standing::new(name.clone()));
+            state.peers.push(peer);
+            state.broadcast(&self.events);
+            name
+        };
+
+        for line in lines {
+            let Ok(line) = line else {
+                break;
+            };
+            let Ok(Message::Progress{completed, mistakes}) = serde_json::from_str(&line) else {
+                continue;
+            };
+            let mut state = self.state.lock().unwrap();
+            if state.closed {
+                return;
+            }
+            state.update(&name, completed, mistakes);
+            state.broadcast(&self.events);
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return;
+        }
+        state.peers.retain(|peer| peer.name != name);
+        // Without a race, nobody needs to know they were there
+        if state.race.is_some() {
+            if let Some(standing) = state.board.iter_mut().find(|s| s.name == name) {
+                standing.connected = false;
+            }
+        } else {
+            state.board.retain(|s| s.name != name);
+        }
+        state.broadcast(&self.events);
+    }
+
+    /// Starts a race for everyone who joined. The Stratagems are picked from
+    /// the pool with the seed, the first one is shown after a short
+    /// countdown.
+    pub fn start_race(&self, seed: u64, length: usize, pool: Vec<db::Stratagem>) {
+        let mut state = self.state.lock().unwrap();
+        let start = RaceStart {
+            seed,
+            length,
+            starts_at: Instant::now() + COUNTDOWN,
+            pool,
+        };
+        state.race = Some(start.clone());
+        state.board.retain(|s| s.connected);
+        for standing in state.board.iter_mut() {
+            *standing = Standing::new(standing.name.clone());
+        }
+        let message = get_start_message(&start);
+        state.peers.retain(|peer| peer.send(&message));
+        let _ = self.events.send_blocking(RaceEvent::Started(start));
+        state.broadcast(&self.events);
+    }
+
+    /// The host's own progress
+    pub fn report(&self, completed: usize, mistakes: usize) {
+        let mut state = self.state.lock().unwrap();
+        let name = state.name.clone();
+        state.update(&name, completed, mistakes);
+        state.broadcast(&self.events);
+    }
+
+    /// Sends everyone home and frees the port
+    pub fn stop(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        for peer in state.peers.drain(..) {
+            let _ = peer.stream.shutdown(std::net::Shutdown::Both);
+        }
+        drop(state);
+        // Wakes the thread waiting for players, so it sees the host is closed
+        let _ = TcpStream::connect_timeout(&([127, 0, 0, 1], self.address.port()).into(),
+                                           CONNECT_TIMEOUT);
+    }
+}
+
+/// A host with a newer version may know Stratagems this one doesn't. Racing
+/// without them would pick other Stratagems than everyone else gets.
+fn get_start_event(seed: u64, length: usize, countdown_ms: u64, pool: &[String]) -> RaceEvent {
+    let unknown: Vec<&str> = pool.iter()
+        .filter(|name| db::Stratagem::from_str(name).is_err())
+        .map(String::as_str)
+        .collect();
+    if !unknown.is_empty() {
+        return RaceEvent::Rejected(format!(
+            "Version mismatch: the host races with Stratagems this version doesn't know ({}), \
+             update to race along", unknown.join(", ")));
+    }
+    RaceEvent::Started(RaceStart {
+        seed,
+        length,
+        starts_at: Instant::now() + Duration::from_millis(countdown_ms),
+        pool: pool.iter().filter_map(|name| db::Stratagem::from_str(name).ok()).collect(),
+    })
+}
+
+/// A player who joined a host
+pub struct RaceClient {
+    stream: TcpStream,
+    outbox: mpsc::Sender<Message>,
+}
+
+impl RaceClient {
+    /// Joins the host at the address, like "192.168.1.20" or
+    /// "192.168.1.20:47623"
+    pub fn join(address: &str, name: &str, events: async_channel::Sender<RaceEvent>)
+                -> std::io::Result<Self> {
+        let stream = TcpStream::connect_timeout(&get_address(address)?, CONNECT_TIMEOUT)?;
+        let outbox = spawn_writer(&stream)?;
+        let _ = outbox.send(Message::Hello{name: name.to_string()});
+        let reader = stream.try_clone()?;
+        std::thread::spawn(move || {
+            for line in BufReader::new(reader).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                let event = match serde_json::from_str(&line) {
+                    Ok(Message::Welcome{name}) => RaceEvent::Joined(name),
+                    Ok(Message::Start{seed, length, countdown_ms, pool}) =>
+                        get_start_event(seed, length, countdown_ms, &pool),
+                    Ok(Message::Scoreboard{players}) => RaceEvent::Scoreboard(players),
+                    _ => continue,
+                };
+                if events.send_blocking(event).is_err() {
+                    return;
+                }
+            }
+            let _ = events.send_blocking(RaceEvent::Disconnected(
+                "The connection to the host was lost".to_string()));
+        });
+        Ok(RaceClient{stream, outbox})
+    }
+
+    /// A report that can't be sent shows up as a lost connection
+    pub fn report(&self, completed: usize, mistakes: usize) {
+        let _ = self.outbox.send(Message::Progress{completed, mistakes});
+    }
+
+    pub fn leave(&self) {
+        let _ = self.stream.shutdown(std::net::Shutdown::Both);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// How long a test waits for an event before it fails
+    const WAIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Waits for the first event `find` picks something from
+    fn wait_for<T>(events: &async_channel::Receiver<RaceEvent>,
+                   find: impl Fn(RaceEvent) -> Option<T>) -> T {
+        let deadline = Instant::now() + WAIT_TIMEOUT;
+        loop {
+            match events.try_recv() {
+                Ok(event) => {
+                    if let Some(found) = find(event) {
+                        return found;
+                    }
+                }
+                Err(async_channel::TryRecvError::Empty) => {
+                    assert!(Instant::now() < deadline, "The event didn't come in time");
+                    std::thread::sleep(Duration::from_millis(5));
+                }
+                Err(async_channel::TryRecvError::Closed) => panic!("The race ended too early"),
+            }
+        }
+    }
+
+    fn joined(event: RaceEvent) -> Option<String> {
+        match event {
+            RaceEvent::Joined(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    fn started(event: RaceEvent) -> Option<RaceStart> {
+        match event {
+            RaceEvent::Started(start) => Some(start),
+            _ => None,
+        }
+    }
+
+    fn join(address: &str, name: &str) -> (RaceClient, async_channel::Receiver<RaceEvent>) {
+        let (sender, receiver) = async_channel::unbounded();
+        (RaceClient::join(address, name, sender).unwrap(), receiver)
+    }
+
+    fn get_state(board: &[&str]) -> HostState {
+        HostState {
+            name: String::new(),
+            peers: Vec::new(),
+            board: board.iter().map(|name| Standing::new(name.to_string())).collect(),
+            race: None,
+            closed: false,
+        }
+    }
+
+    #[test]
+    fn race_on_localhost() {
+        let (sender, host_events) = async_channel::unbounded();
+        let host = RaceHost::start("Host", 0, sender).unwrap();
+        let address = format!("127.0.0.1:{}", host.get_port());
+        assert_eq!(wait_for(&host_events, joined), "Host");
+
+        let (first, first_events) = join(&address, "Diver");
+        assert_eq!(wait_for(&first_events, joined), "Diver");
+        let (second, second_events) = join(&address, "Diver");
+        assert_eq!(wait_for(&second_events, joined), "Diver 2");
+        wait_for(&host_events, |event| match event {
+            RaceEvent::Scoreboard(board) if board.len() == 3 => Some(()),
+            _ => None,
+        });
+
+        let pool = vec!(db::Stratagem::Resupply, db::Stratagem::Reinforce);
+        host.start_race(42, 2, pool.clone());
+        for events in [&host_events, &first_events, &second_events] {
+            let start = wait_for(events, started);
+            assert_eq!((start.seed, start.length, &start.pool), (42, 2, &pool));
+        }
+
+        // Someone who joins late gets the race that is running
+        let (late, late_events) = join(&address, "Late");
+        assert_eq!(wait_for(&late_events, started).seed, 42);
+
+        second.report(1, 3);
+        first.report(2, 1);
+        // Each player's thread reports on its own, wait until both are in
+        let board = wait_for(&host_events, |event| match event {
+            RaceEvent::Scoreboard(board)
+                if board.iter().map(|s| s.completed).sum::<usize>() == 3 => Some(board),
+            _ => None,
+        });
+        let names: Vec<&str> = board.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["Diver", "Diver 2", "Host", "Late"]);
+        assert!(board[0].finished_ms.is_some());
+        assert_eq!((board[1].completed, board[1].mistakes), (1, 3));
+
+        // Players who leave during a race stay on the board
+        late.leave();
+        wait_for(&first_events, |event| match event {
+            RaceEvent::Scoreboard(board) => board.iter()
+                .find(|s| s.name == "Late" && !s.connected)
+                .map(|_| ()),
+            _ => None,
+        });
+
+        host.stop();
+        wait_for(&first_events, |event| match event {
+            RaceEvent::Disconnected(_) => Some(()),
+            _ => None,
+        });
+        first.leave();
+        second.leave();
+    }
+
+    #[test]
+    fn unknown_stratagems_are_rejected() {
+        let pool = ["Resupply".to_string(), "Reinforce".to_string()];
+        match get_start_event(7, 10, 3000, &pool) {
+            RaceEvent::Started(start) =>
+                assert_eq!(start.pool, [db::Stratagem::Resupply, db::Stratagem::Reinforce]),
+            event => panic!("The race was not started: {event:?}"),
+        }
+        let pool = ["Resupply".to_string(), "OrbitalToaster".to_string()];
+        match get_start_event(7, 10, 3000, &pool) {
+            RaceEvent::Rejected(reason) => {
+                assert!(reason.starts_with("Version mismatch"));
+                assert!(reason.contains("OrbitalToaster"));
+            }
+            event => panic!("The race was not rejected: {event:?}"),
+        }
+    }
+
+    #[test]
+    fn free_names() {
+        let state = get_state(&["Helldiver", "Diver", "Diver 2"]);
+        assert_eq!(state.get_free_name("Diver"), "Diver 3");
+        assert_eq!(state.get_free_name(" Eagle "), "Eagle");
+        assert_eq!(state.get_free_name(""), "Helldiver 2");
+    }
+
+    #[test]
+    fn scoreboard_order() {
+        let standing = |name: &str, completed, mistakes, finished_ms| Standing {
+            completed,
+            mistakes,
+            finished_ms,
+            ..Standing::new(name.to_string())
+        };
+        let mut board = vec!(
+            standing("Few", 3, 0, None),
+            standing("Slow", 10, 4, Some(9000)),
+            standing("Sloppy", 5, 2, None),
+            standing("Fast", 10, 0, Some(7000)),
+            standing("Clean", 5, 0, None),
+        );
+        sort_scoreboard(&mut board);
+        let names: Vec<&str> = board.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["Fast", "Slow", "Clean", "Sloppy", "Few"]);
+    }
+
+    #[test]
+    fn finishing_is_counted_once() {
+        let mut state = get_state(&["Diver"]);
+        state.race = Some(RaceStart {
+            seed: 1,
+            length: 3,
+            starts_at: Instant::now(),
+            pool: Vec::new(),
+        });
+        state.update("Diver", 2, 1);
+        assert_eq!(state.board[0].finished_ms, None);
+        assert!(state.is_running());
+
+        state.update("Diver", 3, 1);
+        let finished_ms = state.board[0].finished_ms;
+        assert!(finished_ms.is_some());
+        assert!(!state.is_running());
+
+        std::thread::sleep(Duration::from_millis(5));
+        state.update("Diver", 4, 1);
+        assert_eq!(state.board[0].finished_ms, finished_ms);
+        assert_eq!(state.board[0].completed, 4);
+    }
+}
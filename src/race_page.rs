@@ -0,0 +1,318 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// Hosting and joining races on the local network, and the scoreboard of the
+// race. The race itself is run on the keycode trainer.
+
+use adw::prelude::*;
+use gtk::glib;
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::db;
+use crate::gui_templates;
+use crate::picker;
+use crate::race;
+
+/// Called with the race the host started and the connection to report to
+pub type StartRace = Rc<dyn Fn(race::RaceStart, Rc<race::Connection>)>;
+/// Called with the scoreboard and the player's own name on it
+pub type ShowBoard = Rc<dyn Fn(&[race::Standing], &str)>;
+/// Gives the Stratagems the host trains, everyone races with them
+pub type GetPool = Rc<dyn Fn() -> Vec<db::Stratagem>>;
+
+/// The page shares everything, so a clone is just another handle to it
+#[derive(Clone)]
+struct RacePage {
+    name_row: adw::EntryRow,
+    port_row: adw::SpinRow,
+    length_row: adw::ComboRow,
+    host_row: adw::ActionRow,
+    host_button: gtk::Button,
+    start_button: gtk::Button,
+    address_row: adw::EntryRow,
+    leave_button: gtk::Button,
+    board_list: gtk::ListBox,
+    toasts: adw::ToastOverlay,
+    connection: Rc<RefCell<Option<Rc<race::Connection>>>>,
+    /// The player's name on the scoreboard, the host may have changed it
+    own_name: Rc<RefCell<String>>,
+    /// How many codes the last race that was started is long
+    length: Rc<Cell<usize>>,
+    on_start: StartRace,
+    on_board: ShowBoard,
+    get_pool: GetPool,
+}
+
+/// `on_start` is called when the host starts a race, `on_board` with the
+/// scoreboard and the player's own name whenever someone made progress. An
+/// empty scoreboard means the player left. A race that is hosted is run with
+/// the Stratagems from `get_pool`.
+pub fn create_race_page(on_start: StartRace, on_board: ShowBoard, get_pool: GetPool)
+                        -> adw::NavigationPage {
+    let lengths: Vec<String> = race::RACE_LENGTHS.iter()
+        .map(|length| format!("{length} codes"))
+        .collect();
+    let lengths: Vec<&str> = lengths.iter().map(String::as_str).collect();
+    let list = || gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .css_classes(*&["boxed-list"])
+        .build();
+
+    let page = RacePage {
+        name_row: adw::EntryRow::builder()
+            .title("Name")
+            .text(glib::user_name().to_string_lossy())
+            .build(),
+        port_row: adw::SpinRow::builder()
+            .title("Port")
+            .adjustment(&gtk::Adjustment::new(race::DEFAULT_PORT as f64, 1024.0, 65535.0,
+                                              1.0, 10.0, 0.0))
+            .build(),
+        length_row: adw::ComboRow::builder()
+            .title("Race length")
+            .model(&gtk::StringList::new(&lengths))
+            .build(),
+        host_row: adw::ActionRow::builder()
+            .title("Host a race")
+            .subtitle("Others on your network join this computer")
+            .build(),
+        host_button: gtk::Button::builder()
+            .label("Host")
+            .valign(gtk::Align::Center)
+            .build(),
+        start_button: gtk::Button::builder()
+            .label("Start Race")
+            .css_classes(*&["suggested-action"])
+            .valign(gtk::Align::Center)
+            .sensitive(false)
+            .build(),
+        address_row: adw::EntryRow::builder()
+            .title("Address of the host, like 192.168.1.20 or 192.168.1.20:47623")
+            .show_apply_button(true)
+            .build(),
+        leave_button: gtk::Button::builder()
+            .label("Leave")
+            .css_classes(*&["destructive-action"])
+            .valign(gtk::Align::Center)
+            .sensitive(false)
+            .build(),
+        board_list: list(),
+        toasts: adw::ToastOverlay::new(),
+        connection: Rc::new(RefCell::new(None)),
+        own_name: Rc::new(RefCell::new(String::new())),
+        length: Rc::new(Cell::new(0)),
+        on_start,
+        on_board,
+        get_pool,
+    };
+
+    let name_list = list();
+    name_list.append(&page.name_row);
+    let name_group = adw::PreferencesGroup::builder()
+        .title("Player")
+        .build();
+    name_group.add(&name_list);
+
+    page.host_row.add_suffix(&page.host_button);
+    let host_list = list();
+    host_list.append(&page.port_row);
+    host_list.append(&page.length_row);
+    host_list.append(&page.host_row);
+    let host_group = adw::PreferencesGroup::builder()
+        .title("Host")
+        .description("Everyone gets the same Stratagems, whoever is through first wins")
+        .header_suffix(&page.start_button)
+        .build();
+    host_group.add(&host_list);
+
+    let join_list = list();
+    join_list.append(&page.address_row);
+    let join_group = adw::PreferencesGroup::builder()
+        .title("Join")
+        .build();
+    join_group.add(&join_list);
+
+    page.board_list.set_placeholder(Some(&gtk::Label::builder()
+        .label("Host or join a race to see everyone here")
+        .css_classes(*&["dim-label"])
+        .margin_top(12)
+        .margin_bottom(12)
+        .build()));
+    let board_group = adw::PreferencesGroup::builder()
+        .title("Scoreboard")
+        .header_suffix(&page.leave_button)
+        .build();
+    board_group.add(&page.board_list);
+
+    let host_page = page.clone();
+    page.host_button.connect_clicked(move |_| host_page.host());
+    let start_page = page.clone();
+    page.start_button.connect_clicked(move |_| start_page.start_race());
+    let join_page = page.clone();
+    page.address_row.connect_apply(move |_| join_page.join());
+    let leave_page = page.clone();
+    page.leave_button.connect_clicked(move |_| leave_page.leave());
+
+    let content = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(18)
+        .margin_top(12)
+        .margin_bottom(12)
+        .build();
+    content.append(&name_group);
+    content.append(&host_group);
+    content.append(&join_group);
+    content.append(&board_group);
+    page.toasts.set_child(Some(&gui_templates::create_scrolled_content(&content)));
+    gui_templates::create_titled_page("LAN Race", None, &page.toasts)
+}
+
+impl RacePage {
+    fn host(&self) {
+        let port = self.port_row.value() as u16;
+        let (sender, receiver) = async_channel::unbounded();
+        match race::RaceHost::start(&self.name_row.text(), port, sender) {
+            Ok(host) => {
+                self.host_row.set_subtitle(&format!(
+                    "Others join {}:{} or this computer's IP address",
+                    glib::host_name(), host.get_port()));
+                self.connect(race::Connection::Host(host), receiver);
+            }
+            Err(e) => self.toasts.add_toast(adw::Toast::new(
+                &format!("Could not host on port {port}: {e}"))),
+        }
+    }
+
+    fn join(&self) {
+        let address = self.address_row.text();
+        if address.trim().is_empty() {
+            self.address_row.error_bell();
+            return;
+        }
+        let (sender, receiver) = async_channel::unbounded();
+        match race::RaceClient::join(&address, &self.name_row.text(), sender) {
+            Ok(client) => self.connect(race::Connection::Client(client), receiver),
+            Err(e) => self.toasts.add_toast(adw::Toast::new(
+                &format!("Could not join {address}: {e}"))),
+        }
+    }
+
+    /// Listens to the race until the player leaves it
+    fn connect(&self, connection: race::Connection,
+               receiver: async_channel::Receiver<race::RaceEvent>) {
+        let connection = Rc::new(connection);
+        self.connection.replace(Some(connection.clone()));
+        self.set_connected(true);
+
+        let page = self.clone();
+        glib::spawn_future_local(async move {
+            while let Ok(event) = receiver.recv().await {
+                // Events of a race the player left don't matter anymore
+                let current = page.connection.borrow().clone();
+                if !current.is_some_and(|current| Rc::ptr_eq(&current, &connection)) {
+                    return;
+                }
+                match event {
+                    race::RaceEvent::Joined(name) => {
+                        page.own_name.replace(name);
+                    }
+                    race::RaceEvent::Started(start) => {
+                        page.length.set(start.length);
+                        (page.on_start)(start, connection.clone());
+                    }
+                    race::RaceEvent::Scoreboard(board) => {
+                        page.fill_board(&board);
+                        (page.on_board)(&board, &page.own_name.borrow());
+                    }
+                    race::RaceEvent::Rejected(reason) => {
+                        page.toasts.add_toast(adw::Toast::new(&reason));
+                    }
+                    race::RaceEvent::Disconnected(reason) => {
+                        page.toasts.add_toast(adw::Toast::new(&reason));
+                        page.disconnect();
+                    }
+                }
+            }
+        });
+    }
+
+    fn start_race(&self) {
+        if let Some(race::Connection::Host(host)) = self.connection.borrow().as_deref() {
+            let length = race::RACE_LENGTHS.get(self.length_row.selected() as usize)
+                .copied()
+                .unwrap_or(race::RACE_LENGTHS[0]);
+            host.start_race(picker::random_seed(), length, (self.get_pool)());
+        }
+    }
+
+    fn leave(&self) {
+        if let Some(connection) = self.connection.borrow().as_ref() {
+            connection.leave();
+        }
+        self.disconnect();
+    }
+
+    /// Forgets the race
+    fn disconnect(&self) {
+        self.connection.replace(None);
+        self.board_list.remove_all();
+        self.host_row.set_subtitle("Others on your network join this computer");
+        self.set_connected(false);
+        (self.on_board)(&[], "");
+    }
+
+    /// Only one race at a time, and only the host starts it
+    fn set_connected(&self, connected: bool) {
+        let hosting = matches!(self.connection.borrow().as_deref(),
+                               Some(race::Connection::Host(_)));
+        self.name_row.set_sensitive(!connected);
+        self.port_row.set_sensitive(!connected);
+        self.host_button.set_sensitive(!connected);
+        self.address_row.set_sensitive(!connected);
+        self.length_row.set_sensitive(!connected || hosting);
+        self.start_button.set_sensitive(hosting);
+        self.leave_button.set_sensitive(connected);
+        self.leave_button.set_label(if hosting { "Stop Hosting" } else { "Leave" });
+    }
+
+    fn fill_board(&self, board: &[race::Standing]) {
+        self.board_list.remove_all();
+        let own_name = self.own_name.borrow();
+        for (place, standing) in board.iter().enumerate() {
+            let mut title = format!("{}. {}", place + 1, standing.name);
+            if standing.name == *own_name {
+                title += " (You)";
+            }
+            let mut subtitle = match (standing.finished_ms, self.length.get()) {
+                (Some(ms), _) => format!("Through in {}",
+                                         gui_templates::format_duration(Duration::from_millis(ms))),
+                (None, 0) => "Ready".to_string(),
+                (None, length) => format!("{} of {length} codes", standing.completed),
+            };
+            if standing.mistakes > 0 {
+                subtitle += &format!(" · {} mistakes", standing.mistakes);
+            }
+            if !standing.connected {
+                subtitle += " · Left";
+            }
+            self.board_list.append(&adw::ActionRow::builder()
+                .title(title)
+                .subtitle(subtitle)
+                .use_markup(false)
+                .build());
+        }
+    }
+}
@@ -0,0 +1,422 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// Sessions and games are recorded key by key, so they can be watched again
+// later. A replay is a list of events: a Stratagem was shown, a key was
+// pressed. Every event has its time since the recording started. Replays are
+// saved as one JSON file each in the user data directory, so they can also be
+// sent to someone else.
+
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+
+use crate::db;
+use crate::hero;
+use crate::picker;
+use crate::stats;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayEvent {
+    Shown{at: Duration, stratagem: db::Stratagem},
+    Key{at: Duration, code: db::Code},
+}
+
+impl ReplayEvent {
+    pub fn get_time(&self) -> Duration {
+        match self {
+            ReplayEvent::Shown{at, ..} | ReplayEvent::Key{at, ..} => *at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replay {
+    pub mode: stats::TrainerMode,
+    /// The seed the Stratagems were picked with
+    pub seed: u64,
+    /// The Stratagems the seed picked from
+    pub pool: picker::SharedPool,
+    /// Only for Stratagem Hero
+    pub difficulty: Option<hero::Difficulty>,
+    pub started: SystemTime,
+    /// Oldest first
+    pub events: Vec<ReplayEvent>,
+}
+
+/// How a replay looks on disk. Times are in milliseconds, the start is in
+/// seconds since the Unix epoch.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ReplayFile {
+    mode: String,
+    seed: u64,
+    /// Like in a seed code, older replays have none and used the default
+    /// pool
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pool: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    difficulty: Option<String>,
+    started: u64,
+    events: Vec<EventFile>,
+}
+
+/// An event has either a Stratagem or a key
+#[derive(serde::Serialize, serde::Deserialize)]
+struct EventFile {
+    at_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    shown: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+}
+
+impl Replay {
+    /// How long the recording is
+    pub fn get_duration(&self) -> Duration {
+        self.events.last().map_or(Duration::ZERO, ReplayEvent::get_time)
+    }
+
+    /// How many Stratagems were shown, a Stratagem shown again after a wrong
+    /// key counts twice
+    pub fn get_shown_count(&self) -> usize {
+        self.events.iter().filter(|e| matches!(e, ReplayEvent::Shown{..})).count()
+    }
+
+    /// The seed code that picks the Stratagems of this replay again
+    pub fn get_seed_code(&self) -> picker::SeedCode {
+        picker::SeedCode {
+            seed: self.seed,
+            pool: self.pool.clone(),
+        }
+    }
+
+    /// A short description for lists, like "Stratagem Hero, Hard"
+    pub fn get_title(&self) -> String {
+        match self.difficulty {
+            Some(difficulty) => format!("{}, {difficulty}", self.mode),
+            None => self.mode.to_string(),
+        }
+    }
+
+    fn from_file(file: ReplayFile) -> Result<Self, String> {
+        let events = file.events.into_iter()
+            .map(|event| {
+                let at = Duration::from_millis(event.at_ms);
+                match (event.shown, event.key) {
+                    (Some(shown), None) => db::Stratagem::from_str(&shown)
+                        .map(|stratagem| ReplayEvent::Shown{at, stratagem})
+                        .map_err(|_| format!("Unknown Stratagem {shown}")),
+                    (None, Some(key)) => db::Code::from_str(&key)
+                        .map(|code| ReplayEvent::Key{at, code})
+                        .map_err(|_| format!("Unknown key {key}")),
+                    _ => Err("An event needs either a Stratagem or a key".to_string()),
+                }
+            })
+            .collect::<Result<Vec<ReplayEvent>, String>>()?;
+        Ok(Replay {
+            mode: stats::TrainerMode::from_str(&file.mode)
+                .map_err(|_| format!("Unknown mode {}", file.mode))?,
+            seed: file.seed,
+            pool: match file.pool {
+                Some(pool) => picker::SharedPool::parse(&pool)?,
+                None => picker::SharedPool::default(),
+            },
+            difficulty: match file.difficulty {
+                Some(difficulty) => Some(hero::Difficulty::from_str(&difficulty)
+                                         .map_err(|_| format!("Unknown difficulty {difficulty}"))?),
+                None => None,
+            },
+            started: SystemTime::UNIX_EPOCH + Duration::from_secs(file.started),
+            events,
+        })
+    }
+
+    fn to_file(&self) -> ReplayFile {
+        ReplayFile {
+            mode: self.mode.to_string(),
+            seed: self.seed,
+            pool: self.pool.to_code(),
+            difficulty: self.difficulty.map(|d| d.to_string()),
+            started: self.started.duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            events: self.events.iter()
+                .map(|event| match event {
+                    ReplayEvent::Shown{at, stratagem} => EventFile {
+                        at_ms: at.as_millis() as u64,
+                        shown: Some(stratagem.to_string()),
+                        key: None,
+                    },
+                    ReplayEvent::Key{at, code} => EventFile {
+                        at_ms: at.as_millis() as u64,
+                        shown: None,
+                        key: Some(code.to_string()),
+                    },
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Records a replay while it happens. Like the attempt timer, the first key
+/// after a Stratagem is placed by when the key handler got it and the keys
+/// after it by the times of their events.
+#[derive(Debug, Clone)]
+pub struct ReplayRecorder {
+    replay: Replay,
+    started_at: Instant,
+    /// When the recording was paused, if it is
+    paused_at: Option<Instant>,
+    /// The event time and place in the replay of the first key after the
+    /// last Stratagem that was shown
+    first_key: Option<(u32, Duration)>,
+}
+
+impl ReplayRecorder {
+    pub fn new(mode: stats::TrainerMode, code: picker::SeedCode,
+               difficulty: Option<hero::Difficulty>) -> Self {
+        ReplayRecorder {
+            replay: Replay {
+                mode,
+                seed: code.seed,
+                pool: code.pool,
+                difficulty,
+                started: SystemTime::now(),
+                events: Vec::new(),
+            },
+            started_at: Instant::now(),
+            paused_at: None,
+            first_key: None,
+        }
+    }
+
+    /// Leaves the time until `resume` out of the recording
+    pub fn pause(&mut self) {
+        self.paused_at.get_or_insert(Instant::now());
+    }
+
+    pub fn resume(&mut self) {
+        if let Some(paused_at) = self.paused_at.take() {
+            self.started_at += paused_at.elapsed();
+        }
+    }
+
+    /// Records that a Stratagem is shown right now
+    pub fn record_shown(&mut self, stratagem: db::Stratagem) {
+        let at = self.get_offset(Instant::now());
+        self.replay.events.push(ReplayEvent::Shown{at, stratagem});
+        self.first_key = None;
+    }
+
+    /// Records a key pressed at the given time
+    pub fn record_key(&mut self, code: db::Code, time: stats::KeyTime) {
+        let at = match self.first_key {
+            Some((event_ms, first_at)) => self.get_offset_after(
+                first_at + stats::get_event_delta(event_ms, time.event_ms)),
+            None => {
+                let at = self.get_offset(time.received);
+                self.first_key = Some((time.event_ms, at));
+                at
+            }
+        };
+        self.replay.events.push(ReplayEvent::Key{at, code});
+    }
+
+    /// The replay so far
+    pub fn get_replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    fn get_offset(&self, time: Instant) -> Duration {
+        self.get_offset_after(time.saturating_duration_since(self.started_at))
+    }
+
+    /// Events can't go back in time, even if the key event is a little older
+    /// than the moment the Stratagem was shown
+    fn get_offset_after(&self, at: Duration) -> Duration {
+        match self.replay.events.last() {
+            Some(last) if last.get_time() > at => last.get_time(),
+            _ => at,
+        }
+    }
+}
+
+/// Plays a replay back at some speed. It only says which events are due,
+/// showing them is up to the caller.
+#[derive(Debug, Clone)]
+pub struct ReplayPlayer {
+    pub replay: Replay,
+    /// 2 plays twice as fast
+    pub speed: f64,
+    started_at: Instant,
+    next: usize,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay, speed: f64) -> Self {
+        ReplayPlayer {
+            replay,
+            speed,
+            started_at: Instant::now(),
+            next: 0,
+        }
+    }
+
+    /// How far the replay has come, in the time of the recording
+    pub fn get_position(&self, now: Instant) -> Duration {
+        now.duration_since(self.started_at).mul_f64(self.speed)
+    }
+
+    /// Returns the events that are due since the last call
+    pub fn advance(&mut self, now: Instant) -> Vec<ReplayEvent> {
+        let position = self.get_position(now);
+        let due: Vec<ReplayEvent> = self.replay.events[self.next..].iter()
+            .take_while(|event| event.get_time() <= position)
+            .copied()
+            .collect();
+        self.next += due.len();
+        due
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.next >= self.replay.events.len()
+    }
+}
+
+pub fn get_replays_dir() -> PathBuf {
+    gtk::glib::user_data_dir().join("hd2helper").join("replays")
+}
+
+/// Writes the replay to a new file in the replay directory and returns its
+/// path
+pub fn save_replay(replay: &Replay) -> std::io::Result<PathBuf> {
+    let dir = get_replays_dir();
+    std::fs::create_dir_all(&dir)?;
+    let started = replay.started.duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mode: String = replay.mode.to_string().split_whitespace().collect();
+    let content = serde_json::to_string(&replay.to_file())?;
+    // Replays started in the same second, like an imported copy, get a
+    // number instead of replacing each other
+    for number in 1.. {
+        let name = match number {
+            1 => format!("{started}-{}.json", mode.to_lowercase()),
+            _ => format!("{started}-{}-{number}.json", mode.to_lowercase()),
+        };
+        let path = dir.join(name);
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(content.as_bytes())?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!("There is always a free number")
+}
+
+/// Reads one replay, from anywhere
+pub fn load_replay(path: &Path) -> Result<Replay, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read {}: {e}", path.display()))?;
+    let file: ReplayFile = serde_json::from_str(&content)
+        .map_err(|e| format!("{} is not a replay: {e}", path.display()))?;
+    Replay::from_file(file).map_err(|e| format!("{} is broken: {e}", path.display()))
+}
+
+/// Copies a replay from somewhere else into the replay directory. Fails if
+/// it isn't a replay.
+pub fn import_replay(path: &Path) -> Result<PathBuf, String> {
+    let replay = load_replay(path)?;
+    save_replay(&replay).map_err(|e| format!("Could not import {}: {e}", path.display()))
+}
+
+/// Reads every replay in the replay directory, the newest first. Replays
+/// that can't be read are reported and left out.
+pub fn load_replays() -> (Vec<(PathBuf, Replay)>, Vec<String>) {
+    let mut problems = Vec::new();
+    let entries = match std::fs::read_dir(get_replays_dir()) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return (Vec::new(), problems),
+        Err(e) => {
+            problems.push(format!("Could not read the replays: {e}"));
+            return (Vec::new(), problems);
+        }
+    };
+    let mut replays: Vec<(PathBuf, Replay)> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+        .filter_map(|path| match load_replay(&path) {
+            Ok(replay) => Some((path, replay)),
+            Err(problem) => {
+                problems.push(problem);
+                None
+            }
+        })
+        .collect();
+    replays.sort_by_key(|(_, replay)| std::cmp::Reverse(replay.started));
+    (replays, problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_recorded_by_their_events() {
+        let code = picker::SeedCode{seed: 7, pool: picker::SharedPool::default()};
+        let mut recorder = ReplayRecorder::new(stats::TrainerMode::Practice, code, None);
+        recorder.record_shown(db::Stratagem::Resupply);
+        let received = recorder.started_at + Duration::from_secs(1);
+        for event_ms in [5000, 5150, 5100] {
+            recorder.record_key(db::Code::Down, stats::KeyTime{event_ms, received});
+        }
+        let times: Vec<Duration> = recorder.get_replay().events[1..].iter()
+            .map(ReplayEvent::get_time)
+            .collect();
+        assert_eq!(times, [Duration::from_millis(1000), Duration::from_millis(1150),
+                           Duration::from_millis(1150)]);
+    }
+
+    #[test]
+    fn pool_is_saved() {
+        let code = picker::SeedCode {
+            seed: 7,
+            pool: picker::SharedPool::Stratagems(vec![db::Stratagem::Resupply,
+                                                      db::Stratagem::OrbitalLaser]),
+        };
+        let mut recorder = ReplayRecorder::new(stats::TrainerMode::CallOut, code.clone(), None);
+        let received = recorder.started_at + Duration::from_millis(500);
+        recorder.record_key(db::Code::Right, stats::KeyTime{event_ms: 0, received});
+        let json = serde_json::to_string(&recorder.get_replay().to_file()).unwrap();
+        let replay = Replay::from_file(serde_json::from_str(&json).unwrap()).unwrap();
+        assert_eq!(replay.get_seed_code(), code);
+        assert_eq!(replay.mode, stats::TrainerMode::CallOut);
+        assert_eq!(replay.events, recorder.get_replay().events);
+    }
+
+    #[test]
+    fn older_replays_use_the_default_pool() {
+        let json = r#"{"mode": "Practice", "seed": 7, "started": 0,
+                       "events": [{"at_ms": 0, "shown": "Resupply"}, {"at_ms": 300, "key": "Down"}]}"#;
+        let replay = Replay::from_file(serde_json::from_str(json).unwrap()).unwrap();
+        assert_eq!(replay.pool, picker::SharedPool::default());
+        assert_eq!(replay.get_duration(), Duration::from_millis(300));
+    }
+}
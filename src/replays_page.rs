@@ -0,0 +1,198 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// Lists the recorded sessions and games and the replays someone sent. Each
+// of them can be played back on the keycode trainer or raced as a ghost.
+
+use adw::prelude::*;
+use gtk::gio;
+use gtk::glib;
+use std::rc::Rc;
+use std::time::SystemTime;
+
+use crate::gui_templates;
+use crate::replay;
+
+/// The speeds a replay can be played at
+const SPEEDS: [f64; 4] = [1.0, 2.0, 4.0, 8.0];
+
+/// `on_play` is called with a replay that should be played back and its
+/// speed, `on_race` with a replay that should be raced
+pub fn create_replays_page(on_play: Rc<dyn Fn(replay::Replay, f64)>,
+                           on_race: Rc<dyn Fn(replay::Replay)>)
+                           -> adw::NavigationPage {
+    let toasts = adw::ToastOverlay::new();
+    let speeds: Vec<String> = SPEEDS.iter().map(|speed| format!("{speed}×")).collect();
+    let speeds: Vec<&str> = speeds.iter().map(String::as_str).collect();
+    let speed_row = adw::ComboRow::builder()
+        .title("Playback speed")
+        .model(&gtk::StringList::new(&speeds))
+        .build();
+    let open_row = adw::ActionRow::builder()
+        .title("Import a replay file")
+        .subtitle("For example one a teammate sent, to watch or race it")
+        .activatable(true)
+        .build();
+    open_row.add_suffix(&gtk::Image::from_icon_name("document-open-symbolic"));
+    let options_list = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .css_classes(*&["boxed-list"])
+        .build();
+    options_list.append(&speed_row);
+    options_list.append(&open_row);
+    let options_group = adw::PreferencesGroup::builder()
+        .title("Playback")
+        .build();
+    options_group.add(&options_list);
+
+    let replay_list = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .css_classes(*&["boxed-list"])
+        .build();
+    replay_list.set_placeholder(Some(&gtk::Label::builder()
+        .label("Sessions and games of Stratagem Hero are recorded here")
+        .css_classes(*&["dim-label"])
+        .margin_top(12)
+        .margin_bottom(12)
+        .build()));
+    let replay_group = adw::PreferencesGroup::builder()
+        .title("Recordings")
+        .build();
+    replay_group.add(&replay_list);
+
+    let content = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(18)
+        .margin_top(12)
+        .margin_bottom(12)
+        .build();
+    content.append(&options_group);
+    content.append(&replay_group);
+    toasts.set_child(Some(&gui_templates::create_scrolled_content(&content)));
+
+    // Plays at the chosen speed
+    let play = {
+        let speed_row = speed_row.clone();
+        Rc::new(move |replay: replay::Replay| {
+            let speed = SPEEDS.get(speed_row.selected() as usize).copied().unwrap_or(1.0);
+            on_play(replay, speed);
+        })
+    };
+
+    // Sessions and games end all the time, so read the replays every time
+    // the page is shown
+    let refresh = {
+        let toasts = toasts.clone();
+        Rc::new(move || {
+            let (replays, problems) = replay::load_replays();
+            for problem in problems {
+                toasts.add_toast(adw::Toast::new(&problem));
+            }
+            fill_replays(&replay_list, replays, play.clone(), on_race.clone(), &toasts);
+        })
+    };
+    let map_refresh = refresh.clone();
+    content.connect_map(move |_| map_refresh());
+
+    let open_toasts = toasts.clone();
+    open_row.connect_activated(move |row| {
+        let filter = gtk::FileFilter::new();
+        filter.set_name(Some("Replays"));
+        filter.add_suffix("json");
+        let filters = gio::ListStore::new::<gtk::FileFilter>();
+        filters.append(&filter);
+        let dialog = gtk::FileDialog::builder()
+            .title("Import Replay")
+            .filters(&filters)
+            .build();
+        let toasts = open_toasts.clone();
+        let refresh = refresh.clone();
+        dialog.open(row.root().and_downcast::<gtk::Window>().as_ref(),
+                    gio::Cancellable::NONE,
+                    move |result| {
+            let Some(path) = result.ok().and_then(|file| file.path()) else {
+                return;
+            };
+            match replay::import_replay(&path) {
+                Ok(_) => refresh(),
+                Err(problem) => toasts.add_toast(adw::Toast::new(&problem)),
+            }
+        });
+    });
+
+    gui_templates::create_titled_page("Replays", None, &toasts)
+}
+
+fn fill_replays(list: &gtk::ListBox,
+                replays: Vec<(std::path::PathBuf, replay::Replay)>,
+                play: Rc<dyn Fn(replay::Replay)>,
+                race: Rc<dyn Fn(replay::Replay)>,
+                toasts: &adw::ToastOverlay) {
+    list.remove_all();
+    for (path, replay) in replays {
+        let row = adw::ActionRow::builder()
+            .title(format!("{} · {}", replay.get_title(), format_date(replay.started)))
+            .subtitle(format!("{} Stratagems in {} · Seed {}",
+                              replay.get_shown_count(),
+                              gui_templates::format_duration(replay.get_duration()),
+                              replay.get_seed_code().to_code()))
+            .build();
+
+        let delete = gtk::Button::builder()
+            .icon_name("user-trash-symbolic")
+            .tooltip_text("Delete Replay")
+            .valign(gtk::Align::Center)
+            .css_classes(*&["flat"])
+            .build();
+        let (delete_list, delete_row, delete_toasts) =
+            (list.clone(), row.clone(), toasts.clone());
+        delete.connect_clicked(move |_| match std::fs::remove_file(&path) {
+            Ok(()) => delete_list.remove(&delete_row),
+            Err(e) => delete_toasts.add_toast(adw::Toast::new(
+                &format!("Could not delete the replay: {e}"))),
+        });
+        row.add_suffix(&delete);
+
+        let play_button = gtk::Button::builder()
+            .icon_name("media-playback-start-symbolic")
+            .tooltip_text("Play Replay")
+            .valign(gtk::Align::Center)
+            .css_classes(*&["flat"])
+            .build();
+        let play = play.clone();
+        let play_replay = replay.clone();
+        play_button.connect_clicked(move |_| play(play_replay.clone()));
+        row.add_suffix(&play_button);
+
+        let race_button = gtk::Button::builder()
+            .icon_name("system-run-symbolic")
+            .tooltip_text("Race as Ghost")
+            .valign(gtk::Align::Center)
+            .css_classes(*&["flat"])
+            .build();
+        let race = race.clone();
+        race_button.connect_clicked(move |_| race(replay.clone()));
+        row.add_suffix(&race_button);
+        list.append(&row);
+    }
+}
+
+/// The local date and time a replay was recorded, in the format of the
+/// user's locale
+fn format_date(date: SystemTime) -> String {
+    let secs = date.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs();
+    glib::DateTime::from_unix_local(secs as i64)
+        .and_then(|date| date.format("%c"))
+        .map(|date| date.to_string())
+        .unwrap_or_default()
+}
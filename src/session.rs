@@ -0,0 +1,223 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// A practice session groups the attempts between pressing start and end, so
+// they can be looked at together afterwards.
+
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::db;
+use crate::picker;
+use crate::replay;
+use crate::stats;
+
+/// How many Stratagems are suggested to practice next time
+pub const FOCUS_SIZE: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub attempts: Vec<stats::Attempt>,
+    pub paused: bool,
+    /// Every Stratagem and key of the session
+    pub recorder: replay::ReplayRecorder,
+    /// The personal bests from before the session started
+    bests_before: Vec<(db::Stratagem, Duration)>,
+    /// The time spent practicing so far, without pauses
+    active: Duration,
+    /// When the session was started or resumed the last time
+    resumed_at: Option<Instant>,
+}
+
+/// A personal best that was beaten during a session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PersonalBest {
+    pub stratagem: db::Stratagem,
+    pub previous: Duration,
+    pub new: Duration,
+}
+
+/// How a session went
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionSummary {
+    /// Without pauses
+    pub duration: Duration,
+    pub codes: usize,
+    pub successes: usize,
+    /// The Stratagem with the lowest average time of its correct attempts
+    pub fastest: Option<(db::Stratagem, Duration)>,
+    /// The Stratagem with the highest average time of its correct attempts
+    pub slowest: Option<(db::Stratagem, Duration)>,
+    pub personal_bests: Vec<PersonalBest>,
+    /// What should be practiced next time, the most important first
+    pub focus: Vec<db::Stratagem>,
+}
+
+impl SessionSummary {
+    /// From 0 to 1, None if no code was entered
+    pub fn get_accuracy(&self) -> Option<f64> {
+        (self.codes > 0).then(|| self.successes as f64 / self.codes as f64)
+    }
+}
+
+impl Session {
+    /// Starts a session. `history` are all attempts so far, to know which
+    /// personal bests have to be beaten. `code` is the seed code the
+    /// Stratagems are picked with, for the replay.
+    pub fn start(history: &[stats::Attempt], code: picker::SeedCode) -> Self {
+        Session {
+            attempts: Vec::new(),
+            paused: false,
+            recorder: replay::ReplayRecorder::new(stats::TrainerMode::Practice, code, None),
+            bests_before: stats::get_personal_bests(history),
+            active: Duration::ZERO,
+            resumed_at: Some(Instant::now()),
+        }
+    }
+
+    /// Attempts made while the session is paused don't count
+    pub fn add(&mut self, attempt: stats::Attempt) {
+        if !self.paused {
+            self.attempts.push(attempt);
+        }
+    }
+
+    pub fn pause(&mut self) {
+        if let Some(resumed_at) = self.resumed_at.take() {
+            self.active += resumed_at.elapsed();
+        }
+        self.recorder.pause();
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        if self.paused {
+            self.resumed_at = Some(Instant::now());
+            self.recorder.resume();
+            self.paused = false;
+        }
+    }
+
+    /// The time spent practicing, without pauses
+    pub fn get_duration(&self) -> Duration {
+        self.active + self.resumed_at.map_or(Duration::ZERO, |r| r.elapsed())
+    }
+
+    pub fn summarize(&self) -> SessionSummary {
+        let summaries = stats::summarize(&self.attempts);
+        let by_time = || summaries.iter().filter_map(|s| s.total.map(|t| (s.stratagem, t)));
+
+        let personal_bests = stats::get_personal_bests(&self.attempts)
+            .into_iter()
+            .filter_map(|(stratagem, new)| {
+                let (_, previous) = self.bests_before.iter().find(|(s, _)| *s == stratagem)?;
+                (new < *previous).then_some(PersonalBest { stratagem, previous: *previous, new })
+            })
+            .collect();
+
+        // The least accurate Stratagems first, then the slowest ones
+        let mut focus: Vec<&stats::StratagemSummary> = summaries.iter().collect();
+        focus.sort_by(|a, b| {
+            let accuracy = |s: &stats::StratagemSummary| s.successes as f64 / s.attempts as f64;
+            accuracy(a).total_cmp(&accuracy(b))
+                .then(b.total.unwrap_or(Duration::MAX).cmp(&a.total.unwrap_or(Duration::MAX)))
+        });
+
+        SessionSummary {
+            duration: self.get_duration(),
+            codes: self.attempts.len(),
+            successes: self.attempts.iter().filter(|a| a.is_success()).count(),
+            fastest: by_time().min_by_key(|(_, time)| *time),
+            slowest: by_time().max_by_key(|(_, time)| *time),
+            personal_bests,
+            focus: focus.into_iter().take(FOCUS_SIZE).map(|s| s.stratagem).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+    use db::Stratagem::*;
+
+    fn seed_code() -> picker::SeedCode {
+        picker::SeedCode{seed: 7, pool: picker::SharedPool::default()}
+    }
+
+    fn attempt(stratagem: db::Stratagem, total_ms: u64, success: bool) -> stats::Attempt {
+        stats::Attempt {
+            stratagem,
+            mode: stats::TrainerMode::Practice,
+            started: SystemTime::UNIX_EPOCH,
+            reaction: Duration::from_millis(200),
+            intervals: Vec::new(),
+            total: Duration::from_millis(total_ms),
+            mistake_at: (!success).then_some(0),
+            wrong_key: None,
+        }
+    }
+
+    #[test]
+    fn personal_bests() {
+        let history = [attempt(Resupply, 1000, true), attempt(Reinforce, 2000, true)];
+        let mut session = Session::start(&history, seed_code());
+        session.add(attempt(Resupply, 800, true));
+        session.add(attempt(Reinforce, 2500, true));
+        session.add(attempt(Reinforce, 1500, false));
+        // Nothing to beat yet
+        session.add(attempt(EagleAirstrike, 1500, true));
+
+        let summary = session.summarize();
+        assert_eq!((summary.codes, summary.successes), (4, 3));
+        assert_eq!(summary.get_accuracy(), Some(0.75));
+        assert_eq!(summary.fastest, Some((Resupply, Duration::from_millis(800))));
+        assert_eq!(summary.slowest, Some((Reinforce, Duration::from_millis(2500))));
+        assert_eq!(summary.personal_bests, [PersonalBest {
+            stratagem: Resupply,
+            previous: Duration::from_millis(1000),
+            new: Duration::from_millis(800),
+        }]);
+    }
+
+    #[test]
+    fn focus_order() {
+        let mut session = Session::start(&[], seed_code());
+        for attempt in [
+            attempt(Resupply, 500, true),
+            attempt(Reinforce, 3000, true),
+            attempt(EagleAirstrike, 1000, true),
+            attempt(OrbitalLaser, 1000, true),
+            attempt(OrbitalLaser, 1000, false),
+        ] {
+            session.add(attempt);
+        }
+        let summary = session.summarize();
+        assert_eq!(summary.focus, [OrbitalLaser, Reinforce, EagleAirstrike]);
+        assert!(summary.personal_bests.is_empty());
+    }
+
+    #[test]
+    fn paused_attempts_are_left_out() {
+        let mut session = Session::start(&[], seed_code());
+        session.pause();
+        session.add(attempt(Resupply, 500, true));
+        session.resume();
+        session.add(attempt(Reinforce, 500, false));
+
+        let summary = session.summarize();
+        assert_eq!((summary.codes, summary.successes), (1, 0));
+        assert_eq!(summary.fastest, None);
+        assert_eq!(summary.focus, [Reinforce]);
+    }
+}
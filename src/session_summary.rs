@@ -0,0 +1,113 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// The window shown at the end of a practice session.
+
+use adw::prelude::*;
+
+use crate::gui_templates;
+use crate::session;
+
+/// Shows the summary in a window on top of the one the widget is in
+pub fn open_session_summary(summary: &session::SessionSummary, widget: &impl IsA<gtk::Widget>) {
+    let content = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(18)
+        .margin_top(12)
+        .margin_bottom(12)
+        .margin_start(12)
+        .margin_end(12)
+        .build();
+    content.append(&create_overview(summary));
+
+    if !summary.personal_bests.is_empty() {
+        let group = adw::PreferencesGroup::builder()
+            .title("Personal Bests")
+            .build();
+        for best in summary.personal_bests.iter() {
+            let row = adw::ActionRow::builder()
+                .title(best.stratagem.to_string())
+                .subtitle(format!("Before {}",
+                                  gui_templates::format_short_duration(best.previous)))
+                .build();
+            row.add_prefix(&gui_templates::create_stratagem_icon(&best.stratagem, 32));
+            row.add_suffix(&gtk::Label::builder()
+                           .label(gui_templates::format_short_duration(best.new))
+                           .css_classes(*&["numeric", "success"])
+                           .build());
+            group.add(&row);
+        }
+        content.append(&group);
+    }
+
+    if !summary.focus.is_empty() {
+        let group = adw::PreferencesGroup::builder()
+            .title("Focus Next Time")
+            .description("The least accurate and slowest Stratagems of this session")
+            .build();
+        for stratagem in summary.focus.iter() {
+            let row = adw::ActionRow::builder()
+                .title(stratagem.to_string())
+                .subtitle(crate::get_keycode_str(&stratagem.get_keycode()))
+                .build();
+            row.update_property(&[gtk::accessible::Property::Description(
+                &gui_templates::describe_keycode(&stratagem.get_keycode()))]);
+            row.add_prefix(&gui_templates::create_stratagem_icon(stratagem, 32));
+            group.add(&row);
+        }
+        content.append(&group);
+    }
+
+    let toolbar = adw::ToolbarView::new();
+    toolbar.add_top_bar(&adw::HeaderBar::new());
+    toolbar.set_content(Some(&gui_templates::create_scrolled_content(&content)));
+    let window = adw::Window::builder()
+        .title("Session Summary")
+        .content(&toolbar)
+        .modal(true)
+        .default_width(450)
+        .default_height(550)
+        .build();
+    window.set_transient_for(widget.root().and_downcast::<gtk::Window>().as_ref());
+    window.present();
+}
+
+/// The numbers of the whole session
+fn create_overview(summary: &session::SessionSummary) -> adw::PreferencesGroup {
+    let group = adw::PreferencesGroup::builder()
+        .title("Session")
+        .build();
+    let accuracy = summary.get_accuracy()
+        .map_or("-".to_string(), |accuracy| format!("{:.0}%", accuracy * 100.0));
+    let format_stratagem = |stratagem: Option<(crate::db::Stratagem, std::time::Duration)>|
+        stratagem.map_or("-".to_string(), |(stratagem, time)|
+                         format!("{stratagem} · {}", gui_templates::format_short_duration(time)));
+    let rows = [
+        ("Practiced for", gui_templates::format_duration(summary.duration)),
+        ("Codes entered", summary.codes.to_string()),
+        ("Entered correctly", accuracy),
+        ("Fastest", format_stratagem(summary.fastest)),
+        ("Slowest", format_stratagem(summary.slowest)),
+    ];
+    for (title, value) in rows {
+        let row = adw::ActionRow::builder()
+            .title(title)
+            .build();
+        row.add_suffix(&gtk::Label::builder()
+                       .label(value)
+                       .css_classes(*&["numeric"])
+                       .build());
+        group.add(&row);
+    }
+    group
+}
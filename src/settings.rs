@@ -0,0 +1,355 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// The settings of the whole program. They are kept in GSettings when the
+// schema from the data directory is installed, otherwise, like in portable
+// and Windows builds, in a JSON file in the user config directory.
+
+use gtk::gio;
+use gtk::prelude::*;
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::path::Path;
+use std::path::PathBuf;
+use std::rc::Rc;
+use strum::IntoEnumIterator;
+
+use crate::accessibility;
+use crate::arrow_theme::ArrowTheme;
+use crate::db;
+use crate::sound;
+
+/// The id of the GSettings schema, the same as the application's
+pub const SCHEMA_ID: &str = "com.github.BjarneSeger.hd2helper";
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub input: InputSettings,
+    pub trainer: TrainerSettings,
+    pub appearance: accessibility::AccessibilitySettings,
+    pub audio: sound::SoundSettings,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct InputSettings {
+    pub trainer_keys: TrainerKeys,
+}
+
+/// The keys codes are entered with on the keycode trainer. Versus games
+/// always use both.
+#[derive(Debug, Default, PartialEq, Eq, strum_macros::Display, strum_macros::EnumIter,
+         Copy, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrainerKeys {
+    #[default]
+    #[strum(to_string = "WASD")]
+    Wasd,
+    #[strum(to_string = "Arrow keys")]
+    Arrows,
+    #[strum(to_string = "WASD and arrow keys")]
+    Both,
+}
+
+impl TrainerKeys {
+    /// The name the choice is saved with
+    pub fn get_id(&self) -> &'static str {
+        match self {
+            TrainerKeys::Wasd => "wasd",
+            TrainerKeys::Arrows => "arrows",
+            TrainerKeys::Both => "both",
+        }
+    }
+
+    pub fn uses_wasd(&self) -> bool {
+        *self != TrainerKeys::Arrows
+    }
+
+    pub fn uses_arrow_keys(&self) -> bool {
+        *self != TrainerKeys::Wasd
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct TrainerSettings {
+    /// Which kinds of Stratagems are trained, like in `db::StratagemFilter`
+    pub mission_only: bool,
+    pub warbond: bool,
+    pub removed: bool,
+    /// Whether Stratagems are called out instead of shown
+    pub call_out: bool,
+}
+
+impl Default for TrainerSettings {
+    fn default() -> Self {
+        let filter = db::StratagemFilter::default();
+        TrainerSettings {
+            mission_only: filter.mission_only,
+            warbond: filter.warbond,
+            removed: filter.removed,
+            call_out: false,
+        }
+    }
+}
+
+impl TrainerSettings {
+    /// The filter with the kinds of Stratagems from the settings, the patch
+    /// stays
+    pub fn apply_to(&self, filter: db::StratagemFilter) -> db::StratagemFilter {
+        db::StratagemFilter {
+            mission_only: self.mission_only,
+            warbond: self.warbond,
+            removed: self.removed,
+            ..filter
+        }
+    }
+}
+
+pub fn get_settings_path() -> PathBuf {
+    gtk::glib::user_config_dir().join("hd2helper").join("settings.json")
+}
+
+/// Where the settings are kept
+enum Backend {
+    GSettings(gio::Settings),
+    File(PathBuf),
+}
+
+impl Backend {
+    /// GSettings if the schema is installed, the file otherwise
+    fn find() -> Self {
+        let schema = gio::SettingsSchemaSource::default()
+            .and_then(|source| source.lookup(SCHEMA_ID, true));
+        match schema {
+            Some(schema) => Backend::GSettings(
+                gio::Settings::new_full(&schema, None::<&gio::SettingsBackend>, None)),
+            None => Backend::File(get_settings_path()),
+        }
+    }
+
+    fn load(&self) -> (Settings, Vec<String>) {
+        match self {
+            Backend::GSettings(gsettings) => (read_gsettings(gsettings), Vec::new()),
+            Backend::File(path) => load_file(path),
+        }
+    }
+
+    fn save(&self, settings: &Settings) -> std::io::Result<()> {
+        match self {
+            Backend::GSettings(gsettings) => write_gsettings(gsettings, settings)
+                .map_err(|e| std::io::Error::other(e.to_string())),
+            Backend::File(path) => {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(path, serde_json::to_string_pretty(settings)?)
+            }
+        }
+    }
+}
+
+/// Reads the settings file. Without one, the sound and accessibility
+/// settings of older versions are taken over.
+fn load_file(path: &Path) -> (Settings, Vec<String>) {
+    let mut problems = Vec::new();
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound =>
+            return (load_old_files(&mut problems), problems),
+        Err(e) => {
+            problems.push(format!("Could not read settings: {e}"));
+            return (Settings::default(), problems);
+        }
+    };
+    match serde_json::from_str::<Settings>(&content) {
+        Ok(settings) => (Settings {
+            audio: sound::SoundSettings {
+                volume: settings.audio.volume.clamp(0.0, 1.0),
+                ..settings.audio
+            },
+            ..settings
+        }, problems),
+        Err(e) => {
+            problems.push(format!("Saved settings are broken: {e}"));
+            (Settings::default(), problems)
+        }
+    }
+}
+
+/// Older versions kept the sound and accessibility settings in files of
+/// their own
+fn load_old_files(problems: &mut Vec<String>) -> Settings {
+    let dir = gtk::glib::user_config_dir().join("hd2helper");
+    let mut settings = Settings::default();
+    if let Some(audio) = load_old_file(&dir.join("sound.json"), problems) {
+        settings.audio = audio;
+    }
+    if let Some(appearance) = load_old_file(&dir.join("accessibility.json"), problems) {
+        settings.appearance = appearance;
+    }
+    settings
+}
+
+fn load_old_file<T: serde::de::DeserializeOwned>(path: &Path, problems: &mut Vec<String>)
+                                                 -> Option<T> {
+    let content = std::fs::read_to_string(path).ok()?;
+    match serde_json::from_str(&content) {
+        Ok(settings) => Some(settings),
+        Err(e) => {
+            problems.push(format!("Old settings in {} are broken: {e}", path.display()));
+            None
+        }
+    }
+}
+
+fn read_gsettings(gsettings: &gio::Settings) -> Settings {
+    let trainer_keys = gsettings.string("trainer-keys");
+    let arrow_theme = gsettings.string("arrow-theme");
+    Settings {
+        input: InputSettings {
+            trainer_keys: TrainerKeys::iter()
+                .find(|keys| keys.get_id() == trainer_keys)
+                .unwrap_or_default(),
+        },
+        trainer: TrainerSettings {
+            mission_only: gsettings.boolean("mission-stratagems"),
+            warbond: gsettings.boolean("warbond-stratagems"),
+            removed: gsettings.boolean("removed-stratagems"),
+            call_out: gsettings.boolean("call-out"),
+        },
+        appearance: accessibility::AccessibilitySettings {
+            large_arrows: gsettings.boolean("large-arrows"),
+            high_contrast: gsettings.boolean("high-contrast"),
+            reduce_motion: gsettings.boolean("reduce-motion"),
+            arrow_theme: ArrowTheme::iter()
+                .find(|theme| theme.get_id() == arrow_theme)
+                .unwrap_or_default(),
+        },
+        audio: sound::SoundSettings {
+            volume: gsettings.double("volume"),
+            muted: gsettings.boolean("muted"),
+        },
+    }
+}
+
+/// Writes all keys at once, so whoever watches them sees the settings
+/// change only once
+fn write_gsettings(gsettings: &gio::Settings, settings: &Settings)
+                   -> Result<(), gtk::glib::BoolError> {
+    gsettings.delay();
+    let written = (|| {
+        gsettings.set_string("trainer-keys", settings.input.trainer_keys.get_id())?;
+        gsettings.set_boolean("mission-stratagems", settings.trainer.mission_only)?;
+        gsettings.set_boolean("warbond-stratagems", settings.trainer.warbond)?;
+        gsettings.set_boolean("removed-stratagems", settings.trainer.removed)?;
+        gsettings.set_boolean("call-out", settings.trainer.call_out)?;
+        gsettings.set_boolean("large-arrows", settings.appearance.large_arrows)?;
+        gsettings.set_boolean("high-contrast", settings.appearance.high_contrast)?;
+        gsettings.set_boolean("reduce-motion", settings.appearance.reduce_motion)?;
+        gsettings.set_string("arrow-theme", settings.appearance.arrow_theme.get_id())?;
+        gsettings.set_double("volume", settings.audio.volume)?;
+        gsettings.set_boolean("muted", settings.audio.muted)
+    })();
+    gsettings.apply();
+    written
+}
+
+type Listener = Box<dyn Fn(&Settings)>;
+
+/// Holds the current settings and saves every change. A clone is just
+/// another handle to the same settings.
+#[derive(Clone)]
+pub struct SettingsStore {
+    backend: Rc<Backend>,
+    current: Rc<Cell<Settings>>,
+    listeners: Rc<RefCell<Vec<Listener>>>,
+}
+
+impl SettingsStore {
+    /// Reads the settings from GSettings or the file. Problems are printed,
+    /// the defaults are used instead.
+    pub fn load() -> Self {
+        let backend = Backend::find();
+        let (settings, problems) = backend.load();
+        for problem in problems {
+            eprintln!("{problem}");
+        }
+        let store = SettingsStore {
+            backend: Rc::new(backend),
+            current: Rc::new(Cell::new(settings)),
+            listeners: Rc::new(RefCell::new(Vec::new())),
+        };
+        // Others can change GSettings too, like dconf-editor
+        if let Backend::GSettings(gsettings) = store.backend.as_ref() {
+            let watching = store.clone();
+            gsettings.connect_changed(None, move |gsettings, _| {
+                watching.changed(read_gsettings(gsettings));
+            });
+        }
+        store
+    }
+
+    pub fn get(&self) -> Settings {
+        self.current.get()
+    }
+
+    /// Uses and saves the new settings
+    pub fn set(&self, settings: Settings) {
+        if settings == self.current.get() {
+            return;
+        }
+        self.current.set(settings);
+        if let Err(e) = self.backend.save(&settings) {
+            eprintln!("Could not save the settings: {e}");
+        }
+        self.notify();
+    }
+
+    /// Changes some of the settings
+    pub fn update(&self, change: impl FnOnce(&mut Settings)) {
+        let mut settings = self.get();
+        change(&mut settings);
+        self.set(settings);
+    }
+
+    /// Calls `listener` with the new settings whenever they changed
+    pub fn connect_changed(&self, listener: impl Fn(&Settings) + 'static) {
+        self.listeners.borrow_mut().push(Box::new(listener));
+    }
+
+    /// Where the settings are kept, for people who want to edit them by hand
+    pub fn describe_storage(&self) -> String {
+        match self.backend.as_ref() {
+            Backend::GSettings(_) => format!("Saved in GSettings as {SCHEMA_ID}"),
+            Backend::File(path) => format!("Saved in {}", path.display()),
+        }
+    }
+
+    /// Takes settings that were changed elsewhere
+    fn changed(&self, settings: Settings) {
+        if settings != self.current.get() {
+            self.current.set(settings);
+            self.notify();
+        }
+    }
+
+    fn notify(&self) {
+        // A listener may change the settings again, the others should see
+        // that
+        for listener in self.listeners.borrow().iter() {
+            listener(&self.current.get());
+        }
+    }
+}
@@ -0,0 +1,209 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// The upgrades of the Super Destroyer's ship modules. The user records which
+// ones they own, and the stats of the Stratagems are computed from that. The
+// owned upgrades are saved as JSON in the user config directory.
+
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::db;
+
+/// The upgrades that change the numbers of a Stratagem. Upgrades that only
+/// change how a Stratagem behaves once it's there are left out.
+#[derive(Debug, PartialEq, Eq, strum_macros::EnumString, strum_macros::Display,
+         strum_macros::EnumIter, Copy, Clone)]
+pub enum ShipUpgrade {
+    // Hangar
+    #[strum(to_string = "Liquid-Ventilated Cockpit", serialize = "LiquidVentilatedCockpit")]
+    LiquidVentilatedCockpit,
+    #[strum(to_string = "Pit Crew Hazard Pay", serialize = "PitCrewHazardPay")]
+    PitCrewHazardPay,
+    #[strum(to_string = "Expanded Weapons Bay", serialize = "ExpandedWeaponsBay")]
+    ExpandedWeaponsBay,
+    // Bridge
+    #[strum(to_string = "Targeting Software Upgrade", serialize = "TargetingSoftwareUpgrade")]
+    TargetingSoftwareUpgrade,
+    // Orbital Cannons
+    #[strum(to_string = "Zero-G Breech Loading", serialize = "ZeroGBreechLoading")]
+    ZeroGBreechLoading,
+    // Robotics Workshop
+    #[strum(to_string = "Streamlined Launch Process", serialize = "StreamlinedLaunchProcess")]
+    StreamlinedLaunchProcess,
+    // Engineering Bay
+    #[strum(to_string = "Synthetic Supplementation", serialize = "SyntheticSupplementation")]
+    SyntheticSupplementation,
+    // Patriotic Administration Center
+    #[strum(to_string = "Streamlined Request Process", serialize = "StreamlinedRequestProcess")]
+    StreamlinedRequestProcess,
+}
+
+/// How an upgrade changes one Stratagem
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct UpgradeEffect {
+    /// In percent of the base cooldown
+    pub cooldown_reduction: u32,
+    pub call_in_reduction: Duration,
+    pub extra_uses: u32,
+}
+
+impl UpgradeEffect {
+    fn cooldown(percent: u32) -> Self {
+        UpgradeEffect { cooldown_reduction: percent, call_in_reduction: Duration::ZERO, extra_uses: 0 }
+    }
+
+    fn call_in(secs: u64) -> Self {
+        UpgradeEffect { cooldown_reduction: 0, call_in_reduction: Duration::from_secs(secs), extra_uses: 0 }
+    }
+
+    fn uses(extra: u32) -> Self {
+        UpgradeEffect { cooldown_reduction: 0, call_in_reduction: Duration::ZERO, extra_uses: extra }
+    }
+}
+
+impl ShipUpgrade {
+    /// The ship module this upgrade is bought in
+    pub fn get_department(&self) -> db::Department {
+        use ShipUpgrade::*;
+        use db::Department::*;
+        match self {
+            LiquidVentilatedCockpit | PitCrewHazardPay | ExpandedWeaponsBay => Hangar,
+            TargetingSoftwareUpgrade => Bridge,
+            ZeroGBreechLoading => OrbitalCannons,
+            StreamlinedLaunchProcess => RoboticsWorkshop,
+            SyntheticSupplementation => EngineeringBay,
+            StreamlinedRequestProcess => PatrioticAdministrationCenter,
+        }
+    }
+
+    /// What the upgrade does, as the ship terminal describes it
+    pub fn get_description(&self) -> &'static str {
+        use ShipUpgrade::*;
+        match self {
+            LiquidVentilatedCockpit => "Eagle Stratagem cooldowns are 50% shorter",
+            PitCrewHazardPay => "Eagle Rearm takes 20% less time",
+            ExpandedWeaponsBay => "Eagle Stratagems can be used once more per rearm",
+            TargetingSoftwareUpgrade => "Orbital Stratagems arrive 1 s sooner",
+            ZeroGBreechLoading => "Orbital Stratagem cooldowns are 10% shorter",
+            StreamlinedLaunchProcess => "Sentries and emplacements arrive 2 s sooner",
+            SyntheticSupplementation => "Engineering Bay Stratagem cooldowns are 10% shorter",
+            StreamlinedRequestProcess => "Support weapon cooldowns are 10% shorter",
+        }
+    }
+
+    /// How the upgrade changes the given Stratagem, or None if it doesn't
+    pub fn get_effect(&self, stratagem: &db::Stratagem) -> Option<UpgradeEffect> {
+        use ShipUpgrade::*;
+        use db::Category;
+        let category = stratagem.get_category();
+        match self {
+            LiquidVentilatedCockpit if category == Category::Eagle =>
+                Some(UpgradeEffect::cooldown(50)),
+            PitCrewHazardPay if *stratagem == db::Stratagem::EagleRearm =>
+                Some(UpgradeEffect::cooldown(20)),
+            ExpandedWeaponsBay if category == Category::Eagle && stratagem.get_uses().is_some() =>
+                Some(UpgradeEffect::uses(1)),
+            TargetingSoftwareUpgrade if category == Category::Orbital =>
+                Some(UpgradeEffect::call_in(1)),
+            ZeroGBreechLoading if category == Category::Orbital =>
+                Some(UpgradeEffect::cooldown(10)),
+            StreamlinedLaunchProcess if category == Category::Defensive =>
+                Some(UpgradeEffect::call_in(2)),
+            SyntheticSupplementation
+                if stratagem.get_department() == Some(db::Department::EngineeringBay) =>
+                Some(UpgradeEffect::cooldown(10)),
+            StreamlinedRequestProcess if stratagem.uses_support_weapon_slot() =>
+                Some(UpgradeEffect::cooldown(10)),
+            _ => None,
+        }
+    }
+}
+
+/// The upgrades the user owns
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShipUpgrades {
+    pub owned: Vec<ShipUpgrade>,
+}
+
+impl ShipUpgrades {
+    pub fn has(&self, upgrade: ShipUpgrade) -> bool {
+        self.owned.contains(&upgrade)
+    }
+
+    /// Adds or removes an upgrade
+    pub fn set(&mut self, upgrade: ShipUpgrade, owned: bool) {
+        self.owned.retain(|u| *u != upgrade);
+        if owned {
+            self.owned.push(upgrade);
+        }
+    }
+
+    /// The stats of the Stratagem with every owned upgrade applied.
+    /// Cooldown reductions stack by multiplying.
+    pub fn get_effective_stats(&self, stratagem: &db::Stratagem) -> db::StratagemStats {
+        let mut stats = stratagem.get_stats();
+        for effect in self.owned.iter().filter_map(|u| u.get_effect(stratagem)) {
+            stats.cooldown = stats.cooldown * (100 - effect.cooldown_reduction) / 100;
+            stats.call_in = stats.call_in.saturating_sub(effect.call_in_reduction);
+            stats.uses = stats.uses.map(|uses| uses + effect.extra_uses);
+        }
+        stats
+    }
+}
+
+pub fn get_upgrades_path() -> PathBuf {
+    gtk::glib::user_config_dir().join("hd2helper").join("ship_upgrades.json")
+}
+
+/// Reads the owned upgrades. Names that aren't known anymore are reported
+/// instead of breaking the whole file. A missing file just means nothing was
+/// recorded yet.
+pub fn load_upgrades() -> (ShipUpgrades, Vec<String>) {
+    let mut problems = Vec::new();
+    let content = match std::fs::read_to_string(get_upgrades_path()) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound =>
+            return (ShipUpgrades::default(), problems),
+        Err(e) => {
+            problems.push(format!("Could not read ship upgrades: {e}"));
+            return (ShipUpgrades::default(), problems);
+        }
+    };
+    let names: Vec<String> = match serde_json::from_str(&content) {
+        Ok(names) => names,
+        Err(e) => {
+            problems.push(format!("Saved ship upgrades are broken: {e}"));
+            return (ShipUpgrades::default(), problems);
+        }
+    };
+    let mut upgrades = ShipUpgrades::default();
+    for name in names {
+        match ShipUpgrade::from_str(&name) {
+            Ok(upgrade) => upgrades.set(upgrade, true),
+            Err(_) => problems.push(format!("Unknown ship upgrade {name} was dropped")),
+        }
+    }
+    (upgrades, problems)
+}
+
+/// Writes the owned upgrades to the config file
+pub fn save_upgrades(upgrades: &ShipUpgrades) -> std::io::Result<()> {
+    let path = get_upgrades_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let names: Vec<String> = upgrades.owned.iter().map(|u| format!("{u:?}")).collect();
+    std::fs::write(path, serde_json::to_string_pretty(&names)?)
+}
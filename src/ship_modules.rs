@@ -0,0 +1,70 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// The ship modules page is where the user records the upgrades they bought
+// for their Super Destroyer. Every change is saved right away.
+
+use adw::prelude::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+use strum::IntoEnumIterator;
+
+use crate::db;
+use crate::gui_templates;
+use crate::ship;
+
+pub fn create_ship_modules_page() -> adw::NavigationPage {
+    let toasts = adw::ToastOverlay::new();
+    let (upgrades, problems) = ship::load_upgrades();
+    for problem in problems {
+        toasts.add_toast(adw::Toast::new(&problem));
+    }
+    let upgrades = Rc::new(RefCell::new(upgrades));
+
+    let content = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(18)
+        .margin_top(12)
+        .margin_bottom(12)
+        .build();
+    for department in db::Department::iter() {
+        let rows = gtk::ListBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .css_classes(*&["boxed-list"])
+            .build();
+        for upgrade in ship::ShipUpgrade::iter().filter(|u| u.get_department() == department) {
+            let row = adw::SwitchRow::builder()
+                .title(upgrade.to_string())
+                .subtitle(upgrade.get_description())
+                .active(upgrades.borrow().has(upgrade))
+                .build();
+            let upgrades = upgrades.clone();
+            let toasts = toasts.clone();
+            row.connect_active_notify(move |row| {
+                upgrades.borrow_mut().set(upgrade, row.is_active());
+                if let Err(e) = ship::save_upgrades(&upgrades.borrow()) {
+                    toasts.add_toast(adw::Toast::new(&format!("Could not save ship upgrades: {e}")));
+                }
+            });
+            rows.append(&row);
+        }
+        let group = adw::PreferencesGroup::builder()
+            .title(department.to_string())
+            .build();
+        group.add(&rows);
+        content.append(&group);
+    }
+    toasts.set_child(Some(&gui_templates::create_scrolled_content(&content)));
+
+    gui_templates::create_titled_page("Ship Modules", None, &toasts)
+}
@@ -0,0 +1,121 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// Sounds of the keycode trainer, like the game makes while a Stratagem is
+// entered. They are played with GTK's media support, which uses GStreamer.
+// Every sound can be replaced by a file with the same name in the sounds
+// directory of the user data directory.
+
+use gtk::prelude::*;
+use std::cell::Cell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use strum::IntoEnumIterator;
+
+#[derive(Debug, PartialEq, Eq, strum_macros::EnumIter, Copy, Clone)]
+pub enum Sound {
+    /// A right key
+    Arrow,
+    /// A whole code
+    Success,
+    /// A wrong key
+    Error,
+    /// A round of Stratagem Hero, a race or a game is through
+    RoundComplete,
+}
+
+impl Sound {
+    pub fn get_file_name(&self) -> &'static str {
+        match self {
+            Sound::Arrow => "arrow.wav",
+            Sound::Success => "success.wav",
+            Sound::Error => "error.wav",
+            Sound::RoundComplete => "round.wav",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SoundSettings {
+    /// From 0 to 1
+    pub volume: f64,
+    pub muted: bool,
+}
+
+impl Default for SoundSettings {
+    fn default() -> Self {
+        SoundSettings {
+            volume: 0.6,
+            muted: false,
+        }
+    }
+}
+
+/// Sound files put here are played instead of the ones that come with the
+/// program
+pub fn get_sounds_dir() -> PathBuf {
+    gtk::glib::user_data_dir().join("hd2helper").join("sounds")
+}
+
+/// The user's own file if there is one, otherwise the bundled one
+fn get_sound_path(sound: Sound) -> PathBuf {
+    let own = get_sounds_dir().join(sound.get_file_name());
+    if own.exists() {
+        own
+    } else {
+        PathBuf::from("assets/sounds").join(sound.get_file_name())
+    }
+}
+
+/// Plays the sounds with the current settings. A clone is just another handle
+/// to the same player.
+#[derive(Clone)]
+pub struct SoundPlayer {
+    settings: Rc<Cell<SoundSettings>>,
+    /// Every sound is loaded once and started over when it is played again
+    media: Rc<Vec<(Sound, gtk::MediaFile)>>,
+}
+
+impl SoundPlayer {
+    pub fn new(settings: SoundSettings) -> Self {
+        SoundPlayer {
+            settings: Rc::new(Cell::new(settings)),
+            media: Rc::new(Sound::iter()
+                .map(|sound| (sound, gtk::MediaFile::for_filename(get_sound_path(sound))))
+                .collect()),
+        }
+    }
+
+    pub fn play(&self, sound: Sound) {
+        let settings = self.settings.get();
+        if settings.muted {
+            return;
+        }
+        let Some((_, media)) = self.media.iter().find(|(s, _)| *s == sound) else {
+            return;
+        };
+        // A file that can't be played just stays silent, GStreamer explains
+        // why on the terminal
+        if media.error().is_some() {
+            return;
+        }
+        media.set_volume(settings.volume);
+        media.seek(0);
+        media.play();
+    }
+
+    pub fn set_settings(&self, settings: SoundSettings) {
+        self.settings.set(settings);
+    }
+}
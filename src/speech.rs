@@ -0,0 +1,118 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// Speaks text with a speech synthesizer that is installed locally. Desktops
+// use speech-dispatcher for their screen readers, espeak is the fallback.
+
+use std::cell::RefCell;
+use std::process::Child;
+use std::process::Command;
+use std::process::Stdio;
+use std::rc::Rc;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum SpeechEngine {
+    SpeechDispatcher,
+    EspeakNg,
+    Espeak,
+}
+
+impl SpeechEngine {
+    fn get_program(&self) -> &'static str {
+        match self {
+            SpeechEngine::SpeechDispatcher => "spd-say",
+            SpeechEngine::EspeakNg => "espeak-ng",
+            SpeechEngine::Espeak => "espeak",
+        }
+    }
+
+    /// The first engine that is installed, in the order of the enum
+    pub fn find() -> Option<Self> {
+        [SpeechEngine::SpeechDispatcher, SpeechEngine::EspeakNg, SpeechEngine::Espeak]
+            .into_iter()
+            .find(|engine| is_installed(engine.get_program()))
+    }
+}
+
+/// Whether the program is somewhere in PATH
+fn is_installed(program: &str) -> bool {
+    std::env::var_os("PATH").is_some_and(|paths| std::env::split_paths(&paths)
+                                         .any(|dir| dir.join(program).is_file()))
+}
+
+/// Says one thing at a time. A clone is just another handle to the same
+/// speaker.
+#[derive(Clone)]
+pub struct Speaker {
+    engine: Option<SpeechEngine>,
+    /// The synthesizer that says the last text, it may be done already
+    speaking: Rc<RefCell<Option<Child>>>,
+}
+
+impl Speaker {
+    pub fn new() -> Self {
+        Speaker {
+            engine: SpeechEngine::find(),
+            speaking: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    pub fn is_available(&self) -> bool {
+        self.engine.is_some()
+    }
+
+    /// Stops what is said right now and says the text instead
+    pub fn say(&self, text: &str) {
+        let Some(engine) = self.engine else {
+            return;
+        };
+        self.kill_synthesizer();
+        let mut command = Command::new(engine.get_program());
+        if engine == SpeechEngine::SpeechDispatcher {
+            // spd-say only hands the text to the daemon, which has to drop
+            // what it is still saying
+            command.arg("--cancel");
+        }
+        match command.arg(text).stdout(Stdio::null()).stderr(Stdio::null()).spawn() {
+            Ok(child) => {
+                self.speaking.replace(Some(child));
+            }
+            Err(e) => eprintln!("Could not run {}: {e}", engine.get_program()),
+        }
+    }
+
+    pub fn stop(&self) {
+        self.kill_synthesizer();
+        if self.engine == Some(SpeechEngine::SpeechDispatcher) {
+            // Don't wait for the daemon on the GUI thread
+            let cancel = Command::new("spd-say")
+                .arg("--cancel")
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn();
+            match cancel {
+                Ok(mut child) => {
+                    std::thread::spawn(move || child.wait());
+                }
+                Err(e) => eprintln!("Could not run spd-say: {e}"),
+            }
+        }
+    }
+
+    fn kill_synthesizer(&self) {
+        if let Some(mut child) = self.speaking.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
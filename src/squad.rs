@@ -0,0 +1,217 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// A squad is four players with a loadout each. This checks what the squad as
+// a whole can deal with and where the loadouts get in each other's way.
+
+use std::time::Duration;
+
+use strum::IntoEnumIterator;
+
+use crate::db;
+use crate::loadout;
+use crate::ship;
+
+/// How many players fit into one squad
+pub const SQUAD_SIZE: usize = 4;
+
+/// A role that only strikes cover is warned about if the squad has to wait
+/// at least this long for the first of them to come back
+const STRIKE_GAP: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Clone)]
+pub struct Player {
+    pub name: String,
+    pub loadout: loadout::Loadout,
+}
+
+#[derive(Debug, Clone)]
+pub struct Squad {
+    /// Always has `SQUAD_SIZE` players
+    pub players: Vec<Player>,
+}
+
+/// How long a strike is gone once it is used up, with the ship upgrades.
+/// Eagle Stratagems only come back with the rearm of the Eagle, the cooldown
+/// between their uses is short.
+fn get_recovery(stratagem: &db::Stratagem, upgrades: &ship::ShipUpgrades) -> Duration {
+    match stratagem.get_category() {
+        db::Category::Eagle => upgrades.get_effective_stats(&db::Stratagem::EagleRearm).cooldown,
+        _ => upgrades.get_effective_stats(stratagem).cooldown,
+    }
+}
+
+impl Squad {
+    pub fn new() -> Self {
+        Squad {
+            players: (1..=SQUAD_SIZE)
+                .map(|i| Player {
+                    name: format!("Helldiver {i}"),
+                    loadout: loadout::Loadout::new(""),
+                })
+                .collect(),
+        }
+    }
+
+    /// Every Stratagem in the squad together with the player who brings it
+    fn get_stratagems(&self) -> impl Iterator<Item = (&Player, db::Stratagem)> {
+        self.players.iter()
+            .flat_map(|p| p.loadout.stratagems.iter().map(move |s| (p, *s)))
+    }
+
+    /// Returns for each role which Stratagems of the squad fill it. A role
+    /// with an empty list is not covered at all.
+    pub fn get_coverage(&self) -> Vec<(db::Role, Vec<db::Stratagem>)> {
+        db::Role::iter()
+            .map(|role| {
+                let mut stratagems: Vec<db::Stratagem> = Vec::new();
+                for (_, stratagem) in self.get_stratagems() {
+                    if stratagem.get_roles().contains(&role) && !stratagems.contains(&stratagem) {
+                        stratagems.push(stratagem);
+                    }
+                }
+                (role, stratagems)
+            })
+            .collect()
+    }
+
+    /// Returns everything that is likely a mistake in the squad: Stratagems
+    /// that more than one player brings, players bringing more backpacks or
+    /// support weapons than they can carry, Eagle Stratagems waiting for the
+    /// same rearm, and roles that only Eagle and Orbital Stratagems cover if
+    /// they can all be on cooldown for long. Cooldowns are the ones with the
+    /// given ship upgrades.
+    pub fn get_warnings(&self, upgrades: &ship::ShipUpgrades) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        // The same Stratagem from different players
+        let mut seen: Vec<db::Stratagem> = Vec::new();
+        for (_, stratagem) in self.get_stratagems() {
+            if seen.contains(&stratagem) {
+                continue;
+            }
+            seen.push(stratagem);
+            let owners: Vec<&str> = self.get_stratagems()
+                .filter(|(_, s)| *s == stratagem)
+                .map(|(p, _)| p.name.as_str())
+                .collect();
+            if owners.len() > 1 {
+                warnings.push(format!("{stratagem} is brought by {}", owners.join(" and ")));
+            }
+        }
+
+        for player in self.players.iter() {
+            let count_slot = |uses_slot: fn(&db::Stratagem) -> bool| player.loadout.stratagems
+                .iter()
+                .filter(|s| uses_slot(s))
+                .count();
+            let backpacks = count_slot(db::Stratagem::uses_backpack_slot);
+            if backpacks > 1 {
+                warnings.push(format!("{} brings {backpacks} backpacks but can only wear one",
+                                      player.name));
+            }
+            let support_weapons = count_slot(db::Stratagem::uses_support_weapon_slot);
+            if support_weapons > 1 {
+                warnings.push(format!("{} brings {support_weapons} support weapons but can only carry one",
+                                      player.name));
+            }
+            // Every player has one Eagle, once it is out of any Stratagem
+            // all of them wait for its rearm
+            let eagles = player.loadout.stratagems.iter()
+                .filter(|s| s.get_category() == db::Category::Eagle)
+                .count();
+            if eagles > 1 {
+                let rearm = get_recovery(&db::Stratagem::EagleRearm, upgrades);
+                warnings.push(format!("{}'s {eagles} Eagle Stratagems share one rearm of {} seconds",
+                                      player.name, rearm.as_secs()));
+            }
+        }
+
+        // If a role is only covered by strikes, the squad has nothing left
+        // once all of them are used up, until the first one is back
+        for (role, stratagems) in self.get_coverage() {
+            let only_strikes = stratagems.iter().all(|s| matches!(
+                s.get_category(), db::Category::Eagle | db::Category::Orbital));
+            let gap = stratagems.iter().map(|s| get_recovery(s, upgrades)).min();
+            if let Some(gap) = gap.filter(|gap| only_strikes && *gap >= STRIKE_GAP) {
+                warnings.push(format!("{role} only comes from Eagle and Orbital Stratagems, \
+                                       the squad can be without it for {} seconds",
+                                      gap.as_secs()));
+            }
+        }
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use db::Stratagem::*;
+
+    fn squad(loadouts: &[&[db::Stratagem]]) -> Squad {
+        let mut squad = Squad::new();
+        for (player, stratagems) in squad.players.iter_mut().zip(loadouts) {
+            player.loadout.stratagems = stratagems.to_vec();
+        }
+        squad
+    }
+
+    fn upgrades(owned: &[ship::ShipUpgrade]) -> ship::ShipUpgrades {
+        ship::ShipUpgrades { owned: owned.to_vec() }
+    }
+
+    #[test]
+    fn shared_eagle_rearm() {
+        let squad = squad(&[
+            &[EAT17ExpendableAntiTank, EagleAirstrike, EagleClusterBomb, EagleNapalmStrike],
+            &[OrbitalGatlingBarrage, Resupply, EagleSmokeStrike],
+        ]);
+        assert_eq!(squad.get_warnings(&upgrades(&[])),
+                   ["Helldiver 1's 3 Eagle Stratagems share one rearm of 150 seconds"]);
+        assert_eq!(squad.get_warnings(&upgrades(&[ship::ShipUpgrade::PitCrewHazardPay])),
+                   ["Helldiver 1's 3 Eagle Stratagems share one rearm of 120 seconds"]);
+    }
+
+    #[test]
+    fn long_strike_gaps() {
+        let mixed = squad(&[
+            &[OrbitalRailcannonStrike, EagleStrafingRun],
+            &[Eagle500kgBomb, OrbitalGatlingBarrage],
+        ]);
+        let warnings = mixed.get_warnings(&upgrades(&[]));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains(&db::Role::AntiTank.to_string()));
+        assert!(warnings[0].contains("150 seconds"));
+
+        // Without the Eagle the Railcannon is all there is
+        let orbitals = squad(&[&[OrbitalRailcannonStrike], &[OrbitalGatlingBarrage]]);
+        let warnings = orbitals.get_warnings(&upgrades(&[ship::ShipUpgrade::ZeroGBreechLoading]));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("189 seconds"));
+    }
+
+    #[test]
+    fn duplicates_and_slots() {
+        let squad = squad(&[
+            &[OrbitalLaser, B1SupplyPack, LIFT850JumpPack],
+            &[OrbitalLaser],
+        ]);
+        let warnings = squad.get_warnings(&upgrades(&[]));
+        assert_eq!(warnings, [
+            "OrbitalLaser is brought by Helldiver 1 and Helldiver 2",
+            "Helldiver 1 brings 2 backpacks but can only wear one",
+            "Anti-Tank only comes from Eagle and Orbital Stratagems, \
+             the squad can be without it for 300 seconds",
+        ]);
+    }
+}
@@ -0,0 +1,257 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// The squad planner puts four loadouts next to each other and shows what the
+// squad covers and where the loadouts clash.
+
+use adw::prelude::*;
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::gui_templates;
+use crate::loadout;
+use crate::ship;
+use crate::squad;
+
+/// The widgets and state of the squad page. A clone is just another handle
+/// to the same page.
+#[derive(Clone)]
+struct SquadPlanner {
+    squad: Rc<RefCell<squad::Squad>>,
+    /// The player that Stratagems from the catalog are added to
+    active_player: Rc<Cell<usize>>,
+    /// The loadout slots of every player
+    player_slots: Vec<gtk::Box>,
+    coverage_list: gtk::ListBox,
+    warnings_list: gtk::ListBox,
+}
+
+pub fn create_squad_page() -> adw::NavigationPage {
+    let planner = SquadPlanner {
+        squad: Rc::new(RefCell::new(squad::Squad::new())),
+        active_player: Rc::new(Cell::new(0)),
+        player_slots: (0..squad::SQUAD_SIZE)
+            .map(|_| gtk::Box::builder()
+                 .spacing(12)
+                 .halign(gtk::Align::Center)
+                 .margin_top(6)
+                 .build())
+            .collect(),
+        coverage_list: gtk::ListBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .css_classes(*&["boxed-list"])
+            .build(),
+        warnings_list: gtk::ListBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .css_classes(*&["boxed-list"])
+            .build(),
+    };
+    planner.warnings_list.set_placeholder(Some(&gtk::Label::builder()
+        .label("Nothing to worry about")
+        .css_classes(*&["dim-label"])
+        .margin_top(12)
+        .margin_bottom(12)
+        .build()));
+
+    let content = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(18)
+        .margin_top(12)
+        .margin_bottom(12)
+        .build();
+
+    // Only one player at a time gets Stratagems from the catalog
+    let mut first_check: Option<gtk::CheckButton> = None;
+    for player in 0..squad::SQUAD_SIZE {
+        let check = gtk::CheckButton::builder()
+            .active(player == 0)
+            .valign(gtk::Align::Center)
+            .build();
+        check.set_group(first_check.as_ref());
+        first_check.get_or_insert(check.clone());
+        content.append(&planner.create_player_group(player, &check));
+    }
+
+    let catalog_planner = planner.clone();
+    let catalog = gui_templates::create_catalog_picker(move |stratagem| {
+        let player = catalog_planner.active_player.get();
+        catalog_planner.squad.borrow_mut().players[player].loadout.add(stratagem);
+        catalog_planner.refresh_player(player);
+    });
+    let catalog_group = adw::PreferencesGroup::builder()
+        .title("Catalog")
+        .description("Adds to the selected Helldiver")
+        .build();
+    catalog_group.add(&catalog);
+    content.append(&catalog_group);
+
+    let coverage_group = adw::PreferencesGroup::builder()
+        .title("Team Coverage")
+        .build();
+    coverage_group.add(&planner.coverage_list);
+    content.append(&coverage_group);
+
+    let warnings_group = adw::PreferencesGroup::builder()
+        .title("Warnings")
+        .build();
+    warnings_group.add(&planner.warnings_list);
+    content.append(&warnings_group);
+
+    for player in 0..squad::SQUAD_SIZE {
+        planner.refresh_player(player);
+    }
+
+    gui_templates::create_titled_page("Squad Planner", None,
+                                      &gui_templates::create_scrolled_content(&content))
+}
+
+impl SquadPlanner {
+    /// The group for one player, with their name, a way to use one of the
+    /// saved loadouts and their four slots
+    fn create_player_group(&self, player: usize, active_check: &gtk::CheckButton)
+                           -> adw::PreferencesGroup {
+        let name = self.squad.borrow().players[player].name.clone();
+        let name_entry = adw::EntryRow::builder()
+            .title("Name")
+            .text(name.as_str())
+            .build();
+        let planner = self.clone();
+        name_entry.connect_changed(move |entry| {
+            planner.squad.borrow_mut().players[player].name = entry.text().to_string();
+            planner.refresh_analysis();
+        });
+
+        let planner = self.clone();
+        active_check.connect_toggled(move |check| {
+            if check.is_active() {
+                planner.active_player.set(player);
+            }
+        });
+        let active_row = adw::ActionRow::builder()
+            .title("Add Stratagems from the catalog")
+            .activatable_widget(active_check)
+            .build();
+        active_row.add_prefix(active_check);
+
+        // The saved loadouts are read every time the menu opens, so loadouts
+        // saved in the meantime show up as well
+        let saved_menu = gtk::MenuButton::builder()
+            .label("Use Saved Loadout")
+            .valign(gtk::Align::Center)
+            .build();
+        let planner = self.clone();
+        saved_menu.set_create_popup_func(move |menu| {
+            let options = gtk::Box::new(gtk::Orientation::Vertical, 6);
+            let (saved, _) = loadout::load_loadouts();
+            if saved.is_empty() {
+                options.append(&gtk::Label::new(Some("No saved loadouts yet")));
+            }
+            for saved_loadout in saved {
+                let button = gtk::Button::builder()
+                    .label(saved_loadout.name.as_str())
+                    .css_classes(*&["flat"])
+                    .build();
+                let planner = planner.clone();
+                let menu = menu.clone();
+                button.connect_clicked(move |_| {
+                    planner.squad.borrow_mut().players[player].loadout = saved_loadout.clone();
+                    planner.refresh_player(player);
+                    menu.popdown();
+                });
+                options.append(&button);
+            }
+            menu.set_popover(Some(&gtk::Popover::builder().child(&options).build()));
+        });
+        let saved_row = adw::ActionRow::builder()
+            .title("Loadout")
+            .build();
+        saved_row.add_suffix(&saved_menu);
+
+        let rows = gtk::ListBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .css_classes(*&["boxed-list"])
+            .build();
+        rows.append(&name_entry);
+        rows.append(&active_row);
+        rows.append(&saved_row);
+
+        let group = adw::PreferencesGroup::builder()
+            .title(format!("Helldiver {}", player + 1))
+            .build();
+        group.add(&rows);
+        group.add(&self.player_slots[player]);
+        group
+    }
+
+    /// Redraws the slots of one player and the analysis of the squad
+    fn refresh_player(&self, player: usize) {
+        let slots = &self.player_slots[player];
+        while let Some(child) = slots.first_child() {
+            slots.remove(&child);
+        }
+        let stratagems = self.squad.borrow().players[player].loadout.stratagems.clone();
+        let (upgrades, _) = ship::load_upgrades();
+        for slot in 0..loadout::LOADOUT_SIZE {
+            let button = match stratagems.get(slot) {
+                Some(stratagem) => {
+                    let button = gtk::Button::builder()
+                        .child(&gui_templates::create_stratagem_icon(stratagem, 48))
+                        .tooltip_text(gui_templates::get_stratagem_tooltip(stratagem, &upgrades))
+                        .build();
+                    let planner = self.clone();
+                    button.connect_clicked(move |_| {
+                        planner.squad.borrow_mut().players[player].loadout.stratagems.remove(slot);
+                        planner.refresh_player(player);
+                    });
+                    button
+                }
+                None => gtk::Button::builder()
+                    .icon_name("list-add-symbolic")
+                    .width_request(48)
+                    .height_request(48)
+                    .sensitive(false)
+                    .build(),
+            };
+            slots.append(&button);
+        }
+        self.refresh_analysis();
+    }
+
+    /// Rebuilds the coverage and warning lists
+    fn refresh_analysis(&self) {
+        let squad = self.squad.borrow();
+
+        self.coverage_list.remove_all();
+        for (role, stratagems) in squad.get_coverage() {
+            let names: Vec<String> = stratagems.iter().map(|s| s.to_string()).collect();
+            let row = adw::ActionRow::builder()
+                .title(role.to_string())
+                .subtitle(if names.is_empty() { "Not covered".to_string() } else { names.join(", ") })
+                .build();
+            row.add_suffix(&gtk::Image::from_icon_name(
+                if stratagems.is_empty() { "dialog-warning-symbolic" } else { "emblem-ok-symbolic" }));
+            self.coverage_list.append(&row);
+        }
+
+        self.warnings_list.remove_all();
+        let (upgrades, _) = ship::load_upgrades();
+        for warning in squad.get_warnings(&upgrades) {
+            let row = adw::ActionRow::builder()
+                .title(warning)
+                .build();
+            row.add_prefix(&gtk::Image::from_icon_name("dialog-warning-symbolic"));
+            self.warnings_list.append(&row);
+        }
+    }
+}
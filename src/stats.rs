@@ -0,0 +1,516 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// Every keycode the trainer asks for is an attempt. Attempts remember how
+// long it took to start typing, how long each key took and whether the code
+// was entered correctly. They are appended to a file in the user data
+// directory, one JSON object per line.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+
+use crate::db;
+
+/// The ways the trainer can ask for keycodes
+#[derive(Debug, PartialEq, Eq, strum_macros::EnumString, strum_macros::Display,
+         strum_macros::EnumIter, Copy, Clone, Default)]
+pub enum TrainerMode {
+    /// One Stratagem after the other, without a time limit
+    #[default]
+    Practice,
+    /// Against the clock, see hero.rs
+    #[strum(to_string = "Stratagem Hero", serialize = "StratagemHero")]
+    StratagemHero,
+    /// Like practice, but the Stratagems are said instead of shown, so the
+    /// reaction includes listening to the name
+    #[strum(to_string = "Call-out", serialize = "CallOut")]
+    CallOut,
+}
+
+impl TrainerMode {
+    /// Whether the mode is played against the clock and has a high-score
+    /// table
+    pub fn is_timed(&self) -> bool {
+        match self {
+            TrainerMode::Practice | TrainerMode::CallOut => false,
+            TrainerMode::StratagemHero => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attempt {
+    pub stratagem: db::Stratagem,
+    pub mode: TrainerMode,
+    /// When the Stratagem was shown
+    pub started: SystemTime,
+    /// From showing the Stratagem to the first key
+    pub reaction: Duration,
+    /// The time between each key and the one before it, so the first entry
+    /// is the time between the first and the second key
+    pub intervals: Vec<Duration>,
+    /// From showing the Stratagem to the last key
+    pub total: Duration,
+    /// The position of the wrong key if the attempt failed
+    pub mistake_at: Option<usize>,
+    /// The wrong key if the attempt failed
+    pub wrong_key: Option<db::Code>,
+}
+
+/// How an attempt looks on disk. Durations are in milliseconds, the start is
+/// in seconds since the Unix epoch.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct AttemptFile {
+    stratagem: String,
+    #[serde(default)]
+    mode: Option<String>,
+    started: u64,
+    reaction_ms: u64,
+    intervals_ms: Vec<u64>,
+    total_ms: u64,
+    #[serde(default)]
+    mistake_at: Option<usize>,
+    #[serde(default)]
+    wrong_key: Option<String>,
+}
+
+impl Attempt {
+    pub fn is_success(&self) -> bool {
+        self.mistake_at.is_none()
+    }
+
+    /// How many keys were pressed, including a wrong one
+    pub fn get_key_count(&self) -> usize {
+        self.intervals.len() + 1
+    }
+
+    fn from_file(file: AttemptFile) -> Option<Self> {
+        Some(Attempt {
+            stratagem: db::Stratagem::from_str(&file.stratagem).ok()?,
+            mode: match file.mode {
+                Some(mode) => TrainerMode::from_str(&mode).ok()?,
+                None => TrainerMode::default(),
+            },
+            started: SystemTime::UNIX_EPOCH + Duration::from_secs(file.started),
+            reaction: Duration::from_millis(file.reaction_ms),
+            intervals: file.intervals_ms.into_iter().map(Duration::from_millis).collect(),
+            total: Duration::from_millis(file.total_ms),
+            mistake_at: file.mistake_at,
+            wrong_key: file.wrong_key.and_then(|key| db::Code::from_str(&key).ok()),
+        })
+    }
+
+    fn to_file(&self) -> AttemptFile {
+        AttemptFile {
+            stratagem: self.stratagem.to_string(),
+            mode: Some(self.mode.to_string()),
+            started: self.started.duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            reaction_ms: self.reaction.as_millis() as u64,
+            intervals_ms: self.intervals.iter().map(|i| i.as_millis() as u64).collect(),
+            total_ms: self.total.as_millis() as u64,
+            mistake_at: self.mistake_at,
+            wrong_key: self.wrong_key.map(|key| key.to_string()),
+        }
+    }
+}
+
+/// When a key was pressed, as the trainer sees it
+#[derive(Debug, Copy, Clone)]
+pub struct KeyTime {
+    /// The time of the GDK key event in milliseconds. Only the difference
+    /// between two of them means something.
+    pub event_ms: u32,
+    /// When the key handler got the key
+    pub received: Instant,
+}
+
+impl KeyTime {
+    /// A key of the event with the given time, that the key handler got just
+    /// now
+    pub fn now(event_ms: u32) -> Self {
+        KeyTime {
+            event_ms,
+            received: Instant::now(),
+        }
+    }
+}
+
+/// The time between two GDK event times. They wrap around after 49 days, a
+/// later event that seems to be earlier takes no time at all.
+pub fn get_event_delta(from: u32, to: u32) -> Duration {
+    match to.wrapping_sub(from) {
+        delta if delta > u32::MAX / 2 => Duration::ZERO,
+        delta => Duration::from_millis(delta as u64),
+    }
+}
+
+/// Times an attempt while it is being typed. The keys are timed with the
+/// times of their events, so waiting in the main loop doesn't count. The
+/// events don't tell when the Stratagem was shown though, so the reaction is
+/// taken from when the key handler got the first key.
+#[derive(Debug, Clone)]
+pub struct AttemptTimer {
+    stratagem: db::Stratagem,
+    mode: TrainerMode,
+    started: SystemTime,
+    shown_at: Instant,
+    key_times: Vec<KeyTime>,
+}
+
+impl AttemptTimer {
+    /// Starts timing a Stratagem that was just shown
+    pub fn new(stratagem: db::Stratagem, mode: TrainerMode) -> Self {
+        AttemptTimer {
+            stratagem,
+            mode,
+            started: SystemTime::now(),
+            shown_at: Instant::now(),
+            key_times: Vec::new(),
+        }
+    }
+
+    /// Records a key press
+    pub fn press(&mut self, time: KeyTime) {
+        self.key_times.push(time);
+    }
+
+    /// Ends the attempt. If it failed, `wrong_key` is the last key that was
+    /// pressed. Returns None if no key was pressed at all.
+    pub fn finish(&self, wrong_key: Option<db::Code>) -> Option<Attempt> {
+        let first = self.key_times.first()?;
+        let reaction = first.received.saturating_duration_since(self.shown_at);
+        let intervals: Vec<Duration> = self.key_times.windows(2)
+            .map(|pair| get_event_delta(pair[0].event_ms, pair[1].event_ms))
+            .collect();
+        Some(Attempt {
+            stratagem: self.stratagem,
+            mode: self.mode,
+            started: self.started,
+            reaction,
+            total: reaction + intervals.iter().sum::<Duration>(),
+            intervals,
+            mistake_at: wrong_key.map(|_| self.key_times.len() - 1),
+            wrong_key,
+        })
+    }
+}
+
+pub fn get_attempts_path() -> PathBuf {
+    gtk::glib::user_data_dir().join("hd2helper").join("attempts.jsonl")
+}
+
+/// Adds an attempt to the end of the file
+pub fn save_attempt(attempt: &Attempt) -> std::io::Result<()> {
+    let path = get_attempts_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(&attempt.to_file())?)
+}
+
+/// Reads every attempt, oldest first. Lines that can't be read anymore are
+/// skipped and counted in the returned messages, so one broken line doesn't
+/// lose the whole history.
+pub fn load_attempts() -> (Vec<Attempt>, Vec<String>) {
+    let mut problems = Vec::new();
+    let content = match std::fs::read_to_string(get_attempts_path()) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return (Vec::new(), problems),
+        Err(e) => {
+            problems.push(format!("Could not read training history: {e}"));
+            return (Vec::new(), problems);
+        }
+    };
+    let mut skipped = 0;
+    let attempts = content.lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            let attempt = serde_json::from_str(line).ok().and_then(Attempt::from_file);
+            if attempt.is_none() {
+                skipped += 1;
+            }
+            attempt
+        })
+        .collect();
+    if skipped > 0 {
+        problems.push(format!("Skipped {skipped} attempts that could not be read"));
+    }
+    (attempts, problems)
+}
+
+/// The averages of all attempts at one Stratagem
+#[derive(Debug, Clone, PartialEq)]
+pub struct StratagemSummary {
+    pub stratagem: db::Stratagem,
+    pub attempts: usize,
+    pub successes: usize,
+    /// Average time to the first key
+    pub reaction: Duration,
+    /// Average time of successful attempts
+    pub total: Option<Duration>,
+    /// Average time before each key. The first entry is the reaction time,
+    /// the others the intervals before the following keys.
+    pub key_times: Vec<Duration>,
+}
+
+/// Sums up the attempts per Stratagem, the most trained Stratagem first
+pub fn summarize(attempts: &[Attempt]) -> Vec<StratagemSummary> {
+    let mut stratagems: Vec<db::Stratagem> = Vec::new();
+    for attempt in attempts {
+        if !stratagems.contains(&attempt.stratagem) {
+            stratagems.push(attempt.stratagem);
+        }
+    }
+    let mut summaries: Vec<StratagemSummary> = stratagems.into_iter()
+        .map(|stratagem| {
+            let own: Vec<&Attempt> = attempts.iter().filter(|a| a.stratagem == stratagem).collect();
+            let successful: Vec<Duration> = own.iter()
+                .filter(|a| a.is_success())
+                .map(|a| a.total)
+                .collect();
+
+            // Every position averages over the attempts that got that far
+            let longest = own.iter().map(|a| a.get_key_count()).max().unwrap_or(0);
+            let key_times = (0..longest)
+                .map(|position| average(own.iter().filter_map(|a| match position {
+                    0 => Some(a.reaction),
+                    _ => a.intervals.get(position - 1).copied(),
+                })).unwrap_or_default())
+                .collect();
+
+            StratagemSummary {
+                stratagem,
+                attempts: own.len(),
+                successes: successful.len(),
+                reaction: average(own.iter().map(|a| a.reaction)).unwrap_or_default(),
+                total: average(successful.into_iter()),
+                key_times,
+            }
+        })
+        .collect();
+    summaries.sort_by_key(|s| std::cmp::Reverse(s.attempts));
+    summaries
+}
+
+/// The average of the durations, or None if there are none
+pub fn average(durations: impl Iterator<Item = Duration>) -> Option<Duration> {
+    let (sum, count) = durations.fold((Duration::ZERO, 0), |(sum, count), d| (sum + d, count + 1));
+    (count > 0).then(|| sum / count)
+}
+
+/// How often one key of a keycode went wrong
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PositionMistakes {
+    /// The right key at this position
+    pub expected: db::Code,
+    /// How many attempts got as far as this key
+    pub reached: usize,
+    pub mistakes: usize,
+    /// The keys that were pressed instead, the most common one first
+    pub wrong_keys: Vec<(db::Code, usize)>,
+}
+
+impl PositionMistakes {
+    /// The share of attempts that failed at this key, from 0 to 1
+    pub fn get_rate(&self) -> f64 {
+        match self.reached {
+            0 => 0.0,
+            reached => self.mistakes as f64 / reached as f64,
+        }
+    }
+}
+
+/// Returns for every key of the Stratagem's keycode how often it was entered
+/// wrong and what was pressed instead
+pub fn analyze_mistakes(attempts: &[Attempt], stratagem: db::Stratagem) -> Vec<PositionMistakes> {
+    let own: Vec<&Attempt> = attempts.iter().filter(|a| a.stratagem == stratagem).collect();
+    stratagem.get_keycode()
+        .into_iter()
+        .enumerate()
+        .map(|(position, expected)| {
+            let failed: Vec<&&Attempt> = own.iter()
+                .filter(|a| a.mistake_at == Some(position))
+                .collect();
+            let mut wrong_keys: Vec<(db::Code, usize)> = Vec::new();
+            for key in failed.iter().filter_map(|a| a.wrong_key) {
+                match wrong_keys.iter_mut().find(|(wrong, _)| *wrong == key) {
+                    Some((_, count)) => *count += 1,
+                    None => wrong_keys.push((key, 1)),
+                }
+            }
+            wrong_keys.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+            PositionMistakes {
+                expected,
+                reached: own.iter().filter(|a| a.get_key_count() > position).count(),
+                mistakes: failed.len(),
+                wrong_keys,
+            }
+        })
+        .collect()
+}
+
+/// The mistake made most often on a Stratagem, as the position, the right
+/// key and the key that is pressed instead
+pub fn get_typical_mistake(mistakes: &[PositionMistakes]) -> Option<(usize, db::Code, db::Code)> {
+    mistakes.iter()
+        .enumerate()
+        .filter_map(|(position, m)| m.wrong_keys.first()
+                    .map(|(wrong, count)| (position, m.expected, *wrong, *count)))
+        .max_by_key(|(_, _, _, count)| *count)
+        .map(|(position, expected, wrong, _)| (position, expected, wrong))
+}
+
+/// The fastest correct attempt at every Stratagem that was entered correctly
+/// at least once
+pub fn get_personal_bests(attempts: &[Attempt]) -> Vec<(db::Stratagem, Duration)> {
+    let mut bests: Vec<(db::Stratagem, Duration)> = Vec::new();
+    for attempt in attempts.iter().filter(|a| a.is_success()) {
+        match bests.iter_mut().find(|(stratagem, _)| *stratagem == attempt.stratagem) {
+            Some((_, best)) => *best = (*best).min(attempt.total),
+            None => bests.push((attempt.stratagem, attempt.total)),
+        }
+    }
+    bests
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use db::Stratagem::*;
+
+    /// An attempt that ends with a wrong key if one is given
+    fn attempt(stratagem: db::Stratagem, reaction_ms: u64, intervals_ms: &[u64],
+               wrong_key: Option<db::Code>) -> Attempt {
+        let reaction = Duration::from_millis(reaction_ms);
+        let intervals: Vec<Duration> = intervals_ms.iter().copied().map(Duration::from_millis).collect();
+        Attempt {
+            stratagem,
+            mode: TrainerMode::Practice,
+            started: SystemTime::UNIX_EPOCH,
+            reaction,
+            total: reaction + intervals.iter().sum::<Duration>(),
+            mistake_at: wrong_key.map(|_| intervals.len()),
+            intervals,
+            wrong_key,
+        }
+    }
+
+    #[test]
+    fn summaries() {
+        let attempts = [
+            attempt(Resupply, 300, &[100, 100, 100], None),
+            attempt(Reinforce, 200, &[100, 100, 100, 100], None),
+            attempt(Resupply, 500, &[200, 100, 100], None),
+            attempt(Resupply, 400, &[300], Some(db::Code::Up)),
+        ];
+        let summaries = summarize(&attempts);
+        assert_eq!(summaries.len(), 2);
+
+        let resupply = &summaries[0];
+        assert_eq!(resupply.stratagem, Resupply);
+        assert_eq!((resupply.attempts, resupply.successes), (3, 2));
+        assert_eq!(resupply.reaction, Duration::from_millis(400));
+        assert_eq!(resupply.total, Some(Duration::from_millis(750)));
+        let key_times: Vec<u128> = resupply.key_times.iter().map(Duration::as_millis).collect();
+        assert_eq!(key_times, [400, 200, 100, 100]);
+
+        assert_eq!(summaries[1].stratagem, Reinforce);
+        assert_eq!(summaries[1].total, Some(Duration::from_millis(600)));
+        assert!(summarize(&[]).is_empty());
+    }
+
+    #[test]
+    fn failed_attempts_have_no_total() {
+        let summaries = summarize(&[attempt(Resupply, 400, &[], Some(db::Code::Left))]);
+        assert_eq!(summaries[0].successes, 0);
+        assert_eq!(summaries[0].total, None);
+    }
+
+    #[test]
+    fn mistakes_per_position() {
+        use db::Code::*;
+        // Resupply is ↓ ↓ ↑ →
+        let attempts = [
+            attempt(Resupply, 300, &[100], Some(Up)),
+            attempt(Resupply, 300, &[100], Some(Left)),
+            attempt(Reinforce, 300, &[100], Some(Left)),
+            attempt(Resupply, 300, &[100], Some(Up)),
+            attempt(Resupply, 300, &[100, 100, 100], Some(Left)),
+            attempt(Resupply, 300, &[100, 100, 100], None),
+        ];
+        let mistakes = analyze_mistakes(&attempts, Resupply);
+        let expected: Vec<db::Code> = mistakes.iter().map(|m| m.expected).collect();
+        assert_eq!(expected, Resupply.get_keycode());
+        let reached: Vec<usize> = mistakes.iter().map(|m| m.reached).collect();
+        assert_eq!(reached, [5, 5, 2, 2]);
+        let failed: Vec<usize> = mistakes.iter().map(|m| m.mistakes).collect();
+        assert_eq!(failed, [0, 3, 0, 1]);
+        assert_eq!(mistakes[1].wrong_keys, [(Up, 2), (Left, 1)]);
+        assert_eq!(mistakes[1].get_rate(), 0.6);
+        assert_eq!(mistakes[3].wrong_keys, [(Left, 1)]);
+
+        assert_eq!(get_typical_mistake(&mistakes), Some((1, Down, Up)));
+    }
+
+    #[test]
+    fn no_mistakes() {
+        let mistakes = analyze_mistakes(&[], Resupply);
+        assert_eq!(mistakes.len(), Resupply.get_keycode().len());
+        assert!(mistakes.iter().all(|m| m.reached == 0 && m.get_rate() == 0.0));
+        assert_eq!(get_typical_mistake(&mistakes), None);
+
+        let clean = [attempt(Resupply, 300, &[100, 100, 100], None)];
+        assert_eq!(get_typical_mistake(&analyze_mistakes(&clean, Resupply)), None);
+    }
+
+    #[test]
+    fn keys_before_the_stratagem_take_no_time() {
+        let mut timer = AttemptTimer::new(Resupply, TrainerMode::Practice);
+        let early = timer.shown_at.checked_sub(Duration::from_millis(20)).unwrap();
+        timer.press(KeyTime{event_ms: 1000, received: early});
+        timer.press(KeyTime{event_ms: 1300, received: timer.shown_at});
+        let attempt = timer.finish(None).unwrap();
+        assert_eq!(attempt.reaction, Duration::ZERO);
+        assert_eq!(attempt.intervals, [Duration::from_millis(300)]);
+        assert_eq!(attempt.total, Duration::from_millis(300));
+    }
+
+    #[test]
+    fn keys_are_timed_by_their_events() {
+        let mut timer = AttemptTimer::new(Resupply, TrainerMode::Practice);
+        let received = timer.shown_at + Duration::from_millis(400);
+        // The handler got the keys late and all at once
+        for event_ms in [u32::MAX - 50, 49, 149] {
+            timer.press(KeyTime{event_ms, received});
+        }
+        let attempt = timer.finish(Some(db::Code::Up)).unwrap();
+        assert_eq!(attempt.reaction, Duration::from_millis(400));
+        assert_eq!(attempt.intervals, [Duration::from_millis(100), Duration::from_millis(100)]);
+        assert_eq!(attempt.total, Duration::from_millis(600));
+        assert_eq!(attempt.mistake_at, Some(2));
+    }
+
+    #[test]
+    fn event_deltas() {
+        assert_eq!(get_event_delta(100, 350), Duration::from_millis(250));
+        assert_eq!(get_event_delta(u32::MAX, 9), Duration::from_millis(10));
+        assert_eq!(get_event_delta(350, 100), Duration::ZERO);
+    }
+}
@@ -0,0 +1,156 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// The statistics page shows how the training went so far: how fast each
+// Stratagem is entered, before which key of its code there is hesitation and
+// which keys go wrong.
+
+use adw::prelude::*;
+
+use crate::gui_templates;
+use crate::stats;
+
+pub fn create_stats_page() -> adw::NavigationPage {
+    let toasts = adw::ToastOverlay::new();
+    let overview_list = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .css_classes(*&["boxed-list"])
+        .build();
+    let stratagem_list = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .css_classes(*&["boxed-list"])
+        .build();
+    stratagem_list.set_placeholder(Some(&gtk::Label::builder()
+        .label("Train some Stratagems first")
+        .css_classes(*&["dim-label"])
+        .margin_top(12)
+        .margin_bottom(12)
+        .build()));
+
+    let overview_group = adw::PreferencesGroup::builder()
+        .title("Overview")
+        .build();
+    overview_group.add(&overview_list);
+    let stratagem_group = adw::PreferencesGroup::builder()
+        .title("Stratagems")
+        .description("The time before each key of the code, the slowest one is highlighted")
+        .build();
+    stratagem_group.add(&stratagem_list);
+
+    let content = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(18)
+        .margin_top(12)
+        .margin_bottom(12)
+        .build();
+    content.append(&overview_group);
+    content.append(&stratagem_group);
+    toasts.set_child(Some(&gui_templates::create_scrolled_content(&content)));
+
+    // The trainer adds attempts all the time, so read them every time the
+    // page is shown
+    let map_toasts = toasts.clone();
+    content.connect_map(move |_| {
+        let (attempts, problems) = stats::load_attempts();
+        for problem in problems {
+            map_toasts.add_toast(adw::Toast::new(&problem));
+        }
+        fill_overview(&overview_list, &attempts);
+        fill_stratagems(&stratagem_list, &attempts);
+    });
+
+    gui_templates::create_titled_page("Statistics", None, &toasts)
+}
+
+/// Shows the numbers over all attempts
+fn fill_overview(list: &gtk::ListBox, attempts: &[stats::Attempt]) {
+    list.remove_all();
+    let successes = attempts.iter().filter(|a| a.is_success()).count();
+    let accuracy = match attempts.len() {
+        0 => "-".to_string(),
+        count => format!("{}%", successes * 100 / count),
+    };
+    let format_average = |average: Option<std::time::Duration>|
+        average.map_or("-".to_string(), gui_templates::format_short_duration);
+    let rows = [
+        ("Attempts", attempts.len().to_string()),
+        ("Entered correctly", accuracy),
+        ("Average reaction", format_average(stats::average(attempts.iter().map(|a| a.reaction)))),
+        ("Average time per code", format_average(stats::average(
+            attempts.iter().filter(|a| a.is_success()).map(|a| a.total)))),
+    ];
+    for (title, value) in rows {
+        let row = adw::ActionRow::builder()
+            .title(title)
+            .build();
+        row.add_suffix(&gtk::Label::builder()
+                       .label(value)
+                       .css_classes(*&["numeric"])
+                       .build());
+        list.append(&row);
+    }
+}
+
+/// One row per trained Stratagem, with the time before each key below it
+fn fill_stratagems(list: &gtk::ListBox, attempts: &[stats::Attempt]) {
+    list.remove_all();
+    for summary in stats::summarize(attempts) {
+        let mut subtitle = format!("{} attempts · {}% correct · Reaction {}",
+                                   summary.attempts,
+                                   summary.successes * 100 / summary.attempts,
+                                   gui_templates::format_short_duration(summary.reaction));
+        if let Some(total) = summary.total {
+            subtitle += &format!(" · Done in {}", gui_templates::format_short_duration(total));
+        }
+        let row = adw::ExpanderRow::builder()
+            .title(summary.stratagem.to_string())
+            .subtitle(subtitle)
+            .build();
+        row.add_prefix(&gui_templates::create_stratagem_icon(&summary.stratagem, 40));
+
+        let mistakes = stats::analyze_mistakes(attempts, summary.stratagem);
+        let mistake_row = adw::ActionRow::builder()
+            .title("Mistakes")
+            .subtitle(gui_templates::describe_typical_mistake(&mistakes)
+                      .unwrap_or("No mistakes so far".to_string()))
+            .build();
+        mistake_row.add_suffix(&gui_templates::create_mistake_heatmap(&mistakes));
+        row.add_row(&mistake_row);
+
+        // The reaction is left out, waiting for the next Stratagem is
+        // something else than hesitating in the middle of a code
+        let slowest = summary.key_times.iter()
+            .enumerate()
+            .skip(1)
+            .max_by_key(|(_, time)| **time)
+            .map(|(position, _)| position);
+        let keycode = summary.stratagem.get_keycode();
+        for (position, time) in summary.key_times.iter().enumerate() {
+            let arrow = keycode.get(position).map_or("", gui_templates::get_arrow);
+            let key_row = adw::ActionRow::builder()
+                .title(format!("Key {} {arrow}", position + 1))
+                .subtitle(if position == 0 { "Reaction" } else { "After the previous key" })
+                .build();
+            let time_label = gtk::Label::builder()
+                .label(gui_templates::format_short_duration(*time))
+                .css_classes(*&["numeric"])
+                .build();
+            if slowest == Some(position) {
+                time_label.add_css_class("warning");
+            }
+            key_row.add_suffix(&time_label);
+            row.add_row(&key_row);
+        }
+        list.append(&row);
+    }
+}
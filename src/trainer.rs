@@ -0,0 +1,1461 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// The keycode trainer shows a random Stratagem and checks the keys pressed
+// against its keycode.
+
+use adw::prelude::*;
+use gtk::glib;
+use strum::IntoEnumIterator;
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::accessibility;
+use crate::arrow_theme::ArrowTheme;
+use crate::db;
+use crate::ghost;
+use crate::gui_templates;
+use crate::hero;
+use crate::highscores_page;
+use crate::loadout;
+use crate::picker;
+use crate::race;
+use crate::replay;
+use crate::session;
+use crate::session_summary;
+use crate::settings;
+use crate::sound;
+use crate::speech;
+use crate::stats;
+use crate::versus;
+
+/// Everything the keycode trainer needs to show and check a Stratagem. All
+/// fields are shared, so a clone is just another handle to the same trainer.
+#[derive(Clone)]
+pub struct Trainer {
+    pub current_stratagem: Rc<Cell<db::Stratagem>>,
+    /// The part of the keycode that still has to be entered
+    pub input_buffer: Rc<RefCell<Vec<db::Code>>>,
+    pub filter: Rc<Cell<db::StratagemFilter>>,
+    /// If set, only the Stratagems of this loadout are trained
+    pub loadout: Rc<RefCell<Option<loadout::Loadout>>>,
+    /// Picks the Stratagems outside of Stratagem Hero, which has its own
+    pub picker: Rc<RefCell<picker::StratagemPicker>>,
+    /// Whether the seed of the picker was entered or shared instead of rolled.
+    /// Only then does the next session keep it.
+    seed_chosen: Rc<Cell<bool>>,
+    /// Times the Stratagem that is shown right now
+    pub timer: Rc<RefCell<stats::AttemptTimer>>,
+    /// The running practice session, if one was started
+    pub session: Rc<RefCell<Option<session::Session>>>,
+    /// The running game of Stratagem Hero, if one was started
+    pub hero: Rc<RefCell<Option<hero::HeroGame>>>,
+    /// The replay that is played back on the trainer right now, if any
+    pub player: Rc<RefCell<Option<replay::ReplayPlayer>>>,
+    /// The earlier run that is raced right now, if any
+    pub ghost: Rc<RefCell<Option<ghost::Ghost>>>,
+    /// The race against others on the network, until the player is through
+    pub lan_race: Rc<RefCell<Option<race::RaceProgress>>>,
+    /// The game of two players on one keyboard, if one runs
+    pub versus: Rc<RefCell<Option<versus::VersusGame>>>,
+    /// The settings of the whole program, the trainer follows their changes
+    pub settings: settings::SettingsStore,
+    pub sounds: sound::SoundPlayer,
+    pub speaker: speech::Speaker,
+    /// Whether Stratagems are called out instead of shown
+    pub call_out: Rc<Cell<bool>>,
+    /// What is said before the next Stratagem is called out, like how the
+    /// last attempt went
+    announcement: Rc<RefCell<String>>,
+    /// The fastest correct attempt at every Stratagem so far
+    pub personal_bests: Rc<RefCell<Vec<(db::Stratagem, Duration)>>>,
+    pub prompt: gtk::Label,
+    pub picture: gtk::Picture,
+    pub arrows: gtk::Label,
+    /// How the last attempt went
+    pub last_attempt: gtk::Label,
+    /// How far the ghost is, only visible during a race
+    pub ghost_arrows: gtk::Label,
+    /// What runs right now, above the Stratagem
+    pub status: gtk::Box,
+    /// The clock and score of Stratagem Hero, only visible during a game
+    hero_status: gtk::Box,
+    hero_clock: gtk::ProgressBar,
+    hero_score: gtk::Label,
+    /// Everyone's position in a race on the network
+    race_board: gtk::Label,
+    /// Both players' scores and arrows, only visible during a versus game
+    versus_status: gtk::Box,
+    /// The score and arrows of each player
+    versus_players: Vec<(versus::Side, gtk::Label, gtk::Label)>,
+}
+
+/// How often the clock of Stratagem Hero is updated
+const HERO_TICK: Duration = Duration::from_millis(50);
+/// How often a replay that is played back looks for new events
+const REPLAY_TICK: Duration = Duration::from_millis(16);
+
+impl Trainer {
+    /// The trainer asks for Stratagems in the order the picker gives them
+    pub fn new(mut picker: picker::StratagemPicker, settings: settings::SettingsStore) -> Self {
+        let filter = settings.get().trainer.apply_to(db::StratagemFilter::default());
+        let stratagem = picker.pick(&filter.get_pool());
+        let keycode = filter.get_keycode(&stratagem);
+
+        let prompt = gtk::Label::new(Some(
+            format!("Enter Keycode for {stratagem}").as_str())
+        );
+
+        // Graphical representation of the stratagem...
+        let stratagem_scaled = gtk::gdk_pixbuf::Pixbuf::from_file_at_scale(
+            stratagem.get_image_path(), 3840, 2160, true).unwrap();
+        let picture = gtk::Picture::for_paintable(&gtk::gdk::Texture::for_pixbuf(&stratagem_scaled));
+        picture.set_can_shrink(true);
+        picture.set_alternative_text(Some(&stratagem.to_string()));
+
+        // ... and the keycode
+        let arrows = gtk::Label::new(None);
+        arrows.add_css_class("arrows");
+        gui_templates::show_keycode(&arrows, &keycode, &[]);
+
+        let last_attempt = gtk::Label::builder()
+            .css_classes(*&["dim-label", "numeric"])
+            .margin_bottom(12)
+            .build();
+
+        let ghost_arrows = gtk::Label::builder()
+            .css_classes(*&["ghost-arrows"])
+            .visible(false)
+            .build();
+
+        let hero_clock = gtk::ProgressBar::builder()
+            .hexpand(true)
+            .valign(gtk::Align::Center)
+            .build();
+        let hero_score = gtk::Label::builder()
+            .css_classes(*&["numeric", "heading"])
+            .build();
+        let hero_status = gtk::Box::builder()
+            .spacing(12)
+            .margin_start(15)
+            .margin_end(15)
+            .visible(false)
+            .build();
+        hero_status.append(&hero_clock);
+        hero_status.append(&hero_score);
+
+        let race_board = gtk::Label::builder()
+            .css_classes(*&["numeric"])
+            .wrap(true)
+            .visible(false)
+            .build();
+        let status = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        status.append(&hero_status);
+        status.append(&race_board);
+
+        let versus_status = gtk::Box::builder()
+            .spacing(24)
+            .homogeneous(true)
+            .margin_start(15)
+            .margin_end(15)
+            .visible(false)
+            .build();
+        let versus_players = versus::Side::iter()
+            .map(|side| {
+                let score = gtk::Label::builder()
+                    .css_classes(*&["heading", "numeric"])
+                    .build();
+                let arrows = gtk::Label::builder()
+                    .css_classes(*&["versus-arrows"])
+                    .build();
+                let column = gtk::Box::new(gtk::Orientation::Vertical, 6);
+                column.append(&score);
+                column.append(&arrows);
+                versus_status.append(&column);
+                (side, score, arrows)
+            })
+            .collect();
+        status.append(&versus_status);
+
+        let (history, _) = stats::load_attempts();
+        let sounds = sound::SoundPlayer::new(settings.get().audio);
+
+        let trainer = Trainer {
+            current_stratagem: Rc::new(Cell::new(stratagem)),
+            input_buffer: Rc::new(RefCell::new(keycode)),
+            filter: Rc::new(Cell::new(filter)),
+            loadout: Rc::new(RefCell::new(None)),
+            picker: Rc::new(RefCell::new(picker)),
+            timer: Rc::new(RefCell::new(
+                stats::AttemptTimer::new(stratagem, stats::TrainerMode::Practice))),
+            seed_chosen: Rc::new(Cell::new(false)),
+            session: Rc::new(RefCell::new(None)),
+            hero: Rc::new(RefCell::new(None)),
+            player: Rc::new(RefCell::new(None)),
+            ghost: Rc::new(RefCell::new(None)),
+            lan_race: Rc::new(RefCell::new(None)),
+            versus: Rc::new(RefCell::new(None)),
+            settings,
+            sounds,
+            speaker: speech::Speaker::new(),
+            call_out: Rc::new(Cell::new(false)),
+            announcement: Rc::new(RefCell::new(String::new())),
+            personal_bests: Rc::new(RefCell::new(stats::get_personal_bests(&history))),
+            prompt,
+            picture,
+            arrows,
+            last_attempt,
+            ghost_arrows,
+            status,
+            hero_status,
+            hero_clock,
+            hero_score,
+            race_board,
+            versus_status,
+            versus_players,
+        };
+        let listener = trainer.clone();
+        trainer.settings.connect_changed(move |settings| listener.apply_settings(settings));
+        if trainer.settings.get().trainer.call_out {
+            trainer.enable_saved_call_out();
+        }
+        trainer
+    }
+
+    /// Follows the settings that were changed somewhere else
+    fn apply_settings(&self, settings: &settings::Settings) {
+        self.sounds.set_settings(settings.audio);
+        self.filter.set(settings.trainer.apply_to(self.filter.get()));
+        if settings.trainer.call_out != self.call_out.get() {
+            match settings.trainer.call_out {
+                true => self.enable_saved_call_out(),
+                false => {
+                    self.set_call_out(false);
+                }
+            }
+        }
+        if settings.appearance != accessibility::get_settings() {
+            accessibility::apply(settings.appearance, &self.arrows);
+            self.redraw_keycode();
+        }
+    }
+
+    /// Calls out Stratagems like the settings want it, if that is possible
+    /// at all
+    fn enable_saved_call_out(&self) {
+        if !self.set_call_out(true) {
+            self.settings.update(|settings| settings.trainer.call_out = false);
+            self.tell("Install speech-dispatcher or espeak-ng to hear the Stratagems");
+        }
+    }
+
+    /// Returns the Stratagems the trainer currently picks from. A race on
+    /// the network uses the host's, so every player gets the same ones, and
+    /// Stratagem Hero always the default ones, so high scores compare.
+    pub fn get_pool(&self) -> Vec<db::Stratagem> {
+        if let Some(race) = self.lan_race.borrow().as_ref() {
+            if !race.start.pool.is_empty() {
+                return race.start.pool.clone();
+            }
+        }
+        if self.hero.borrow().is_some() {
+            return db::StratagemFilter::default().get_pool();
+        }
+        match self.loadout.borrow().as_ref() {
+            Some(loadout) if !loadout.stratagems.is_empty() => loadout.stratagems.clone(),
+            _ => self.filter.get().get_pool(),
+        }
+    }
+
+    /// Only train the given loadout from now on, or everything the filter
+    /// allows if it is None. A new Stratagem is shown right away.
+    pub fn train_loadout(&self, loadout: Option<loadout::Loadout>) {
+        self.loadout.replace(loadout);
+        self.refresh_stratagem();
+    }
+
+    /// Starts the order of Stratagems over from the given seed. With the same
+    /// Stratagems to pick from, everyone using the seed gets the same order.
+    pub fn restart_with_seed(&self, seed: u64) {
+        self.picker.replace(picker::StratagemPicker::new(seed));
+        self.seed_chosen.set(false);
+        self.refresh_stratagem();
+    }
+
+    /// The seed code of the current order of Stratagems, to share it
+    pub fn get_seed_code(&self) -> picker::SeedCode {
+        let pool = match self.loadout.borrow().as_ref() {
+            Some(loadout) if !loadout.stratagems.is_empty() =>
+                picker::SharedPool::Stratagems(loadout.stratagems.clone()),
+            _ => picker::SharedPool::Filter(self.filter.get()),
+        };
+        picker::SeedCode {
+            seed: self.picker.borrow().get_seed(),
+            pool,
+        }
+    }
+
+    /// Picks from the Stratagems of a shared seed code and starts over from
+    /// its seed. The kinds of Stratagems of the code are saved like the
+    /// filter menu does, the Stratagems of a loadout are trained like one.
+    pub fn play_seed_code(&self, code: picker::SeedCode) {
+        match code.pool {
+            picker::SharedPool::Filter(filter) => {
+                self.settings.update(|settings| {
+                    settings.trainer.mission_only = filter.mission_only;
+                    settings.trainer.warbond = filter.warbond;
+                    settings.trainer.removed = filter.removed;
+                });
+                // The patch is not saved, only the kinds of Stratagems are
+                self.filter.set(filter);
+                self.loadout.replace(None);
+            }
+            picker::SharedPool::Stratagems(stratagems) => {
+                self.loadout.replace(Some(loadout::Loadout {
+                    stratagems,
+                    ..loadout::Loadout::new("Shared seed")
+                }));
+            }
+        }
+        self.restart_with_seed(code.seed);
+        self.seed_chosen.set(true);
+    }
+
+    /// This refreshes the Label, picture, Arrows and Stratagem when the UI needs
+    /// to be refreshed
+    pub fn refresh_stratagem(&self) {
+        let pool = self.get_pool();
+        let stratagem = match self.hero.borrow_mut().as_mut() {
+            Some(game) => game.pick_stratagem(&pool),
+            None => self.picker.borrow_mut().pick(&pool),
+        };
+        self.current_stratagem.set(stratagem);
+        self.show_current_stratagem();
+    }
+
+    /// Shows the current Stratagem again from its first key
+    pub fn show_current_stratagem(&self) {
+        let stratagem = self.current_stratagem.get();
+        self.input_buffer.replace(self.filter.get().get_keycode(&stratagem));
+        if self.call_out.get() {
+            self.call_out_stratagem(stratagem);
+        } else {
+            self.display_stratagem(stratagem);
+            self.prompt.announce(&self.prompt.text(), gtk::AccessibleAnnouncementPriority::Low);
+        }
+
+        // An attempt that was skipped halfway is not counted
+        self.timer.replace(stats::AttemptTimer::new(stratagem, self.get_mode()));
+        self.record(|recorder| recorder.record_shown(stratagem));
+    }
+
+    /// Shows the picture, name and keycode of a Stratagem, nothing else
+    fn display_stratagem(&self, stratagem: db::Stratagem) {
+        self.picture.set_visible(true);
+        let stratagem_scaled = gtk::gdk_pixbuf::Pixbuf::from_file_at_scale(
+            stratagem.get_image_path(), 3840, 2160, true).unwrap();
+        self.picture.set_paintable(Some(&gtk::gdk::Texture::for_pixbuf(&stratagem_scaled)));
+        self.picture.set_alternative_text(Some(&stratagem.to_string()));
+        self.prompt.set_text(format!("Enter Keycode for {stratagem}").as_str());
+        let keycode = self.filter.get().get_keycode(&stratagem);
+        gui_templates::show_keycode(&self.arrows, &keycode, &[]);
+    }
+
+    /// Shows a message under the Stratagem, screen readers read it out
+    fn tell(&self, message: &str) {
+        self.last_attempt.set_text(message);
+        self.last_attempt.announce(message, gtk::AccessibleAnnouncementPriority::Medium);
+    }
+
+    /// Says the name of a Stratagem, after how the last attempt went,
+    /// instead of showing anything that gives the code away
+    fn call_out_stratagem(&self, stratagem: db::Stratagem) {
+        self.picture.set_visible(false);
+        gui_templates::show_keycode(&self.arrows, &[], &[]);
+        self.prompt.set_text("Listen for the Stratagem");
+        let announcement = self.announcement.take();
+        self.speaker.say(format!("{announcement} {stratagem}").trim());
+    }
+
+    /// Turns calling out Stratagems on or off and starts the current one
+    /// over. Returns false if there is no speech synthesizer to call them out.
+    pub fn set_call_out(&self, call_out: bool) -> bool {
+        if call_out && !self.speaker.is_available() {
+            return false;
+        }
+        self.call_out.set(call_out);
+        self.announcement.take();
+        if !call_out {
+            self.speaker.stop();
+        }
+        self.show_current_stratagem();
+        true
+    }
+
+    /// Adds to the recordings of the running session and game
+    fn record(&self, event: impl Fn(&mut replay::ReplayRecorder)) {
+        if let Some(session) = self.session.borrow_mut().as_mut() {
+            if !session.paused {
+                event(&mut session.recorder);
+            }
+        }
+        if let Some(game) = self.hero.borrow_mut().as_mut() {
+            event(&mut game.recorder);
+        }
+    }
+
+    /// Whether a replay is played back right now, the trainer doesn't take
+    /// keys then
+    pub fn is_replaying(&self) -> bool {
+        self.player.borrow().is_some()
+    }
+
+    /// Plays a replay back on the trainer. A running game of Stratagem Hero
+    /// is given up for it.
+    pub fn play_replay(&self, replay: replay::Replay, speed: f64) {
+        self.finish_race();
+        self.lan_race.take();
+        self.stop_versus();
+        if self.hero.take().is_some() {
+            self.hero_status.set_visible(false);
+        }
+        let player = replay::ReplayPlayer::new(replay, speed);
+        let started = player.replay.started;
+        self.player.replace(Some(player));
+        self.last_attempt.remove_css_class("success");
+
+        let trainer = self.clone();
+        // The keys of the Stratagem that is shown, including a wrong one
+        let mut pressed: Vec<db::Code> = Vec::new();
+        glib::timeout_add_local(REPLAY_TICK, move || {
+            let now = Instant::now();
+            let (events, position, duration, speed, done) =
+                match trainer.player.borrow_mut().as_mut() {
+                    // Another replay was started in the meantime
+                    Some(player) if player.replay.started == started => (
+                        player.advance(now),
+                        player.get_position(now),
+                        player.replay.get_duration(),
+                        player.speed,
+                        player.is_done(),
+                    ),
+                    _ => return glib::ControlFlow::Break,
+                };
+            for event in events {
+                match event {
+                    replay::ReplayEvent::Shown{stratagem, ..} => {
+                        trainer.current_stratagem.set(stratagem);
+                        trainer.display_stratagem(stratagem);
+                        pressed.clear();
+                    }
+                    replay::ReplayEvent::Key{code, ..} => {
+                        pressed.push(code);
+                        let keycode = trainer.filter.get()
+                            .get_keycode(&trainer.current_stratagem.get());
+                        gui_templates::show_keycode(&trainer.arrows, &keycode, &pressed);
+                    }
+                }
+            }
+            if done {
+                trainer.stop_replay();
+                return glib::ControlFlow::Break;
+            }
+            trainer.last_attempt.set_text(&format!(
+                "Replay {} of {} at {speed}× · Escape stops it",
+                gui_templates::format_duration(position.min(duration)),
+                gui_templates::format_duration(duration)));
+            glib::ControlFlow::Continue
+        });
+    }
+
+    /// Ends the replay and goes back to training
+    pub fn stop_replay(&self) {
+        if self.player.take().is_some() {
+            self.last_attempt.set_text("");
+            self.refresh_stratagem();
+        }
+    }
+
+    /// Races the run of a replay. The Stratagems are picked with the seed
+    /// code of the replay like a shared one, a replay of Stratagem Hero
+    /// starts a game with its difficulty.
+    pub fn race_ghost(&self, replay: replay::Replay) {
+        self.player.take();
+        self.finish_race();
+        self.lan_race.take();
+        self.stop_versus();
+        match replay.difficulty {
+            Some(difficulty) => self.start_hero(difficulty, replay.seed),
+            None => self.play_seed_code(replay.get_seed_code()),
+        }
+        let ghost = ghost::Ghost::new(replay);
+        let started = ghost.started;
+        self.ghost.replace(Some(ghost));
+        self.ghost_arrows.set_visible(true);
+        self.redraw_ghost();
+
+        let trainer = self.clone();
+        glib::timeout_add_local(REPLAY_TICK, move || {
+            let filter = trainer.filter.get();
+            let done = match trainer.ghost.borrow_mut().as_mut() {
+                // A race that was started later has its own timeout
+                Some(ghost) if ghost.started != started => return glib::ControlFlow::Break,
+                Some(ghost) => {
+                    ghost.advance(Instant::now(), |stratagem| filter.get_keycode(stratagem));
+                    ghost.is_done()
+                }
+                None => return glib::ControlFlow::Break,
+            };
+            if done {
+                trainer.finish_race();
+                return glib::ControlFlow::Break;
+            }
+            trainer.redraw_ghost();
+            glib::ControlFlow::Continue
+        });
+    }
+
+    /// Ends the race, if there is one, and tells how it went
+    fn finish_race(&self) {
+        let Some(ghost) = self.ghost.take() else {
+            return;
+        };
+        self.ghost_arrows.set_visible(false);
+        let result = match ghost.get_lead() {
+            0 => "a draw".to_string(),
+            lead if lead > 0 => format!("the ghost won by {}", describe_codes(lead)),
+            lead => format!("you won by {}", describe_codes(-lead)),
+        };
+        self.last_attempt.remove_css_class("success");
+        self.tell(&format!("Race over, {result}: {} against {} codes",
+                           ghost.player_completed, ghost.completed));
+    }
+
+    /// Shows the keys the ghost pressed for its Stratagem and how far ahead
+    /// it is
+    fn redraw_ghost(&self) {
+        let ghost = self.ghost.borrow();
+        let Some(ghost) = ghost.as_ref() else {
+            return;
+        };
+        let keycode = ghost.stratagem
+            .map(|stratagem| self.filter.get().get_keycode(&stratagem))
+            .unwrap_or_default();
+        let arrows = gui_templates::get_keycode_markup(&keycode, &ghost.pressed);
+        let lead = match ghost.get_lead() {
+            0 => "even".to_string(),
+            lead if lead > 0 => format!("{} ahead", describe_codes(lead)),
+            lead => format!("{} behind", describe_codes(-lead)),
+        };
+        self.ghost_arrows.set_markup(&format!("{arrows} <span size=\"small\">Ghost · {lead}</span>"));
+        self.ghost_arrows.update_property(&[gtk::accessible::Property::Label(&format!(
+            "Ghost, {lead}: {}",
+            gui_templates::describe_keycode_progress(&keycode, &ghost.pressed)))]);
+    }
+
+    /// Races the others on the network. Until the race starts, a countdown
+    /// is shown instead of a Stratagem. A replay, ghost or game of Stratagem
+    /// Hero is given up for it.
+    pub fn start_lan_race(&self, start: race::RaceStart, connection: Rc<race::Connection>) {
+        self.player.take();
+        self.finish_race();
+        if self.hero.take().is_some() {
+            self.hero_status.set_visible(false);
+        }
+        self.stop_versus();
+        self.lan_race.replace(Some(race::RaceProgress::new(start.clone(), connection)));
+        self.arrows.set_text("");
+        self.last_attempt.remove_css_class("success");
+        self.tell(&format!("First to {} wins",
+                           describe_codes(start.length as i64)));
+
+        let trainer = self.clone();
+        glib::timeout_add_local(REPLAY_TICK, move || {
+            match trainer.lan_race.borrow().as_ref() {
+                // A race that was started later has its own countdown
+                Some(race) if race.start == start => (),
+                _ => return glib::ControlFlow::Break,
+            }
+            let left = start.starts_at.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                trainer.last_attempt.set_text("");
+                trainer.restart_with_seed(start.seed);
+                return glib::ControlFlow::Break;
+            }
+            let countdown = format!("Race starts in {}", left.as_secs() + 1);
+            if trainer.prompt.text() != countdown {
+                trainer.prompt.set_text(&countdown);
+                trainer.prompt.announce(&countdown, gtk::AccessibleAnnouncementPriority::High);
+            }
+            glib::ControlFlow::Continue
+        });
+    }
+
+    /// Whether keys are ignored because a race on the network didn't start
+    /// yet
+    pub fn is_counting_down(&self) -> bool {
+        self.lan_race.borrow().as_ref().is_some_and(|race| !race.has_started(Instant::now()))
+    }
+
+    /// Counts an attempt for the race on the network and tells the others
+    fn count_for_lan_race(&self, success: bool) {
+        let mut lan_race = self.lan_race.borrow_mut();
+        let Some(race) = lan_race.as_mut() else {
+            return;
+        };
+        if success {
+            race.completed += 1;
+        } else {
+            race.mistakes += 1;
+        }
+        race.report();
+        if race.is_finished() {
+            *lan_race = None;
+            self.sounds.play(sound::Sound::RoundComplete);
+            self.last_attempt.remove_css_class("success");
+            self.tell("You are through! The scoreboard shows the others");
+        }
+    }
+
+    /// Shows everyone's position in the race on the network, the player's
+    /// own name in bold. An empty board means the player left the race.
+    pub fn show_race_board(&self, board: &[race::Standing], own_name: &str) {
+        if board.is_empty() {
+            let counting_down = self.is_counting_down();
+            self.lan_race.take();
+            self.race_board.set_visible(false);
+            if counting_down {
+                self.refresh_stratagem();
+            }
+            return;
+        }
+        let places: Vec<String> = board.iter()
+            .enumerate()
+            .map(|(place, standing)| {
+                let name = glib::markup_escape_text(&standing.name);
+                let name = match standing.name == own_name {
+                    true => format!("<b>{name}</b>"),
+                    false => name.to_string(),
+                };
+                let progress = match standing.finished_ms {
+                    Some(_) => "✓".to_string(),
+                    None => standing.completed.to_string(),
+                };
+                let place = format!("{}. {name} {progress}", place + 1);
+                match standing.connected {
+                    true => place,
+                    false => format!("<span alpha=\"50%\">{place}</span>"),
+                }
+            })
+            .collect();
+        self.race_board.set_markup(&places.join(" · "));
+        self.race_board.set_visible(true);
+    }
+
+    /// Starts a game for two players on one keyboard, the first to the
+    /// winning score wins. Anything else that runs on the trainer is given
+    /// up for it.
+    pub fn start_versus(&self, winning_score: u32) {
+        self.player.take();
+        self.finish_race();
+        self.lan_race.take();
+        if self.hero.take().is_some() {
+            self.hero_status.set_visible(false);
+        }
+        self.versus.replace(Some(versus::VersusGame::new(winning_score)));
+        self.arrows.set_visible(false);
+        self.versus_status.set_visible(true);
+        self.last_attempt.remove_css_class("success");
+        self.tell(&format!("First to {winning_score} points wins · Escape ends the game"));
+        self.restart_with_seed(picker::random_seed());
+        self.redraw_versus();
+    }
+
+    /// Whether two players share the keyboard right now
+    pub fn is_versus(&self) -> bool {
+        self.versus.borrow().is_some()
+    }
+
+    /// Ends the game for two players and tells who won
+    pub fn end_versus(&self) {
+        let Some(game) = self.stop_versus() else {
+            return;
+        };
+        let scores: Vec<String> = versus::Side::iter()
+            .map(|side| game.get_player(side).score.to_string())
+            .collect();
+        let result = match game.get_winner() {
+            Some(side) => format!("{side} wins"),
+            None => "Game ended".to_string(),
+        };
+        self.tell(&format!("{result}, {}", scores.join(" to ")));
+        self.refresh_stratagem();
+    }
+
+    /// Takes the game for two players away, without a word
+    fn stop_versus(&self) -> Option<versus::VersusGame> {
+        let game = self.versus.take()?;
+        self.versus_status.set_visible(false);
+        self.arrows.set_visible(true);
+        Some(game)
+    }
+
+    fn redraw_versus(&self) {
+        let versus = self.versus.borrow();
+        let Some(game) = versus.as_ref() else {
+            return;
+        };
+        let keycode = self.filter.get().get_keycode(&self.current_stratagem.get());
+        for (side, score, arrows) in &self.versus_players {
+            let player = game.get_player(*side);
+            score.set_text(&format!("{side} · {} · {} Points", side.get_keys(), player.score));
+            gui_templates::show_keycode(arrows, &keycode, &player.pressed);
+        }
+    }
+
+    /// Builds the menu to start a game for two players on one keyboard
+    pub fn create_versus_menu(&self) -> gtk::MenuButton {
+        let options = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        let scores: Vec<String> = versus::WINNING_SCORES.iter()
+            .map(|score| format!("{score} points"))
+            .collect();
+        let scores: Vec<&str> = scores.iter().map(String::as_str).collect();
+        let score_select = gtk::DropDown::from_strings(&scores);
+        let start = gtk::Button::builder()
+            .label("Start")
+            .css_classes(*&["suggested-action"])
+            .build();
+        let keys: Vec<String> = versus::Side::iter()
+            .map(|side| format!("{side}: {}", side.get_keys()))
+            .collect();
+        options.append(&gtk::Label::new(Some(&keys.join("\n"))));
+        options.append(&gtk::Label::new(Some("Play to")));
+        options.append(&score_select);
+        options.append(&start);
+
+        let popover = gtk::Popover::builder().child(&options).build();
+        let trainer = self.clone();
+        let start_popover = popover.clone();
+        start.connect_clicked(move |_| {
+            let score = versus::WINNING_SCORES.get(score_select.selected() as usize)
+                .copied()
+                .unwrap_or(versus::WINNING_SCORES[0]);
+            start_popover.popdown();
+            trainer.start_versus(score);
+        });
+
+        gtk::MenuButton::builder()
+            .icon_name("system-users-symbolic")
+            .tooltip_text("Versus on One Keyboard")
+            .popover(&popover)
+            .build()
+    }
+
+    /// Builds the menu with the volume of the sounds and a switch to mute
+    /// them
+    pub fn create_sound_menu(&self) -> gtk::MenuButton {
+        let settings = self.settings.get();
+        let volume = gtk::Scale::with_range(gtk::Orientation::Horizontal, 0.0, 100.0, 5.0);
+        volume.set_value(settings.audio.volume * 100.0);
+        volume.set_width_request(160);
+        let mute = gtk::CheckButton::builder()
+            .label("Mute")
+            .active(settings.audio.muted)
+            .build();
+        let options = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        options.append(&gtk::Label::new(Some("Volume")));
+        options.append(&volume);
+        options.append(&mute);
+        let call_out = gtk::CheckButton::builder()
+            .label("Call out Stratagems instead of showing them")
+            .active(self.call_out.get())
+            .build();
+        options.append(&call_out);
+
+        let menu = gtk::MenuButton::builder()
+            .icon_name(get_volume_icon(&settings.audio))
+            .tooltip_text("Sound")
+            .popover(&gtk::Popover::builder().child(&options).build())
+            .build();
+        let trainer = self.clone();
+        volume.connect_value_changed(move |volume| {
+            let new_volume = volume.value() / 100.0;
+            if new_volume == trainer.settings.get().audio.volume {
+                return;
+            }
+            trainer.settings.update(|settings| settings.audio.volume = new_volume);
+            // Lets the new volume be heard
+            trainer.sounds.play(sound::Sound::Arrow);
+        });
+        let store = self.settings.clone();
+        mute.connect_toggled(move |mute| {
+            store.update(|settings| settings.audio.muted = mute.is_active());
+        });
+        let store = self.settings.clone();
+        call_out.connect_toggled(move |call_out| {
+            store.update(|settings| settings.trainer.call_out = call_out.is_active());
+        });
+
+        // The preferences can change the same settings
+        let menu_icon = menu.clone();
+        self.settings.connect_changed(move |settings| {
+            if volume.value() / 100.0 != settings.audio.volume {
+                volume.set_value(settings.audio.volume * 100.0);
+            }
+            mute.set_active(settings.audio.muted);
+            call_out.set_active(settings.trainer.call_out);
+            menu_icon.set_icon_name(get_volume_icon(&settings.audio));
+        });
+        menu
+    }
+
+    /// Builds the menu with the settings that make the arrows easier to see
+    /// and the window calmer
+    pub fn create_accessibility_menu(&self) -> gtk::MenuButton {
+        let settings = self.settings.get().appearance;
+        let options = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        let toggles: [(&str, AccessibilityGetter, AccessibilitySetter); 3] = [
+            ("Large arrows", |s| s.large_arrows, |s, v| s.large_arrows = v),
+            ("High-contrast arrows", |s| s.high_contrast, |s, v| s.high_contrast = v),
+            ("Reduce motion", |s| s.reduce_motion, |s, v| s.reduce_motion = v),
+        ];
+        for (label, getter, setter) in toggles {
+            let check = gtk::CheckButton::builder()
+                .label(label)
+                .active(getter(&settings))
+                .build();
+            let store = self.settings.clone();
+            check.connect_toggled(move |check| {
+                store.update(|settings| setter(&mut settings.appearance, check.is_active()));
+            });
+            options.append(&check);
+            let check = check.clone();
+            self.settings.connect_changed(move |settings| {
+                check.set_active(getter(&settings.appearance));
+            });
+        }
+
+        let themes: Vec<String> = ArrowTheme::iter().map(|theme| theme.to_string()).collect();
+        let themes: Vec<&str> = themes.iter().map(String::as_str).collect();
+        let theme_dropdown = gtk::DropDown::from_strings(&themes);
+        let selected = ArrowTheme::iter().position(|theme| theme == settings.arrow_theme);
+        theme_dropdown.set_selected(selected.unwrap_or(0) as u32);
+        let store = self.settings.clone();
+        theme_dropdown.connect_selected_notify(move |dropdown| {
+            let theme = ArrowTheme::iter()
+                .nth(dropdown.selected() as usize)
+                .unwrap_or_default();
+            store.update(|settings| settings.appearance.arrow_theme = theme);
+        });
+        let theme_box = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        theme_box.append(&gtk::Label::new(Some("Arrow style")));
+        theme_box.append(&theme_dropdown);
+        options.append(&theme_box);
+        self.settings.connect_changed(move |settings| {
+            let selected = ArrowTheme::iter()
+                .position(|theme| theme == settings.appearance.arrow_theme);
+            theme_dropdown.set_selected(selected.unwrap_or(0) as u32);
+        });
+
+        // The window only exists once the menu is part of it
+        let menu = gtk::MenuButton::builder()
+            .icon_name("preferences-desktop-accessibility-symbolic")
+            .tooltip_text("Accessibility")
+            .popover(&gtk::Popover::builder().child(&options).build())
+            .build();
+        let store = self.settings.clone();
+        menu.connect_realize(move |menu| accessibility::apply(store.get().appearance, menu));
+        menu
+    }
+
+    /// Draws the arrows again, for example after their style changed
+    pub fn redraw_keycode(&self) {
+        if !self.is_replaying() && !self.call_out.get() {
+            let keycode = self.filter.get().get_keycode(&self.current_stratagem.get());
+            gui_templates::show_keycode(&self.arrows, &keycode, &[]);
+            self.describe_progress();
+        }
+        self.redraw_ghost();
+        self.redraw_versus();
+    }
+
+    /// Tells screen readers how much of the keycode was entered, the arrows
+    /// themselves stay the same
+    fn describe_progress(&self) {
+        let keycode = self.filter.get().get_keycode(&self.current_stratagem.get());
+        let entered = keycode.len().saturating_sub(self.input_buffer.borrow().len());
+        self.arrows.update_property(&[gtk::accessible::Property::Label(
+            &gui_templates::describe_keycode_progress(&keycode, &keycode[..entered]))]);
+    }
+
+    /// The mode the attempts are made in right now
+    pub fn get_mode(&self) -> stats::TrainerMode {
+        match self.hero.borrow().as_ref() {
+            Some(_) => stats::TrainerMode::StratagemHero,
+            None if self.call_out.get() => stats::TrainerMode::CallOut,
+            None => stats::TrainerMode::Practice,
+        }
+    }
+
+    /// Saves the attempt at the current Stratagem and shows how it went.
+    /// `wrong_key` is the key that ended it, if it failed.
+    fn finish_attempt(&self, wrong_key: Option<db::Code>) {
+        let Some(attempt) = self.timer.borrow().finish(wrong_key) else {
+            return;
+        };
+        if let Err(e) = stats::save_attempt(&attempt) {
+            eprintln!("Could not save the attempt: {e}");
+        }
+        if let Some(session) = self.session.borrow_mut().as_mut() {
+            session.add(attempt.clone());
+        }
+        let beaten = attempt.is_success() && self.update_personal_best(&attempt);
+        let mut summary = match attempt.mistake_at {
+            None => format!("Reaction {} · Done in {}",
+                            gui_templates::format_short_duration(attempt.reaction),
+                            gui_templates::format_short_duration(attempt.total)),
+            Some(position) => {
+                let keycode = self.filter.get().get_keycode(&attempt.stratagem);
+                let expected = keycode.get(position).map_or("", gui_templates::get_arrow);
+                let pressed = wrong_key.as_ref().map_or("", gui_templates::get_arrow);
+                format!("Reaction {} · Key {} of {} was {pressed} instead of {expected}",
+                        gui_templates::format_short_duration(attempt.reaction),
+                        position + 1,
+                        keycode.len())
+            }
+        };
+        if beaten {
+            summary = format!("New personal best! {summary}");
+            self.last_attempt.add_css_class("success");
+            if let Some(game) = self.hero.borrow_mut().as_mut() {
+                game.personal_bests.push(attempt.stratagem);
+            }
+        } else {
+            self.last_attempt.remove_css_class("success");
+        }
+        self.tell(&summary);
+
+        if self.call_out.get() {
+            let spoken = match attempt.mistake_at {
+                None => format!("Correct, {:.1} seconds.", attempt.total.as_secs_f64()),
+                Some(position) => {
+                    let keycode = self.filter.get().get_keycode(&attempt.stratagem);
+                    let expected = keycode.get(position).map(|code| code.to_string());
+                    format!("Wrong, key {} was {}.", position + 1, expected.unwrap_or_default())
+                }
+            };
+            let best = if beaten { "New personal best! " } else { "" };
+            self.announcement.replace(format!("{best}{spoken}"));
+        }
+    }
+
+    /// Remembers the time of a correct attempt if it is the fastest so far.
+    /// Returns true if an earlier best was beaten, the first correct attempt
+    /// at a Stratagem doesn't count as beating anything.
+    fn update_personal_best(&self, attempt: &stats::Attempt) -> bool {
+        let mut bests = self.personal_bests.borrow_mut();
+        match bests.iter_mut().find(|(stratagem, _)| *stratagem == attempt.stratagem) {
+            Some((_, best)) if attempt.total < *best => {
+                *best = attempt.total;
+                true
+            }
+            Some(_) => false,
+            None => {
+                bests.push((attempt.stratagem, attempt.total));
+                false
+            }
+        }
+    }
+
+    /// Starts a game of Stratagem Hero with the default Stratagems. A
+    /// running game is given up.
+    pub fn start_hero(&self, difficulty: hero::Difficulty, seed: u64) {
+        let game = hero::HeroGame::new(seed, difficulty);
+        let started = game.started;
+        self.lan_race.take();
+        self.stop_versus();
+        self.hero.replace(Some(game));
+        self.hero_status.set_visible(true);
+        self.last_attempt.set_text("");
+        self.refresh_stratagem();
+        self.redraw_hero();
+
+        let trainer = self.clone();
+        let mut last_tick = Instant::now();
+        glib::timeout_add_local(HERO_TICK, move || {
+            let now = Instant::now();
+            let elapsed = now - last_tick;
+            last_tick = now;
+            let over = match trainer.hero.borrow_mut().as_mut() {
+                // A newer game has its own timeout
+                Some(game) if game.started != started =>
+                    return glib::ControlFlow::Break,
+                Some(game) => {
+                    if !trainer.is_paused() {
+                        game.tick(elapsed);
+                    }
+                    game.is_over()
+                }
+                None => return glib::ControlFlow::Break,
+            };
+            if over {
+                trainer.end_hero();
+                return glib::ControlFlow::Break;
+            }
+            trainer.redraw_hero();
+            glib::ControlFlow::Continue
+        });
+    }
+
+    /// Ends the game of Stratagem Hero and goes back to practice
+    fn end_hero(&self) {
+        let Some(game) = self.hero.take() else {
+            return;
+        };
+        self.hero_status.set_visible(false);
+        self.finish_race();
+        self.refresh_stratagem();
+        let replay_path = match replay::save_replay(game.recorder.get_replay()) {
+            Ok(path) => Some(path),
+            Err(e) => {
+                eprintln!("Could not save the replay: {e}");
+                None
+            }
+        };
+        highscores_page::open_game_over(&game, replay_path.as_deref(), &self.prompt);
+    }
+
+    fn redraw_hero(&self) {
+        if let Some(game) = self.hero.borrow().as_ref() {
+            // A clock that runs smoothly is motion too, without it the
+            // clock moves in steps of a twentieth
+            let share = match accessibility::is_motion_reduced() {
+                true => (game.get_time_share() * 20.0).ceil() / 20.0,
+                false => game.get_time_share(),
+            };
+            self.hero_clock.set_fraction(share);
+            self.hero_score.set_text(&format!("Round {} · {} Points", game.round, game.score));
+        }
+    }
+
+    /// Builds the menu to start a game of Stratagem Hero with a difficulty
+    /// and, to play a game again, a seed
+    pub fn create_hero_menu(&self) -> gtk::MenuButton {
+        let options = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        let difficulties: Vec<String> = hero::Difficulty::iter().map(|d| d.to_string()).collect();
+        let difficulties: Vec<&str> = difficulties.iter().map(String::as_str).collect();
+        let difficulty_select = gtk::DropDown::from_strings(&difficulties);
+        difficulty_select.set_selected(hero::Difficulty::iter()
+                                       .position(|d| d == hero::Difficulty::default())
+                                       .unwrap_or(0) as u32);
+        let seed_entry = gtk::Entry::builder()
+            .placeholder_text("Random seed")
+            .input_purpose(gtk::InputPurpose::Digits)
+            .build();
+        let start = gtk::Button::builder()
+            .label("Start")
+            .css_classes(*&["suggested-action"])
+            .build();
+        options.append(&gtk::Label::new(Some("Difficulty")));
+        options.append(&difficulty_select);
+        options.append(&gtk::Label::new(Some("Seed")));
+        options.append(&seed_entry);
+        options.append(&start);
+
+        let popover = gtk::Popover::builder().child(&options).build();
+        let trainer = self.clone();
+        let start_popover = popover.clone();
+        start.connect_clicked(move |_| {
+            let difficulty = hero::Difficulty::iter()
+                .nth(difficulty_select.selected() as usize)
+                .unwrap_or_default();
+            let seed = match seed_entry.text().trim() {
+                "" => picker::random_seed(),
+                text => match text.parse() {
+                    Ok(seed) => seed,
+                    Err(_) => {
+                        seed_entry.error_bell();
+                        return;
+                    }
+                },
+            };
+            start_popover.popdown();
+            trainer.start_hero(difficulty, seed);
+        });
+
+        gtk::MenuButton::builder()
+            .icon_name("applications-games-symbolic")
+            .tooltip_text("Stratagem Hero")
+            .popover(&popover)
+            .build()
+    }
+
+    /// Whether the trainer is shown and enters codes with this arrow key.
+    /// The window would move the focus with it otherwise.
+    pub fn takes_arrow_key(&self, key: gtk::gdk::Key) -> bool {
+        use gtk::gdk::Key;
+        matches!(key, Key::Up | Key::Down | Key::Left | Key::Right)
+            && self.settings.get().input.trainer_keys.uses_arrow_keys()
+            && self.arrows.is_mapped()
+    }
+
+    /// Whether keys are ignored because the session is paused
+    pub fn is_paused(&self) -> bool {
+        self.session.borrow().as_ref().is_some_and(|session| session.paused)
+    }
+
+    /// Builds the buttons to start, pause and end a practice session. Ending
+    /// it opens its summary.
+    pub fn create_session_controls(&self) -> gtk::Box {
+        let start = gtk::Button::builder()
+            .icon_name("media-playback-start-symbolic")
+            .tooltip_text("Start Session")
+            .build();
+        let pause = gtk::ToggleButton::builder()
+            .icon_name("media-playback-pause-symbolic")
+            .tooltip_text("Pause Session")
+            .sensitive(false)
+            .build();
+        let end = gtk::Button::builder()
+            .icon_name("media-playback-stop-symbolic")
+            .tooltip_text("End Session")
+            .sensitive(false)
+            .build();
+
+        let trainer = self.clone();
+        let (start_pause, start_end) = (pause.clone(), end.clone());
+        start.connect_clicked(move |start| {
+            let (history, _) = stats::load_attempts();
+            let mut code = trainer.get_seed_code();
+            if !trainer.seed_chosen.get() {
+                code.seed = picker::random_seed();
+            }
+            let seed = code.seed;
+            trainer.session.replace(Some(session::Session::start(&history, code)));
+            trainer.tell("Session started");
+            // Every session with the same seed asks for the same Stratagems
+            trainer.restart_with_seed(seed);
+            start.set_sensitive(false);
+            start_pause.set_sensitive(true);
+            start_end.set_sensitive(true);
+        });
+
+        let trainer = self.clone();
+        pause.connect_toggled(move |pause| {
+            let paused = pause.is_active();
+            if let Some(session) = trainer.session.borrow_mut().as_mut() {
+                if paused {
+                    session.pause();
+                } else {
+                    session.resume();
+                }
+            }
+            if paused {
+                trainer.prompt.set_text("Session paused");
+                trainer.tell("Resume to continue");
+            } else {
+                // The pause doesn't count as reaction time
+                trainer.last_attempt.set_text("");
+                trainer.refresh_stratagem();
+            }
+        });
+
+        let trainer = self.clone();
+        let (end_start, end_pause) = (start.clone(), pause.clone());
+        end.connect_clicked(move |end| {
+            // Taken out first, so the pause button doesn't resume it
+            let session = trainer.session.take();
+            end_pause.set_active(false);
+            end_pause.set_sensitive(false);
+            end.set_sensitive(false);
+            end_start.set_sensitive(true);
+            trainer.last_attempt.set_text("");
+            trainer.refresh_stratagem();
+            if let Some(session) = session {
+                if let Err(e) = replay::save_replay(session.recorder.get_replay()) {
+                    eprintln!("Could not save the replay: {e}");
+                }
+                session_summary::open_session_summary(&session.summarize(), end);
+            }
+        });
+
+        let controls = gtk::Box::builder()
+            .spacing(6)
+            .build();
+        controls.append(&start);
+        controls.append(&pause);
+        controls.append(&end);
+        controls
+    }
+
+    /// Builds the menu where users choose if mission, warbond and removed
+    /// Stratagems should be part of the training and which patch the codes are
+    /// taken from. The next Stratagem is picked with the new settings.
+    pub fn create_filter_menu(&self) -> gtk::MenuButton {
+        let options = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        let current = self.filter.get();
+        let toggles: [(&str, FilterGetter, FilterSetter); 3] = [
+            ("Mission Stratagems", |t| t.mission_only, |t, v| t.mission_only = v),
+            ("Warbond Stratagems", |t| t.warbond, |t, v| t.warbond = v),
+            ("Removed Stratagems", |t| t.removed, |t, v| t.removed = v),
+        ];
+        // The kinds of Stratagems are saved, the trainer takes them from the
+        // settings
+        for (label, getter, setter) in toggles {
+            let check = gtk::CheckButton::builder()
+                .label(label)
+                .active(getter(&self.settings.get().trainer))
+                .build();
+            let store = self.settings.clone();
+            check.connect_toggled(move |check| {
+                store.update(|settings| setter(&mut settings.trainer, check.is_active()));
+            });
+            options.append(&check);
+            let check = check.clone();
+            self.settings.connect_changed(move |settings| {
+                check.set_active(getter(&settings.trainer));
+            });
+        }
+
+        let patch_select = gui_templates::create_patch_dropdown(current.patch);
+        let filter = self.filter.clone();
+        patch_select.connect_selected_notify(move |dropdown| {
+            let mut new_filter = filter.get();
+            new_filter.patch = gui_templates::get_selected_patch(dropdown);
+            filter.set(new_filter);
+        });
+        options.append(&gtk::Label::new(Some("Codes from patch")));
+        options.append(&patch_select);
+
+        // Leaves a loadout started from the loadout page
+        let train_all = gtk::Button::with_label("Train all Stratagems");
+        let trainer = self.clone();
+        train_all.connect_clicked(move |_| trainer.train_loadout(None));
+        options.append(&train_all);
+
+        // Share the seed code to get the same Stratagems in the same order,
+        // it holds the Stratagems to pick from too
+        let seed_entry = gtk::Entry::builder()
+            .secondary_icon_name("edit-copy-symbolic")
+            .secondary_icon_tooltip_text("Copy Seed")
+            .build();
+        seed_entry.connect_icon_press(|entry, _| entry.clipboard().set_text(&entry.text()));
+        let trainer = self.clone();
+        seed_entry.connect_activate(move |entry| match picker::SeedCode::parse(&entry.text()) {
+            Ok(code) => trainer.play_seed_code(code),
+            Err(e) => {
+                entry.error_bell();
+                trainer.tell(&e);
+            }
+        });
+        let new_seed = gtk::Button::with_label("Start over with a new seed");
+        let trainer = self.clone();
+        let new_seed_entry = seed_entry.clone();
+        new_seed.connect_clicked(move |_| {
+            trainer.restart_with_seed(picker::random_seed());
+            new_seed_entry.set_text(&trainer.get_seed_code().to_code());
+        });
+        options.append(&gtk::Label::new(Some("Seed")));
+        options.append(&seed_entry);
+        options.append(&new_seed);
+
+        let popover = gtk::Popover::builder().child(&options).build();
+        let trainer = self.clone();
+        popover.connect_show(move |_| seed_entry.set_text(&trainer.get_seed_code().to_code()));
+        gtk::MenuButton::builder()
+            .icon_name("view-more-symbolic")
+            .tooltip_text("Choose Stratagems")
+            .popover(&popover)
+            .build()
+    }
+}
+
+type FilterGetter = fn(&settings::TrainerSettings) -> bool;
+type FilterSetter = fn(&mut settings::TrainerSettings, bool);
+type AccessibilityGetter = fn(&accessibility::AccessibilitySettings) -> bool;
+type AccessibilitySetter = fn(&mut accessibility::AccessibilitySettings, bool);
+
+/// The icon that shows how loud the sounds are
+fn get_volume_icon(settings: &sound::SoundSettings) -> &'static str {
+    if settings.muted {
+        return "audio-volume-muted-symbolic";
+    }
+    match settings.volume {
+        volume if volume <= 0.0 => "audio-volume-muted-symbolic",
+        volume if volume < 0.34 => "audio-volume-low-symbolic",
+        volume if volume < 0.67 => "audio-volume-medium-symbolic",
+        _ => "audio-volume-high-symbolic",
+    }
+}
+
+/// A number of codes, like "1 code" or "3 codes"
+fn describe_codes(count: i64) -> String {
+    match count {
+        1 => "1 code".to_string(),
+        count => format!("{count} codes"),
+    }
+}
+
+/// This function checks the combination and modifies the input buffer as well as
+/// the ui if it needs to be cleared. `event_ms` is the time of the key event,
+/// it is used to time the attempt.
+pub fn keybinds_magic(key: gtk::gdk::Key, event_ms: u32, trainer: &Trainer) -> glib::Propagation {
+    let time = stats::KeyTime::now(event_ms);
+    use gtk::gdk::Key;
+    use db::Code;
+    if trainer.is_replaying() {
+        if key == Key::Escape {
+            trainer.stop_replay();
+        }
+        return glib::Propagation::Proceed;
+    }
+    if trainer.is_versus() {
+        return versus_keybinds(key, trainer);
+    }
+    if trainer.is_paused() || trainer.is_counting_down() {
+        return glib::Propagation::Proceed;
+    }
+    let keys = trainer.settings.get().input.trainer_keys;
+    let input = match key {
+        Key::w if keys.uses_wasd() => Code::Up,
+        Key::a if keys.uses_wasd() => Code::Left,
+        Key::s if keys.uses_wasd() => Code::Down,
+        Key::d if keys.uses_wasd() => Code::Right,
+        Key::Up if keys.uses_arrow_keys() => Code::Up,
+        Key::Left if keys.uses_arrow_keys() => Code::Left,
+        Key::Down if keys.uses_arrow_keys() => Code::Down,
+        Key::Right if keys.uses_arrow_keys() => Code::Right,
+        Key::F5 => {
+            trainer.refresh_stratagem();
+            return glib::Propagation::Proceed;
+        }
+        // Didn't catch the name
+        Key::space if trainer.call_out.get() => {
+            trainer.speaker.say(&trainer.current_stratagem.get().to_string());
+            return glib::Propagation::Proceed;
+        }
+        _ => return glib::Propagation::Proceed,
+    };
+    trainer.timer.borrow_mut().press(time);
+    trainer.record(|recorder| recorder.record_key(input, time));
+    match check_input(input, trainer.input_buffer.clone()) {
+        Input::Correct => {
+            trainer.sounds.play(sound::Sound::Arrow);
+            trainer.describe_progress();
+        }
+        Input::Done => {
+            trainer.finish_attempt(None);
+            let keys = trainer.filter.get().get_keycode(&trainer.current_stratagem.get()).len();
+            let round_done = trainer.hero.borrow_mut().as_mut()
+                .is_some_and(|game| game.on_success(keys));
+            trainer.sounds.play(match round_done {
+                true => sound::Sound::RoundComplete,
+                false => sound::Sound::Success,
+            });
+            if let Some(ghost) = trainer.ghost.borrow_mut().as_mut() {
+                ghost.player_completed += 1;
+            }
+            trainer.count_for_lan_race(true);
+            trainer.redraw_hero();
+            trainer.redraw_ghost();
+            trainer.refresh_stratagem();
+        }
+        // Stratagem Hero keeps the Stratagem until it is entered correctly
+        Input::Wrong if trainer.hero.borrow().is_some() => {
+            trainer.sounds.play(sound::Sound::Error);
+            trainer.finish_attempt(Some(input));
+            if let Some(game) = trainer.hero.borrow_mut().as_mut() {
+                game.on_mistake();
+            }
+            trainer.show_current_stratagem();
+        }
+        Input::Wrong => {
+            trainer.sounds.play(sound::Sound::Error);
+            trainer.finish_attempt(Some(input));
+            trainer.count_for_lan_race(false);
+            trainer.refresh_stratagem();
+        }
+    }
+    glib::Propagation::Proceed
+}
+
+/// Routes the keys of both players to their own part of the keycode. The
+/// keys are taken, so the arrow keys don't move the focus.
+fn versus_keybinds(key: gtk::gdk::Key, trainer: &Trainer) -> glib::Propagation {
+    use gtk::gdk::Key;
+    use db::Code;
+    use versus::Side;
+    let (side, input) = match key {
+        Key::w => (Side::Left, Code::Up),
+        Key::a => (Side::Left, Code::Left),
+        Key::s => (Side::Left, Code::Down),
+        Key::d => (Side::Left, Code::Right),
+        Key::Up => (Side::Right, Code::Up),
+        Key::Left => (Side::Right, Code::Left),
+        Key::Down => (Side::Right, Code::Down),
+        Key::Right => (Side::Right, Code::Right),
+        Key::Escape => {
+            trainer.end_versus();
+            return glib::Propagation::Stop;
+        }
+        _ => return glib::Propagation::Proceed,
+    };
+    let keycode = trainer.filter.get().get_keycode(&trainer.current_stratagem.get());
+    let (result, over) = match trainer.versus.borrow_mut().as_mut() {
+        Some(game) => (game.press(side, input, &keycode), game.get_winner().is_some()),
+        None => return glib::Propagation::Proceed,
+    };
+    trainer.sounds.play(match result {
+        versus::VersusInput::Correct => sound::Sound::Arrow,
+        versus::VersusInput::Wrong => sound::Sound::Error,
+        versus::VersusInput::Point if over => sound::Sound::RoundComplete,
+        versus::VersusInput::Point => sound::Sound::Success,
+    });
+    match result {
+        versus::VersusInput::Point if over => trainer.end_versus(),
+        versus::VersusInput::Point => {
+            trainer.tell(&format!("{side} got the point"));
+            trainer.refresh_stratagem();
+            trainer.redraw_versus();
+        }
+        versus::VersusInput::Correct | versus::VersusInput::Wrong => trainer.redraw_versus(),
+    }
+    glib::Propagation::Stop
+}
+
+/// What a key did to the keycode that is being entered
+enum Input {
+    /// Right key, but the keycode isn't finished yet
+    Correct,
+    /// Right key and the keycode is finished
+    Done,
+    Wrong,
+}
+
+fn check_input(current_input: db::Code,
+               keycode: Rc<RefCell<Vec<db::Code>>>) -> Input {
+    if keycode.borrow().is_empty() {
+        return Input::Wrong;
+    }
+    if keycode.borrow_mut().remove(0) != current_input {
+        return Input::Wrong;
+    }
+    if keycode.borrow().is_empty() {
+        Input::Done
+    } else {
+        Input::Correct
+    }
+}
@@ -0,0 +1,149 @@
+// Copyright (C) 2024 Bjarne Seger
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License version 2 as published
+// by the Free Software Foundation.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/old-licenses/gpl-2.0.txt>.
+
+// Two players on one keyboard, one with WASD and one with the arrow keys.
+// Both enter the code of the same Stratagem, whoever is through first gets
+// the point and both go on to the next Stratagem.
+
+use strum::IntoEnumIterator;
+
+use crate::db;
+
+/// How many points a game can be played to
+pub const WINNING_SCORES: [u32; 3] = [5, 10, 15];
+
+#[derive(Debug, PartialEq, Eq, strum_macros::Display, strum_macros::EnumIter, Copy, Clone)]
+pub enum Side {
+    #[strum(to_string = "Player 1")]
+    Left,
+    #[strum(to_string = "Player 2")]
+    Right,
+}
+
+impl Side {
+    /// The keys the player enters codes with
+    pub fn get_keys(&self) -> &'static str {
+        match self {
+            Side::Left => "WASD",
+            Side::Right => "Arrow keys",
+        }
+    }
+
+    fn get_index(&self) -> usize {
+        match self {
+            Side::Left => 0,
+            Side::Right => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct VersusPlayer {
+    /// The keys of the current code the player got right so far
+    pub pressed: Vec<db::Code>,
+    pub score: u32,
+    pub mistakes: u32,
+}
+
+/// What a key of one player did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersusInput {
+    Correct,
+    /// The player starts the code over
+    Wrong,
+    /// The player was through first and got the point
+    Point,
+}
+
+#[derive(Debug, Clone)]
+pub struct VersusGame {
+    /// The first to get this many points wins
+    pub winning_score: u32,
+    players: [VersusPlayer; 2],
+}
+
+impl VersusGame {
+    pub fn new(winning_score: u32) -> Self {
+        VersusGame {
+            winning_score,
+            players: Default::default(),
+        }
+    }
+
+    pub fn get_player(&self, side: Side) -> &VersusPlayer {
+        &self.players[side.get_index()]
+    }
+
+    /// Checks a key of one player against the keycode both are entering. A
+    /// point starts both players over for the next Stratagem.
+    pub fn press(&mut self, side: Side, code: db::Code, keycode: &[db::Code]) -> VersusInput {
+        let player = &mut self.players[side.get_index()];
+        player.pressed.push(code);
+        if !keycode.starts_with(&player.pressed) {
+            player.pressed.clear();
+            player.mistakes += 1;
+            return VersusInput::Wrong;
+        }
+        if player.pressed.len() < keycode.len() {
+            return VersusInput::Correct;
+        }
+        player.score += 1;
+        for player in self.players.iter_mut() {
+            player.pressed.clear();
+        }
+        VersusInput::Point
+    }
+
+    /// The player who reached the winning score, if someone did
+    pub fn get_winner(&self) -> Option<Side> {
+        Side::iter().find(|side| self.get_player(*side).score >= self.winning_score)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use db::Code::*;
+
+    #[test]
+    fn first_through_gets_the_point() {
+        let keycode = [Up, Down, Left];
+        let mut game = VersusGame::new(2);
+        assert_eq!(game.press(Side::Left, Up, &keycode), VersusInput::Correct);
+        assert_eq!(game.press(Side::Right, Up, &keycode), VersusInput::Correct);
+        assert_eq!(game.press(Side::Right, Left, &keycode), VersusInput::Wrong);
+        assert!(game.get_player(Side::Right).pressed.is_empty());
+        assert_eq!(game.get_player(Side::Right).mistakes, 1);
+
+        assert_eq!(game.press(Side::Right, Up, &keycode), VersusInput::Correct);
+        assert_eq!(game.press(Side::Left, Down, &keycode), VersusInput::Correct);
+        assert_eq!(game.press(Side::Left, Left, &keycode), VersusInput::Point);
+        assert_eq!(game.get_player(Side::Left).score, 1);
+        assert_eq!(game.get_player(Side::Right).score, 0);
+        // Both start the next Stratagem from its first key
+        assert!(game.get_player(Side::Left).pressed.is_empty());
+        assert!(game.get_player(Side::Right).pressed.is_empty());
+        assert_eq!(game.get_winner(), None);
+    }
+
+    #[test]
+    fn winner() {
+        let keycode = [Right];
+        let mut game = VersusGame::new(2);
+        game.press(Side::Right, Right, &keycode);
+        game.press(Side::Left, Right, &keycode);
+        assert_eq!(game.get_winner(), None);
+        assert_eq!(game.press(Side::Right, Right, &keycode), VersusInput::Point);
+        assert_eq!(game.get_winner(), Some(Side::Right));
+    }
+}